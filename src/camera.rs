@@ -2,7 +2,12 @@ use na::Vector3;
 
 use crate::{
     color::Color,
-    world::{ray::Ray, World},
+    integrator::Integrator,
+    math::random_f32,
+    world::{
+        ray::{Ray, RayDifferentials},
+        World,
+    },
 };
 
 pub struct Camera {
@@ -10,6 +15,8 @@ pub struct Camera {
     vertical: Vector3<f32>,
     horizontal: Vector3<f32>,
     lower_left_corner: Vector3<f32>,
+    /// Interval `(open, close)` that ray times are sampled from.
+    shutter: (f32, f32),
 }
 
 impl Camera {
@@ -31,16 +38,41 @@ impl Camera {
             vertical,
             horizontal,
             lower_left_corner,
+            shutter: (0.0, 0.0),
         }
     }
 
-    pub fn trace(&self, u: f32, v: f32, depth: u32, world: &World) -> Color {
-        let cam_ray = Ray::new(
-            self.origin,
+    /// Keeps the shutter open from `open` to `close`, so objects moving in
+    /// that interval are blurred. Closed at time zero by default.
+    pub fn set_shutter(&mut self, open: f32, close: f32) {
+        self.shutter = (open, close);
+    }
+
+    /// Traces the ray through `(u, v)`, screen coordinates running from
+    /// zero to one. `spacing` is the distance to the neighbouring samples
+    /// along each axis, which sets the area texture lookups cover.
+    pub fn trace(
+        &self,
+        u: f32,
+        v: f32,
+        spacing: (f32, f32),
+        world: &World,
+        integrator: &impl Integrator,
+    ) -> Color {
+        let (open, close) = self.shutter;
+        let time = open + random_f32() * (close - open);
+        let direction = |u: f32, v: f32| {
             self.lower_left_corner + u * self.horizontal + v * self.vertical -
-                self.origin,
-        );
+                self.origin
+        };
+        let cam_ray = Ray::with_time(self.origin, direction(u, v), time)
+            .with_differentials(RayDifferentials {
+                x_origin: self.origin,
+                x_direction: direction(u + spacing.0, v),
+                y_origin: self.origin,
+                y_direction: direction(u, v + spacing.1),
+            });
 
-        cam_ray.trace(world, depth, Color::rgb(0.2, 0.5, 1.0))
+        integrator.li(&cam_ray, world)
     }
 }
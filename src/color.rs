@@ -34,13 +34,27 @@ impl Color {
     }
 
     #[inline(always)]
-    pub fn to_rgb(&self, gamma: f32) -> Rgb<u8> {
+    pub fn to_rgb(self, gamma: f32) -> Rgb<u8> {
         let r = (self.r.powf(gamma.recip()) * 255.999) as u8;
         let g = (self.g.powf(gamma.recip()) * 255.999) as u8;
         let b = (self.b.powf(gamma.recip()) * 255.999) as u8;
         Rgb([r, g, b])
     }
 
+    #[inline(always)]
+    pub fn to_rgb_f32(self) -> Rgb<f32> {
+        Rgb([self.r, self.g, self.b])
+    }
+
+    #[inline(always)]
+    pub fn from_rgb_f32(value: Rgb<f32>) -> Self {
+        Self {
+            r: value.0[0],
+            g: value.0[1],
+            b: value.0[2],
+        }
+    }
+
     #[inline(always)]
     pub fn from_rgb_24(value: Rgb<u8>, gamma: f32) -> Self {
         Self {
@@ -64,6 +78,22 @@ impl Color {
     pub fn b(&self) -> f32 {
         self.b
     }
+
+    #[inline(always)]
+    pub fn max_component(&self) -> f32 {
+        self.r.max(self.g).max(self.b)
+    }
+
+    /// Relative luminance with Rec. 709 weights.
+    #[inline(always)]
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    #[inline(always)]
+    pub fn is_black(&self) -> bool {
+        self.r == 0.0 && self.g == 0.0 && self.b == 0.0
+    }
 }
 
 impl Add<Color> for Color {
@@ -0,0 +1,47 @@
+use super::{Integrator, RAY_EPSILON};
+use crate::{
+    color::Color,
+    math::random_vector2,
+    sampling::cosine_hemisphere,
+    world::{object::Hit, ray::Ray, World},
+};
+
+/// Fraction of the hemisphere above the first hit that is not blocked
+/// within `distance`, weighted by cosine. Rays that miss everything are
+/// white.
+#[derive(Debug, Clone, Copy)]
+pub struct AmbientOcclusion {
+    distance: f32,
+}
+
+impl AmbientOcclusion {
+    pub fn new(distance: f32) -> Self {
+        Self { distance }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn li(&self, ray: &Ray, world: &World) -> Color {
+        let Some(hit_info) = world.hit(ray, RAY_EPSILON, f32::INFINITY) else {
+            return Color::gray(1.0);
+        };
+
+        let normal = if hit_info.normal.dot(&ray.direction()) > 0.0 {
+            -hit_info.normal
+        } else {
+            hit_info.normal
+        };
+        let direction = cosine_hemisphere(&normal, random_vector2());
+        let occlusion_ray =
+            Ray::with_time(hit_info.position, direction, ray.time());
+
+        if world
+            .hit(&occlusion_ray, RAY_EPSILON, self.distance)
+            .is_some()
+        {
+            Color::black()
+        } else {
+            Color::gray(1.0)
+        }
+    }
+}
@@ -0,0 +1,595 @@
+use na::Vector3;
+
+use super::{russian_roulette, Integrator, RAY_EPSILON};
+use crate::{
+    color::Color,
+    math::{random_index, random_vector2},
+    sampler::Footprint,
+    sampling::{cosine_hemisphere, cosine_hemisphere_pdf},
+    world::{
+        material::Scatter,
+        object::{Hit, HitInfo, SampleSurface},
+        ray::Ray,
+        World,
+    },
+};
+
+/// Bidirectional path tracer.
+///
+/// Traces one subpath from the camera and one from a randomly chosen light,
+/// then connects every prefix of one with every prefix of the other. The
+/// strategies are combined with the balance heuristic, computed from the
+/// forward and reverse area densities stored in each vertex as in Veach's
+/// thesis and pbrt.
+///
+/// Strategies that connect light subpaths directly to the camera (`t = 1`)
+/// would have to splat into other pixels and are left out of both the
+/// estimate and the weights. Mirrors, glass and rough metal can't be
+/// evaluated, so they are treated as specular vertices that are never
+/// connected to.
+#[derive(Debug, Clone, Copy)]
+pub struct BidirectionalIntegrator {
+    max_depth: u32,
+}
+
+impl BidirectionalIntegrator {
+    pub fn new(max_depth: u32) -> Self {
+        Self { max_depth }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VertexKind {
+    Camera,
+    Light,
+    Surface,
+}
+
+struct Vertex<'a> {
+    kind: VertexKind,
+    position: Vector3<f32>,
+    /// Geometric normal, zero for the camera.
+    normal: Vector3<f32>,
+    /// Direction towards the previous vertex of the subpath.
+    wo: Vector3<f32>,
+    /// Surface data for surface and light vertices.
+    hit_info: Option<HitInfo<'a>>,
+    /// Index of the object the vertex lies on.
+    object: Option<usize>,
+    /// Subpath throughput up to and including this vertex.
+    beta: Color,
+    /// Area density of sampling this vertex from the previous one.
+    pdf_fwd: f32,
+    /// Area density of sampling this vertex from the next one, i.e. when the
+    /// path is generated from the opposite end.
+    pdf_rev: f32,
+    delta: bool,
+}
+
+impl<'a> Vertex<'a> {
+    fn camera(ray: &Ray) -> Self {
+        Self {
+            kind: VertexKind::Camera,
+            position: ray.origin(),
+            normal: Vector3::zeros(),
+            wo: Vector3::zeros(),
+            hit_info: None,
+            object: None,
+            beta: Color::gray(1.0),
+            pdf_fwd: 1.0,
+            pdf_rev: 0.0,
+            delta: false,
+        }
+    }
+
+    fn light(
+        object: usize,
+        hit_info: HitInfo<'a>,
+        beta: Color,
+        pdf_fwd: f32,
+    ) -> Self {
+        Self {
+            kind: VertexKind::Light,
+            position: hit_info.position,
+            normal: hit_info.normal,
+            wo: hit_info.normal,
+            hit_info: Some(hit_info),
+            object: Some(object),
+            beta,
+            pdf_fwd,
+            pdf_rev: 0.0,
+            delta: false,
+        }
+    }
+
+    fn surface(
+        object: usize,
+        hit_info: HitInfo<'a>,
+        ray: &Ray,
+        beta: Color,
+        pdf_fwd: f32,
+    ) -> Self {
+        Self {
+            kind: VertexKind::Surface,
+            position: hit_info.position,
+            normal: hit_info.normal,
+            wo: -ray.direction().normalize(),
+            hit_info: Some(hit_info),
+            object: Some(object),
+            beta,
+            pdf_fwd,
+            pdf_rev: 0.0,
+            delta: false,
+        }
+    }
+
+    #[inline(always)]
+    fn direction_to(&self, other: &Vertex) -> Vector3<f32> {
+        (other.position - self.position).normalize()
+    }
+
+    /// Whether the vertex is a scattering event inside a medium, which has no
+    /// surface to foreshorten.
+    #[inline(always)]
+    fn in_medium(&self) -> bool {
+        self.hit_info
+            .as_ref()
+            .is_some_and(|hit_info| hit_info.material.is_volume())
+    }
+
+    #[inline(always)]
+    fn is_connectible(&self) -> bool {
+        self.kind != VertexKind::Camera && !self.delta
+    }
+
+    /// BSDF times cosine for light scattered from `next` towards the
+    /// previous vertex.
+    fn f(&self, next: &Vertex) -> Color {
+        let Some(hit_info) = &self.hit_info else {
+            return Color::black();
+        };
+        hit_info
+            .material
+            .eval(&self.wo, &self.direction_to(next), hit_info)
+    }
+
+    /// Radiance emitted from this vertex towards `to`.
+    fn le(&self, to: &Vertex) -> Color {
+        let Some(hit_info) = &self.hit_info else {
+            return Color::black();
+        };
+        let direction = self.direction_to(to);
+        let ray = Ray::new(to.position, -direction);
+        hit_info.material.emitted(&ray, hit_info)
+    }
+
+    /// Converts a solid angle density at this vertex into an area density
+    /// at `next`.
+    fn convert_density(&self, pdf: f32, next: &Vertex) -> f32 {
+        let to_next = next.position - self.position;
+        let dist_sq = to_next.magnitude_squared();
+        if dist_sq == 0.0 {
+            return 0.0;
+        }
+        let cos = if next.kind == VertexKind::Camera || next.in_medium() {
+            1.0
+        } else {
+            next.normal.dot(&to_next).abs() / dist_sq.sqrt()
+        };
+        pdf * cos / dist_sq
+    }
+
+    /// Area density at `next` of continuing the subpath from this vertex,
+    /// having arrived from `prev` (or the stored direction if `None`).
+    fn pdf(&self, prev: Option<&Vertex>, next: &Vertex) -> f32 {
+        match self.kind {
+            VertexKind::Camera => 0.0,
+            VertexKind::Light => self.pdf_light(next),
+            VertexKind::Surface => {
+                let Some(hit_info) = &self.hit_info else {
+                    return 0.0;
+                };
+                let wo = prev.map_or(self.wo, |prev| self.direction_to(prev));
+                let pdf = hit_info.material.pdf(
+                    &wo,
+                    &self.direction_to(next),
+                    hit_info,
+                );
+                self.convert_density(pdf, next)
+            }
+        }
+    }
+
+    /// Area density at `next` of the light subpath leaving this emitter.
+    fn pdf_light(&self, next: &Vertex) -> f32 {
+        let pdf = cosine_hemisphere_pdf(&self.normal, &self.direction_to(next));
+        self.convert_density(pdf, next)
+    }
+
+    /// Area density of a light subpath starting at this vertex.
+    fn pdf_light_origin(&self, world: &World) -> f32 {
+        match self.object {
+            Some(object) if world.lights().contains(&object) => {
+                let area = world.object(object).area();
+                (world.lights().len() as f32 * area).recip()
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+impl BidirectionalIntegrator {
+    /// Extends `path` by following scattering events from `ray`, where
+    /// `pdf` is the solid angle density of the ray direction. Returns the
+    /// sky radiance picked up if the subpath escapes.
+    fn random_walk<'a>(
+        &self,
+        world: &'a World,
+        mut ray: Ray,
+        mut beta: Color,
+        mut pdf_fwd: f32,
+        max_vertices: usize,
+        path: &mut Vec<Vertex<'a>>,
+    ) -> Color {
+        let mut throughput = Color::gray(1.0);
+
+        while path.len() < max_vertices {
+            let Some((object, hit_info)) =
+                world.hit_object(&ray, RAY_EPSILON, f32::INFINITY)
+            else {
+                return beta * world.sky(&ray.direction());
+            };
+
+            let mut vertex = Vertex::surface(object, hit_info, &ray, beta, 0.0);
+            let prev = path.last_mut().unwrap();
+            vertex.pdf_fwd = prev.convert_density(pdf_fwd, &vertex);
+            path.push(vertex);
+            if path.len() >= max_vertices {
+                break;
+            }
+
+            let vertex = path.last().unwrap();
+            let hit_info = vertex.hit_info.as_ref().unwrap();
+            let Some(scatter_info) = hit_info.material.scatter(&ray, hit_info)
+            else {
+                break;
+            };
+
+            let wi = scatter_info.scattered_ray.direction().normalize();
+            let (pdf, pdf_rev, delta) = match scatter_info.pdf {
+                Some(pdf) => {
+                    (
+                        pdf,
+                        hit_info.material.pdf(&wi, &vertex.wo, hit_info),
+                        false,
+                    )
+                }
+                None => (0.0, 0.0, true),
+            };
+
+            beta = beta * scatter_info.attenuation;
+            throughput = throughput * scatter_info.attenuation;
+            let bounces = path.len() as u32 - 1;
+            let Some(survival) = russian_roulette(bounces, &throughput) else {
+                break;
+            };
+            beta = beta / survival;
+            throughput = throughput / survival;
+
+            let [.., prev, vertex] = path.as_mut_slice() else {
+                unreachable!()
+            };
+            vertex.delta = delta;
+            prev.pdf_rev = vertex.convert_density(pdf_rev, prev);
+            pdf_fwd = pdf;
+            ray = scatter_info.scattered_ray;
+        }
+
+        Color::black()
+    }
+
+    fn light_subpath<'a>(
+        &self,
+        world: &'a World,
+        time: f32,
+    ) -> Vec<Vertex<'a>> {
+        let lights = world.lights();
+        let mut path = Vec::new();
+        if lights.is_empty() {
+            return path;
+        }
+
+        let object = lights[random_index(lights.len())];
+        let light = world.object(object);
+        let Some(sample) = light.sample_area(random_vector2()) else {
+            return path;
+        };
+        let pdf_pos = sample.pdf / lights.len() as f32;
+        let direction = cosine_hemisphere(&sample.normal, random_vector2());
+        let pdf_dir = cosine_hemisphere_pdf(&sample.normal, &direction);
+
+        let hit_info = HitInfo {
+            t: 0.0,
+            position: sample.position,
+            local_position: sample.position,
+            normal: sample.normal,
+            tangent: Vector3::zeros(),
+            bitangent: Vector3::zeros(),
+            u: sample.u,
+            v: sample.v,
+            footprint: Footprint::default(),
+            material: light.material(),
+        };
+        let ray = Ray::with_time(sample.position, direction, time);
+        let le = hit_info.material.emitted(
+            &Ray::new(sample.position + direction, -direction),
+            &hit_info,
+        );
+        if le.is_black() || pdf_pos == 0.0 || pdf_dir == 0.0 {
+            return path;
+        }
+
+        let cos = sample.normal.dot(&direction).abs();
+        let beta = le * (cos / (pdf_pos * pdf_dir));
+        path.push(Vertex::light(object, hit_info, le, pdf_pos));
+        self.random_walk(
+            world,
+            ray,
+            beta,
+            pdf_dir,
+            self.max_depth as usize + 1,
+            &mut path,
+        );
+        path
+    }
+
+    /// Samples a point on a light for connecting to `pt` directly, returning
+    /// the light vertex if it is visible at `time`.
+    fn sample_light_vertex<'a>(
+        &self,
+        world: &'a World,
+        pt: &Vertex,
+        time: f32,
+    ) -> Option<Vertex<'a>> {
+        let lights = world.lights();
+        if lights.is_empty() {
+            return None;
+        }
+        let object = lights[random_index(lights.len())];
+        let sample = world
+            .object(object)
+            .sample_from(&pt.position, random_vector2())?;
+        let pdf = sample.pdf / lights.len() as f32;
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        let wi = (sample.position - pt.position).normalize();
+        let shadow_ray = Ray::with_time(pt.position, wi, time);
+        let (hit_object, light_hit) =
+            world.hit_object(&shadow_ray, RAY_EPSILON, f32::INFINITY)?;
+        if hit_object != object {
+            return None;
+        }
+
+        let le = light_hit.material.emitted(&shadow_ray, &light_hit);
+        let mut vertex = Vertex::light(object, light_hit, le / pdf, 0.0);
+        vertex.pdf_fwd = vertex.pdf_light_origin(world);
+        Some(vertex)
+    }
+
+    fn visible(world: &World, a: &Vertex, b: &Vertex, time: f32) -> bool {
+        let to_b = b.position - a.position;
+        let dist = to_b.magnitude();
+        let ray = Ray::with_time(a.position, to_b / dist, time);
+        world.hit(&ray, RAY_EPSILON, dist - RAY_EPSILON).is_none()
+    }
+
+    /// Contribution of the strategy using `s` light and `t` camera
+    /// vertices traced at `time`, already weighted by MIS.
+    fn connect(
+        &self,
+        world: &World,
+        light: &[Vertex],
+        camera: &[Vertex],
+        s: usize,
+        t: usize,
+        time: f32,
+    ) -> Color {
+        let pt = &camera[t - 1];
+        let mut sampled = None;
+
+        let radiance = match s {
+            0 => pt.beta * pt.le(&camera[t - 2]),
+            1 => {
+                if !pt.is_connectible() {
+                    return Color::black();
+                }
+                let Some(vertex) = self.sample_light_vertex(world, pt, time)
+                else {
+                    return Color::black();
+                };
+                let radiance = pt.beta * pt.f(&vertex) * vertex.beta;
+                sampled = Some(vertex);
+                radiance
+            }
+            _ => {
+                let qs = &light[s - 1];
+                if !pt.is_connectible() || !qs.is_connectible() {
+                    return Color::black();
+                }
+                let radiance = qs.beta * qs.f(pt) * pt.f(qs) * pt.beta;
+                if radiance.is_black() || !Self::visible(world, qs, pt, time) {
+                    return Color::black();
+                }
+                radiance / (qs.position - pt.position).magnitude_squared()
+            }
+        };
+
+        if radiance.is_black() {
+            return radiance;
+        }
+        radiance * mis_weight(world, light, camera, sampled.as_ref(), s, t)
+    }
+}
+
+/// Balance heuristic weight of the strategy `(s, t)` against all other
+/// strategies with `t >= 2` that could have generated the same path.
+fn mis_weight(
+    world: &World,
+    light: &[Vertex],
+    camera: &[Vertex],
+    sampled: Option<&Vertex>,
+    s: usize,
+    t: usize,
+) -> f32 {
+    if s + t == 2 {
+        return 1.0;
+    }
+
+    let pt = &camera[t - 1];
+    // Emission that isn't from a light, such as from a medium, can only be
+    // found by hitting it.
+    if s == 0 && pt.pdf_light_origin(world) == 0.0 {
+        return 1.0;
+    }
+
+    let qs = match s {
+        0 => None,
+        1 => sampled,
+        _ => Some(&light[s - 1]),
+    };
+    let qs_minus = s.checked_sub(2).map(|i| &light[i]);
+    let pt_minus = &camera[t - 2];
+
+    // (pdf_fwd, pdf_rev, delta) of both subpaths, with the densities around
+    // the connection replaced by the ones this strategy implies.
+    let densities =
+        |vertex: &Vertex| (vertex.pdf_fwd, vertex.pdf_rev, vertex.delta);
+    let mut camera_pdfs: Vec<_> = camera[..t].iter().map(densities).collect();
+    let mut light_pdfs: Vec<_> =
+        light[..s.saturating_sub(1)].iter().map(densities).collect();
+    if let Some(qs) = qs {
+        light_pdfs.push((qs.pdf_fwd, pt.pdf(Some(pt_minus), qs), false));
+    }
+    camera_pdfs[t - 1].2 = false;
+
+    camera_pdfs[t - 1].1 = match qs {
+        Some(qs) => qs.pdf(qs_minus, pt),
+        None => pt.pdf_light_origin(world),
+    };
+    camera_pdfs[t - 2].1 = match qs {
+        Some(qs) => pt.pdf(Some(qs), pt_minus),
+        None => pt.pdf_light(pt_minus),
+    };
+    if let (Some(qs), Some(qs_minus)) = (qs, qs_minus) {
+        light_pdfs[s - 2].1 = qs.pdf(Some(pt), qs_minus);
+    }
+
+    let remap = |pdf: f32| if pdf == 0.0 { 1.0 } else { pdf };
+    let mut sum = 0.0;
+
+    let mut ratio = 1.0;
+    for i in (2..t).rev() {
+        ratio *= remap(camera_pdfs[i].1) / remap(camera_pdfs[i].0);
+        if !camera_pdfs[i].2 && !camera_pdfs[i - 1].2 {
+            sum += ratio;
+        }
+    }
+
+    let mut ratio = 1.0;
+    for i in (0..s).rev() {
+        ratio *= remap(light_pdfs[i].1) / remap(light_pdfs[i].0);
+        let prev_delta = i > 0 && light_pdfs[i - 1].2;
+        if !light_pdfs[i].2 && !prev_delta {
+            sum += ratio;
+        }
+    }
+
+    1.0 / (1.0 + sum)
+}
+
+impl Integrator for BidirectionalIntegrator {
+    fn li(&self, ray: &Ray, world: &World) -> Color {
+        let mut camera = vec![Vertex::camera(ray)];
+        let mut radiance = self.random_walk(
+            world,
+            *ray,
+            Color::gray(1.0),
+            1.0,
+            self.max_depth as usize + 2,
+            &mut camera,
+        );
+        // Both subpaths see the scene at the instant of the camera ray.
+        let light = self.light_subpath(world, ray.time());
+
+        for t in 2..=camera.len() {
+            for s in 0..=light.len() {
+                if s + t - 2 > self.max_depth as usize {
+                    break;
+                }
+                radiance = radiance +
+                    self.connect(world, &light, &camera, s, t, ray.time());
+            }
+        }
+
+        radiance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        integrator::{
+            path::PathIntegrator,
+            test_util::{diffuse, emitter, mean_radiance},
+        },
+        world::object::{sphere::Sphere, Object},
+    };
+
+    #[test]
+    fn converges_to_the_path_tracer() {
+        // A diffuse ball lit by a small spherical lamp, all inside a gray
+        // room that keeps the sky out.
+        let mut world = World::new();
+        world.add_object(Object::Sphere(Sphere::new(
+            Vector3::zeros(),
+            6.0,
+            diffuse(0.5),
+        )));
+        world.add_object(Object::Sphere(Sphere::new(
+            Vector3::zeros(),
+            1.0,
+            diffuse(0.7),
+        )));
+        world.add_object(Object::Sphere(Sphere::new(
+            Vector3::new(0.0, 3.0, 0.0),
+            0.5,
+            emitter(4.0),
+        )));
+        world.build_bvh();
+
+        // Towards the lit top of the ball, and past it onto the room.
+        for origin in
+            [Vector3::new(0.0, 0.6, -4.0), Vector3::new(0.0, -2.0, -4.0)]
+        {
+            let ray = Ray::new(origin, Vector3::z());
+            let path =
+                mean_radiance(&PathIntegrator::new(5), &world, &ray, 4_000, 1);
+            let bidirectional = mean_radiance(
+                &BidirectionalIntegrator::new(5),
+                &world,
+                &ray,
+                4_000,
+                2,
+            );
+            let (path, bidirectional) =
+                (path.luminance(), bidirectional.luminance());
+            assert!(
+                (bidirectional - path).abs() < 0.05 * path,
+                "{bidirectional} instead of {path} from {origin:?}"
+            );
+        }
+    }
+}
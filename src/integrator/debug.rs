@@ -0,0 +1,141 @@
+use std::str::FromStr;
+
+use super::{Integrator, RAY_EPSILON};
+use crate::{
+    color::Color,
+    world::{material::Scatter, ray::Ray, World},
+};
+
+/// Hit distance mapped to the middle of the false-color ramp.
+const DISTANCE_SCALE: f32 = 4.0;
+/// Traversal cost mapped to the top of the false-color ramp.
+const COST_SCALE: f32 = 64.0;
+/// Number of bounces mapped to the top of the false-color ramp, so that
+/// images stay comparable across `max_depth` settings.
+const BOUNCE_SCALE: f32 = 16.0;
+
+/// Quantity shown by the [`DebugIntegrator`].
+#[derive(Debug, Clone, Copy)]
+pub enum DebugMode {
+    /// Shading normal, remapped from `[-1, 1]` to `[0, 1]` per channel.
+    Normal,
+    /// Texture coordinates in the red and green channels.
+    Uv,
+    /// Distance to the first hit.
+    Distance,
+    /// A distinct color per top-level object. Objects own their materials
+    /// and nothing identifies equal ones, so surfaces of the same material
+    /// still get different colors.
+    Object,
+    /// Number of bounces the path makes before it escapes, is absorbed or
+    /// reaches `max_depth`.
+    Bounces,
+    /// Number of BVH nodes and objects tested for the camera ray.
+    TraversalCost,
+}
+
+/// Renders false-color images of scene data instead of radiance. The colors
+/// are meant to be stored as they are, without gamma correction.
+#[derive(Debug, Clone, Copy)]
+pub struct DebugIntegrator {
+    mode: DebugMode,
+    max_depth: u32,
+}
+
+impl DebugIntegrator {
+    pub fn new(mode: DebugMode, max_depth: u32) -> Self {
+        Self { mode, max_depth }
+    }
+
+    fn bounces(&self, ray: &Ray, world: &World) -> u32 {
+        let mut ray = *ray;
+        for depth in 0..self.max_depth {
+            let Some(hit_info) = world
+                .hit_object(&ray, RAY_EPSILON, f32::INFINITY)
+                .map(|(_, info)| info)
+            else {
+                return depth;
+            };
+            let Some(scatter_info) = hit_info.material.scatter(&ray, &hit_info)
+            else {
+                return depth;
+            };
+            ray = scatter_info.scattered_ray;
+        }
+        self.max_depth
+    }
+}
+
+impl FromStr for DebugMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(DebugMode::Normal),
+            "uv" => Ok(DebugMode::Uv),
+            "distance" => Ok(DebugMode::Distance),
+            "object" => Ok(DebugMode::Object),
+            "bounces" => Ok(DebugMode::Bounces),
+            "cost" => Ok(DebugMode::TraversalCost),
+            _ => {
+                Err(format!(
+                    "unknown debug mode `{s}`, expected one of: normal, uv, \
+                     distance, object, bounces, cost"
+                ))
+            }
+        }
+    }
+}
+
+/// Maps `x` in `[0, 1]` onto a blue-cyan-green-yellow-red ramp.
+fn false_color(x: f32) -> Color {
+    let x = x.clamp(0.0, 1.0);
+    let channel =
+        |center: f32| (1.5 - (4.0 * x - center).abs()).clamp(0.0, 1.0);
+    Color::rgb(channel(3.0), channel(2.0), channel(1.0))
+}
+
+/// Well-separated hue for an index, using the golden ratio.
+fn index_color(index: usize) -> Color {
+    let hue = (index as f32 * 0.618_034).fract();
+    let channel = |offset: f32| {
+        let k = (hue * 6.0 + offset) % 6.0;
+        1.0 - (k.min(4.0 - k).clamp(0.0, 1.0))
+    };
+    Color::rgb(channel(5.0), channel(3.0), channel(1.0))
+}
+
+impl Integrator for DebugIntegrator {
+    fn li(&self, ray: &Ray, world: &World) -> Color {
+        let mut cost = 0;
+        let hit = world.hit_object_counted(
+            ray,
+            RAY_EPSILON,
+            f32::INFINITY,
+            &mut cost,
+        );
+
+        match (self.mode, hit) {
+            (DebugMode::TraversalCost, _) => {
+                false_color(cost as f32 / COST_SCALE)
+            }
+            (DebugMode::Bounces, _) => {
+                false_color(self.bounces(ray, world) as f32 / BOUNCE_SCALE)
+            }
+            (_, None) => Color::black(),
+            (DebugMode::Normal, Some((_, hit_info))) => {
+                let normal = hit_info.material.shading_normal(&hit_info);
+                let n = 0.5 * normal.add_scalar(1.0);
+                Color::rgb(n.x, n.y, n.z)
+            }
+            (DebugMode::Uv, Some((_, hit_info))) => {
+                Color::rgb(hit_info.u, hit_info.v, 0.0)
+            }
+            (DebugMode::Distance, Some((_, hit_info))) => {
+                let t = hit_info.t * ray.direction().magnitude();
+                false_color(t / (t + DISTANCE_SCALE))
+            }
+            (DebugMode::Object, Some((index, _))) => index_color(index),
+        }
+    }
+}
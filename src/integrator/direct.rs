@@ -0,0 +1,77 @@
+use super::{light_pdf, sample_light, Integrator, RAY_EPSILON};
+use crate::{
+    color::Color,
+    sampling::power_heuristic,
+    world::{material::Scatter, ray::Ray, World},
+};
+
+/// Direct lighting only: light arriving at the first non-specular surface
+/// straight from an emitter or the sky. Specular chains are followed up to
+/// `max_depth` so mirrors and glass still show their surroundings.
+#[derive(Debug, Clone, Copy)]
+pub struct DirectIntegrator {
+    max_depth: u32,
+}
+
+impl DirectIntegrator {
+    pub fn new(max_depth: u32) -> Self {
+        Self { max_depth }
+    }
+}
+
+impl Integrator for DirectIntegrator {
+    fn li(&self, ray: &Ray, world: &World) -> Color {
+        let mut ray = *ray;
+        let mut throughput = Color::gray(1.0);
+        let mut radiance = Color::black();
+
+        for _ in 0..self.max_depth {
+            let Some((_, hit_info)) =
+                world.hit_object(&ray, RAY_EPSILON, f32::INFINITY)
+            else {
+                return radiance + throughput * world.sky(&ray.direction());
+            };
+
+            radiance = radiance +
+                throughput * hit_info.material.emitted(&ray, &hit_info);
+
+            let Some(scatter_info) = hit_info.material.scatter(&ray, &hit_info)
+            else {
+                return radiance;
+            };
+
+            let Some(pdf) = scatter_info.pdf else {
+                throughput = throughput * scatter_info.attenuation;
+                ray = scatter_info.scattered_ray;
+                continue;
+            };
+
+            // Combine light sampling with one BSDF sample, which is also the
+            // only way to pick up light from the sky.
+            let light = sample_light(world, &ray, &hit_info, true);
+            let scattered = scatter_info.scattered_ray;
+            let bsdf = match world.hit_object(
+                &scattered,
+                RAY_EPSILON,
+                f32::INFINITY,
+            ) {
+                Some((index, light_hit)) => {
+                    let emitted =
+                        light_hit.material.emitted(&scattered, &light_hit);
+                    if emitted.is_black() {
+                        Color::black()
+                    } else {
+                        let light_pdf = light_pdf(world, index, &scattered);
+                        emitted * power_heuristic(1, pdf, 1, light_pdf)
+                    }
+                }
+                None => world.sky(&scattered.direction()),
+            };
+
+            return radiance +
+                throughput * (light + scatter_info.attenuation * bsdf);
+        }
+
+        radiance
+    }
+}
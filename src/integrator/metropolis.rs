@@ -0,0 +1,371 @@
+use std::{
+    cell::RefCell,
+    f32::consts::PI,
+    num::NonZeroUsize,
+    rc::Rc,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{color::Color, math::with_sample_stream, scene::HdrImage};
+
+/// Largest `f32` below one.
+const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+/// A light path as seen by [`Metropolis`]: film coordinates in `[0, 1)^2`
+/// and the radiance carried to them.
+#[derive(Debug, Clone, Copy)]
+pub struct PathSample {
+    pub u: f32,
+    pub v: f32,
+    pub radiance: Color,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PrimarySample {
+    value: f32,
+    /// Iteration in which `value` was last changed.
+    modified: u64,
+    backup: f32,
+    modified_backup: u64,
+}
+
+/// Point in primary sample space, the unit hypercube of random numbers a
+/// path is built from, mutated as in Kelemen et al., "A Simple and Robust
+/// Mutation Strategy for the Metropolis Light Transport Algorithm" (2002).
+///
+/// Coordinates are created lazily when a path asks for them, and small
+/// mutations that a coordinate missed while unused are applied all at once.
+#[derive(Debug)]
+pub struct PrimarySampler {
+    samples: Vec<PrimarySample>,
+    rng: fastrand::Rng,
+    mutation_size: f32,
+    large_step_probability: f32,
+    iteration: u64,
+    large_step: bool,
+    last_large_step: u64,
+    index: usize,
+}
+
+impl PrimarySampler {
+    /// Creates a sampler whose first path uses independent random numbers
+    /// derived from `seed`.
+    pub fn new(
+        seed: u64,
+        mutation_size: f32,
+        large_step_probability: f32,
+    ) -> Self {
+        Self {
+            samples: Vec::new(),
+            rng: fastrand::Rng::with_seed(seed),
+            mutation_size,
+            large_step_probability,
+            iteration: 0,
+            large_step: true,
+            last_large_step: 0,
+            index: 0,
+        }
+    }
+
+    /// Proposes a mutation of the current point, which is applied to each
+    /// coordinate as it is read by [`sample`](Self::sample).
+    pub fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.rng.f32() < self.large_step_probability;
+        self.index = 0;
+    }
+
+    /// Returns the next coordinate of the proposed point.
+    pub fn sample(&mut self) -> f32 {
+        let index = self.index;
+        self.index += 1;
+        if index >= self.samples.len() {
+            self.samples.resize(index + 1, PrimarySample::default());
+        }
+
+        let sample = &mut self.samples[index];
+        if sample.modified < self.last_large_step {
+            sample.value = self.rng.f32();
+            sample.modified = self.last_large_step;
+        }
+
+        sample.backup = sample.value;
+        sample.modified_backup = sample.modified;
+        if self.large_step {
+            sample.value = self.rng.f32();
+        } else {
+            let steps = (self.iteration - sample.modified) as f32;
+            let sigma = self.mutation_size * steps.sqrt();
+            let value = sample.value + normal(&mut self.rng) * sigma;
+            sample.value = (value - value.floor()).min(ONE_MINUS_EPSILON);
+        }
+        sample.modified = self.iteration;
+
+        sample.value
+    }
+
+    /// Makes the proposed point the current one.
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    /// Restores the point from before the last proposal.
+    pub fn reject(&mut self) {
+        for sample in &mut self.samples {
+            if sample.modified == self.iteration {
+                sample.value = sample.backup;
+                sample.modified = sample.modified_backup;
+            }
+        }
+        self.iteration -= 1;
+    }
+}
+
+/// Standard normal sample, using the Box-Muller transform.
+fn normal(rng: &mut fastrand::Rng) -> f32 {
+    let radius = (-2.0 * (1.0 - rng.f32()).ln()).sqrt();
+    radius * (2.0 * PI * rng.f32()).cos()
+}
+
+/// Value the Markov chains sample proportionally to.
+fn importance(radiance: &Color) -> f32 {
+    let luminance = radiance.luminance();
+    if luminance.is_finite() && luminance > 0.0 {
+        luminance
+    } else {
+        0.0
+    }
+}
+
+/// Primary sample space Metropolis light transport.
+///
+/// `sample_path` builds a path from the random numbers returned by
+/// [`random_f32`](crate::math::random_f32), which are driven by one
+/// [`PrimarySampler`] per Markov chain. The chains are started from paths
+/// picked among `bootstrap_samples` independent ones, whose mean importance
+/// also sets the overall brightness of the image.
+#[derive(Debug, Clone, Copy)]
+pub struct Metropolis {
+    /// Standard deviation of a small mutation of one coordinate.
+    mutation_size: f32,
+    /// Probability of replacing the whole point by an independent one.
+    large_step_probability: f32,
+    bootstrap_samples: u32,
+    chains: u32,
+}
+
+impl Metropolis {
+    pub fn new(
+        mutation_size: f32,
+        large_step_probability: f32,
+        bootstrap_samples: u32,
+        chains: u32,
+    ) -> Self {
+        Self {
+            mutation_size,
+            large_step_probability,
+            bootstrap_samples: bootstrap_samples.max(1),
+            chains: chains.max(1),
+        }
+    }
+
+    /// Renders a `width` by `height` image with an average of
+    /// `mutations_per_pixel` mutations per pixel.
+    pub fn render(
+        &self,
+        width: u32,
+        height: u32,
+        mutations_per_pixel: u32,
+        sample_path: &(impl Fn() -> PathSample + Sync),
+    ) -> HdrImage {
+        let thread_count = std::thread::available_parallelism()
+            .map(NonZeroUsize::get)
+            .unwrap_or(1);
+
+        let weights = self.bootstrap(thread_count, sample_path);
+        let total: f32 = weights.iter().sum();
+        let brightness = total / self.bootstrap_samples as f32;
+        eprintln!("[RENDER] Bootstrap brightness: {brightness}");
+        if brightness <= 0.0 {
+            return HdrImage::new(width, height);
+        }
+
+        let cdf: Vec<f32> = weights
+            .iter()
+            .scan(0.0, |sum, weight| {
+                *sum += weight / total;
+                Some(*sum)
+            })
+            .collect();
+
+        let pixel_count = width as usize * height as usize;
+        let mutations = mutations_per_pixel as u64 * pixel_count as u64;
+        let next_chain = AtomicU32::new(0);
+
+        eprintln!("[RENDER] Workers: {thread_count}");
+        eprintln!("[RENDER] Chains: {}", self.chains);
+
+        let film = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..thread_count)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut film = vec![Color::black(); pixel_count];
+                        loop {
+                            let chain =
+                                next_chain.fetch_add(1, Ordering::Relaxed);
+                            if chain >= self.chains {
+                                break film;
+                            }
+                            let chain_mutations = mutations /
+                                self.chains as u64 +
+                                u64::from(
+                                    (chain as u64) <
+                                        mutations % self.chains as u64,
+                                );
+                            self.run_chain(
+                                &cdf,
+                                chain_mutations,
+                                sample_path,
+                                |sample, color| {
+                                    let x = ((sample.u * width as f32)
+                                        as usize)
+                                        .min(width as usize - 1);
+                                    let y = ((sample.v * height as f32)
+                                        as usize)
+                                        .min(height as usize - 1);
+                                    let pixel =
+                                        &mut film[y * width as usize + x];
+                                    *pixel = *pixel + color;
+                                },
+                            );
+                        }
+                    })
+                })
+                .collect();
+
+            workers.into_iter().fold(
+                vec![Color::black(); pixel_count],
+                |mut film, worker| {
+                    for (sum, color) in
+                        film.iter_mut().zip(worker.join().unwrap())
+                    {
+                        *sum = *sum + color;
+                    }
+                    film
+                },
+            )
+        });
+
+        let scale = brightness / mutations_per_pixel as f32;
+        HdrImage::from_fn(width, height, |x, y| {
+            (film[y as usize * width as usize + x as usize] * scale)
+                .to_rgb_f32()
+        })
+    }
+
+    /// Importance of the first path of the sampler seeded with each index.
+    fn bootstrap(
+        &self,
+        thread_count: usize,
+        sample_path: &(impl Fn() -> PathSample + Sync),
+    ) -> Vec<f32> {
+        let count = self.bootstrap_samples as u64;
+        let per_thread = count.div_ceil(thread_count as u64);
+
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..thread_count as u64)
+                .map(|thread| {
+                    scope.spawn(move || {
+                        let start = (thread * per_thread).min(count);
+                        let end = (start + per_thread).min(count);
+                        (start..end)
+                            .map(|seed| {
+                                let sampler = self.sampler(seed);
+                                let sample = with_sample_stream(
+                                    move || sampler.borrow_mut().sample(),
+                                    sample_path,
+                                );
+                                importance(&sample.radiance)
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        })
+    }
+
+    fn sampler(&self, seed: u64) -> Rc<RefCell<PrimarySampler>> {
+        Rc::new(RefCell::new(PrimarySampler::new(
+            seed,
+            self.mutation_size,
+            self.large_step_probability,
+        )))
+    }
+
+    /// Runs one Markov chain for `mutations` steps, starting from a bootstrap
+    /// path picked from `cdf`, and passes every weighted path to `splat`.
+    fn run_chain(
+        &self,
+        cdf: &[f32],
+        mutations: u64,
+        sample_path: &impl Fn() -> PathSample,
+        mut splat: impl FnMut(&PathSample, Color),
+    ) {
+        let mut rng = fastrand::Rng::new();
+        let seed = cdf.partition_point(|&p| p <= rng.f32()).min(cdf.len() - 1);
+
+        let sampler = self.sampler(seed as u64);
+        let stream = Rc::clone(&sampler);
+
+        with_sample_stream(
+            move || stream.borrow_mut().sample(),
+            || {
+                // Replays the bootstrap path with the same seed.
+                let mut current = sample_path();
+                let mut current_importance = importance(&current.radiance);
+
+                for _ in 0..mutations {
+                    sampler.borrow_mut().start_iteration();
+                    let proposed = sample_path();
+                    let proposed_importance = importance(&proposed.radiance);
+
+                    let accept = if current_importance > 0.0 {
+                        (proposed_importance / current_importance).min(1.0)
+                    } else {
+                        1.0
+                    };
+
+                    // Both paths contribute in expectation over acceptance.
+                    if accept > 0.0 && proposed_importance > 0.0 {
+                        splat(
+                            &proposed,
+                            proposed.radiance * (accept / proposed_importance),
+                        );
+                    }
+                    if accept < 1.0 {
+                        splat(
+                            &current,
+                            current.radiance *
+                                ((1.0 - accept) / current_importance),
+                        );
+                    }
+
+                    if rng.f32() < accept {
+                        current = proposed;
+                        current_importance = proposed_importance;
+                        sampler.borrow_mut().accept();
+                    } else {
+                        sampler.borrow_mut().reject();
+                    }
+                }
+            },
+        );
+    }
+}
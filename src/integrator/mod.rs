@@ -0,0 +1,359 @@
+pub mod ambient_occlusion;
+pub mod bidirectional;
+pub mod debug;
+pub mod direct;
+pub mod metropolis;
+pub mod path;
+pub mod photon;
+pub mod whitted;
+
+use std::str::FromStr;
+
+use self::debug::DebugMode;
+use crate::{
+    color::Color,
+    math::{random_f32, random_index, random_vector2},
+    sampling::power_heuristic,
+    world::{
+        material::Scatter,
+        object::{HitInfo, SampleSurface},
+        ray::Ray,
+        World,
+    },
+};
+
+/// Minimum hit distance for secondary rays, to avoid self-intersection.
+pub const RAY_EPSILON: f32 = 0.001;
+
+/// Number of bounces a path makes before Russian roulette may terminate it.
+pub const RUSSIAN_ROULETTE_DEPTH: u32 = 3;
+
+/// Light transport algorithm used to estimate the radiance along camera rays.
+pub trait Integrator: Sync {
+    /// Estimates the radiance arriving at the origin of `ray` from its
+    /// direction.
+    fn li(&self, ray: &Ray, world: &World) -> Color;
+}
+
+/// Integrator selection as stored in [`RenderInfo`](crate::scene::RenderInfo).
+#[derive(Debug, Clone, Copy)]
+pub enum IntegratorKind {
+    Path,
+    /// Path tracing with caustics estimated from a photon map of `photons`
+    /// photons, gathered within `radius`.
+    PathCaustics {
+        photons: u32,
+        radius: f32,
+    },
+    /// Probabilistic progressive photon mapping: every sample uses a fresh
+    /// photon map, and the gather radius shrinks after each one so that the
+    /// average converges. `alpha` in `(0, 1)` controls how fast.
+    ProgressivePhotonMapping {
+        photons: u32,
+        radius: f32,
+        alpha: f32,
+    },
+    Direct,
+    AmbientOcclusion {
+        distance: f32,
+    },
+    Whitted,
+    Bidirectional,
+    /// Primary sample space Metropolis light transport over the path tracer,
+    /// see [`Metropolis`](metropolis::Metropolis).
+    Metropolis {
+        mutation_size: f32,
+        large_step_probability: f32,
+        bootstrap_samples: u32,
+        chains: u32,
+    },
+    Debug(DebugMode),
+}
+
+impl FromStr for IntegratorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(IntegratorKind::Path),
+            "path+caustics" => {
+                Ok(IntegratorKind::PathCaustics {
+                    photons: 200_000,
+                    radius: 0.05,
+                })
+            }
+            "ppm" => {
+                Ok(IntegratorKind::ProgressivePhotonMapping {
+                    photons: 100_000,
+                    radius: 0.1,
+                    alpha: 0.7,
+                })
+            }
+            "direct" => Ok(IntegratorKind::Direct),
+            "ao" => Ok(IntegratorKind::AmbientOcclusion { distance: 1.0 }),
+            "whitted" => Ok(IntegratorKind::Whitted),
+            "bdpt" => Ok(IntegratorKind::Bidirectional),
+            "mlt" => {
+                Ok(IntegratorKind::Metropolis {
+                    mutation_size: 0.01,
+                    large_step_probability: 0.3,
+                    bootstrap_samples: 100_000,
+                    chains: 1000,
+                })
+            }
+            _ => {
+                if let Some(mode) = s.strip_prefix("debug:") {
+                    return mode.parse().map(IntegratorKind::Debug);
+                }
+                Err(format!(
+                    "unknown integrator `{s}`, expected one of: path, \
+                     path+caustics, ppm, direct, ao, whitted, bdpt, mlt, \
+                     debug:<mode>"
+                ))
+            }
+        }
+    }
+}
+
+/// Russian roulette for a path with the product of all attenuations so far
+/// as its `throughput`, after `depth` bounces.
+///
+/// From [`RUSSIAN_ROULETTE_DEPTH`] bounces on the path is terminated with a
+/// probability based on its throughput, which keeps large `max_depth` values
+/// cheap since low-energy paths die out early. Returns `None` if the path
+/// ends, otherwise the probability it survived with, which its throughput
+/// has to be divided by so the estimate stays unbiased.
+pub fn russian_roulette(depth: u32, throughput: &Color) -> Option<f32> {
+    if depth < RUSSIAN_ROULETTE_DEPTH {
+        return Some(1.0);
+    }
+    let survival = throughput.max_component().min(0.95);
+    (random_f32() < survival).then_some(survival)
+}
+
+/// Solid angle density with which [`sample_light`] picks the direction of
+/// `ray` towards the light at `index`. Emitters that can't be sampled aren't
+/// among [`World::lights`] and are only found by rays, so their density is
+/// zero.
+pub fn light_pdf(world: &World, index: usize, ray: &Ray) -> f32 {
+    let lights = world.lights();
+    if !lights.contains(&index) {
+        return 0.0;
+    }
+    world
+        .object(index)
+        .pdf_from(&ray.origin(), &ray.direction().normalize()) /
+        lights.len() as f32
+}
+
+/// Next event estimation: samples a point on a randomly chosen light and
+/// returns its unoccluded contribution at `hit_info`, as seen along `ray`.
+///
+/// With `mis` the result is weighted against BSDF sampling using the power
+/// heuristic, so BSDF-sampled hits on lights have to be weighted with
+/// [`light_pdf`] accordingly.
+pub fn sample_light(
+    world: &World,
+    ray: &Ray,
+    hit_info: &HitInfo,
+    mis: bool,
+) -> Color {
+    let lights = world.lights();
+    if lights.is_empty() {
+        return Color::black();
+    }
+
+    let index = lights[random_index(lights.len())];
+    let Some(sample) = world
+        .object(index)
+        .sample_from(&hit_info.position, random_vector2())
+    else {
+        return Color::black();
+    };
+    let pdf = sample.pdf / lights.len() as f32;
+    if pdf <= 0.0 {
+        return Color::black();
+    }
+
+    let wo = -ray.direction().normalize();
+    let wi = (sample.position - hit_info.position).normalize();
+    let f = hit_info.material.eval(&wo, &wi, hit_info);
+    if f.is_black() {
+        return Color::black();
+    }
+
+    // The shadow ray has to reach the sampled light before any other
+    // surface; tracing it also gives the emission at the exact point. Media
+    // in between attenuate it.
+    let shadow_ray = Ray::with_time(hit_info.position, wi, ray.time());
+    let Some((hit_index, light_hit)) =
+        world.hit_surface(&shadow_ray, RAY_EPSILON, f32::INFINITY)
+    else {
+        return Color::black();
+    };
+    if hit_index != index {
+        return Color::black();
+    }
+    let transmittance =
+        world.transmittance(&shadow_ray, RAY_EPSILON, light_hit.t);
+    if transmittance == 0.0 {
+        return Color::black();
+    }
+
+    let emitted =
+        transmittance * light_hit.material.emitted(&shadow_ray, &light_hit);
+    let weight = if mis {
+        power_heuristic(1, pdf, 1, hit_info.material.pdf(&wo, &wi, hit_info))
+    } else {
+        1.0
+    };
+
+    f * emitted * (weight / pdf)
+}
+
+/// Estimates shared by the tests of the individual integrators.
+#[cfg(test)]
+mod test_util {
+    use super::Integrator;
+    use crate::{
+        color::Color,
+        math::with_sample_stream,
+        sampler::Sampler2D,
+        world::{
+            material::{emissive::Emissive, lambertian::Lambertian, Material},
+            ray::Ray,
+            World,
+        },
+    };
+
+    pub fn diffuse(albedo: f32) -> Material {
+        Material::Lambertian(Lambertian::new(Sampler2D::Static(Color::gray(
+            albedo,
+        ))))
+    }
+
+    pub fn emitter(strength: f32) -> Material {
+        Material::Emissive(Emissive::new(
+            Sampler2D::Static(Color::gray(1.0)),
+            Sampler2D::Static(strength),
+        ))
+    }
+
+    /// Mean of `samples` estimates of the radiance along `ray`, all drawn
+    /// from one stream of random numbers seeded with `seed`.
+    pub fn mean_radiance(
+        integrator: &(impl Integrator + ?Sized),
+        world: &World,
+        ray: &Ray,
+        samples: u32,
+        seed: u64,
+    ) -> Color {
+        let mut rng = fastrand::Rng::with_seed(seed);
+        with_sample_stream(
+            move || rng.f32(),
+            || {
+                let sum = (0..samples).fold(Color::black(), |sum, _| {
+                    let radiance = integrator.li(ray, world);
+                    assert!(
+                        radiance.r().is_finite() &&
+                            radiance.g().is_finite() &&
+                            radiance.b().is_finite(),
+                        "non-finite radiance {radiance:?}"
+                    );
+                    sum + radiance
+                });
+                sum / samples as f32
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Vector3;
+
+    use super::{
+        direct::DirectIntegrator,
+        path::PathIntegrator,
+        test_util::{diffuse, emitter, mean_radiance},
+        *,
+    };
+    use crate::world::{
+        material::Material,
+        object::{
+            cuboid::Cuboid,
+            moving::{Motion, Moving},
+            quad::Quad,
+            sphere::Sphere,
+            Object,
+        },
+    };
+
+    /// A floor under `lamp`, which lights it without being sampled.
+    fn lamp_over_floor(lamp: Object) -> World {
+        let mut world = World::new();
+        world.add_object(Object::Quad(Quad::new(
+            Vector3::new(-5.0, 0.0, -5.0),
+            Vector3::z() * 10.0,
+            Vector3::x() * 10.0,
+            diffuse(0.5),
+        )));
+        world.add_object(lamp);
+        world.build_bvh();
+        assert!(world.lights().is_empty());
+        world
+    }
+
+    /// Asserts that the path and direct integrators find the floor below
+    /// the lamp of `lit` brighter than in `unlit`, at the instant `time`.
+    /// The estimates also assert that every sample is finite.
+    fn assert_lit(lit: &World, unlit: &World, time: f32) {
+        let ray = Ray::with_time(
+            Vector3::new(0.0, 1.0, -3.0),
+            Vector3::new(0.0, -1.0, 3.0),
+            time,
+        );
+        for integrator in [
+            &PathIntegrator::new(5) as &dyn Integrator,
+            &DirectIntegrator::new(5),
+        ] {
+            let lit = mean_radiance(integrator, lit, &ray, 2_000, 1);
+            let unlit = mean_radiance(integrator, unlit, &ray, 2_000, 1);
+            assert!(lit.luminance() > unlit.luminance() + 0.2, "{lit:?}");
+        }
+    }
+
+    #[test]
+    fn unsampled_emitters_light_the_scene() {
+        let lamp = |material: Material| {
+            Object::Cuboid(Cuboid::new(
+                Vector3::new(-0.5, 2.0, -0.5),
+                Vector3::new(0.5, 2.5, 0.5),
+                material,
+            ))
+        };
+        let lit = lamp_over_floor(lamp(emitter(10.0)));
+        let unlit = lamp_over_floor(lamp(diffuse(0.5)));
+        assert_lit(&lit, &unlit, 0.0);
+    }
+
+    #[test]
+    fn moving_emitters_light_the_scene() {
+        // Passing over the floor in front of the camera during the shutter
+        // interval.
+        let lamp = |material: Material| {
+            Object::Moving(Moving::new(
+                Object::Sphere(Sphere::new(Vector3::zeros(), 0.5, material)),
+                Motion::Linear {
+                    from: Vector3::new(-0.5, 2.25, 0.0),
+                    to: Vector3::new(0.5, 2.25, 0.0),
+                },
+            ))
+        };
+        let lit = lamp_over_floor(lamp(emitter(10.0)));
+        let unlit = lamp_over_floor(lamp(diffuse(0.5)));
+        for time in [0.0, 0.5, 1.0] {
+            assert_lit(&lit, &unlit, time);
+        }
+    }
+}
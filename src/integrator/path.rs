@@ -0,0 +1,187 @@
+use super::{
+    light_pdf, photon::PhotonMap, russian_roulette, sample_light, Integrator,
+    RAY_EPSILON,
+};
+use crate::{
+    color::Color,
+    sampling::power_heuristic,
+    world::{material::Scatter, ray::Ray, World},
+};
+
+/// Unidirectional path tracer with next event estimation, ending paths by
+/// [`russian_roulette`].
+///
+/// With a caustic photon map, light reaching diffuse surfaces through
+/// specular bounces is estimated from photons instead. Paths that hit an
+/// emitter through a specular chain after a diffuse bounce are then skipped
+/// so that light isn't counted twice.
+#[derive(Debug, Clone, Copy)]
+pub struct PathIntegrator<'a> {
+    max_depth: u32,
+    caustics: Option<&'a PhotonMap>,
+}
+
+impl<'a> PathIntegrator<'a> {
+    pub fn new(max_depth: u32) -> Self {
+        Self {
+            max_depth,
+            caustics: None,
+        }
+    }
+
+    pub fn with_caustics(max_depth: u32, caustics: &'a PhotonMap) -> Self {
+        Self {
+            max_depth,
+            caustics: Some(caustics),
+        }
+    }
+}
+
+impl Integrator for PathIntegrator<'_> {
+    fn li(&self, ray: &Ray, world: &World) -> Color {
+        let mut ray = *ray;
+        let mut throughput = Color::gray(1.0);
+        let mut radiance = Color::black();
+        // Density of the last scattering event, `None` for camera rays and
+        // specular bounces which light sampling can't produce.
+        let mut scatter_pdf = None;
+        // Whether the path went through specular bounces since its last
+        // diffuse bounce, so emitters it finds are covered by caustics.
+        let mut caustic_path = false;
+
+        for depth in 0..self.max_depth {
+            let Some((index, hit_info)) =
+                world.hit_object(&ray, RAY_EPSILON, f32::INFINITY)
+            else {
+                return radiance + throughput * world.sky(&ray.direction());
+            };
+
+            let emitted = hit_info.material.emitted(&ray, &hit_info);
+            if !emitted.is_black() && !caustic_path {
+                let weight = match scatter_pdf {
+                    Some(pdf) => {
+                        power_heuristic(
+                            1,
+                            pdf,
+                            1,
+                            light_pdf(world, index, &ray),
+                        )
+                    }
+                    None => 1.0,
+                };
+                radiance = radiance + throughput * emitted * weight;
+            }
+
+            let Some(scatter_info) = hit_info.material.scatter(&ray, &hit_info)
+            else {
+                return radiance;
+            };
+
+            if scatter_info.pdf.is_some() {
+                radiance = radiance +
+                    throughput * sample_light(world, &ray, &hit_info, true);
+                if let Some(caustics) = self.caustics {
+                    radiance = radiance +
+                        throughput * caustics.estimate(&ray, &hit_info);
+                }
+            }
+
+            if self.caustics.is_some() {
+                caustic_path = match scatter_info.pdf {
+                    Some(_) => false,
+                    None => caustic_path || scatter_pdf.is_some(),
+                };
+            }
+
+            throughput = throughput * scatter_info.attenuation;
+            scatter_pdf = scatter_info.pdf;
+            ray = scatter_info.scattered_ray;
+
+            let Some(survival) = russian_roulette(depth, &throughput) else {
+                return radiance;
+            };
+            throughput = throughput / survival;
+        }
+
+        radiance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use na::Vector3;
+
+    use super::*;
+    use crate::{
+        integrator::{
+            test_util::{diffuse, mean_radiance},
+            RUSSIAN_ROULETTE_DEPTH,
+        },
+        math::with_sample_stream,
+        world::{
+            material::volume::{PhaseFunction, Volume},
+            object::{medium::ConstantMedium, sphere::Sphere, Object},
+        },
+    };
+
+    fn white_sphere() -> Object {
+        Object::Sphere(Sphere::new(Vector3::zeros(), 1.0, diffuse(1.0)))
+    }
+
+    #[test]
+    fn russian_roulette_keeps_the_furnace_mean() {
+        // A dense medium that scatters everything, under a uniform white
+        // sky, returns exactly the sky however often light bounces in it.
+        let mut world = World::new();
+        world.set_sky_color(Color::gray(1.0));
+        world.add_object(Object::Medium(ConstantMedium::new(
+            white_sphere(),
+            Volume::new(0.0, 5.0, Color::gray(1.0), PhaseFunction::Isotropic),
+        )));
+        world.build_bvh();
+
+        let ray = Ray::new(Vector3::new(0.0, 0.0, -3.0), Vector3::z());
+        let mean = mean_radiance(
+            &PathIntegrator::new(10_000),
+            &world,
+            &ray,
+            20_000,
+            1,
+        );
+        assert!((mean.luminance() - 1.0).abs() < 0.03, "{mean:?}");
+    }
+
+    #[test]
+    fn russian_roulette_terminates_closed_paths() {
+        // Inside a white sphere no path ever escapes, so without roulette
+        // every one would run for all of `max_depth` bounces.
+        let mut world = World::new();
+        world.add_object(white_sphere());
+        world.build_bvh();
+
+        let draws = Rc::new(Cell::new(0u64));
+        let mut rng = fastrand::Rng::with_seed(2);
+        let counter = Rc::clone(&draws);
+        let paths = 1_000;
+        let integrator = PathIntegrator::new(1_000_000);
+        with_sample_stream(
+            move || {
+                counter.set(counter.get() + 1);
+                rng.f32()
+            },
+            || {
+                let ray = Ray::new(Vector3::zeros(), Vector3::z());
+                for _ in 0..paths {
+                    assert!(integrator.li(&ray, &world).is_black());
+                }
+            },
+        );
+
+        // Each bounce draws a direction and a survival decision, and paths
+        // survive a bounce with probability 0.95 at most.
+        let bounces = draws.get() as f32 / (3 * paths) as f32;
+        assert!(bounces < 2.0 * (RUSSIAN_ROULETTE_DEPTH as f32 + 20.0));
+    }
+}
@@ -0,0 +1,450 @@
+use std::f32::consts::PI;
+
+use na::{Vector2, Vector3};
+
+use super::{Integrator, RAY_EPSILON};
+use crate::{
+    color::Color,
+    math::{orthonormal_basis, random_f32, random_index, random_vector2},
+    sampler::Footprint,
+    sampling::{
+        concentric_disk, cosine_hemisphere, cosine_hemisphere_pdf,
+        uniform_sphere, uniform_sphere_pdf,
+    },
+    world::{
+        material::Scatter,
+        object::{HitInfo, SampleSurface},
+        ray::Ray,
+        World,
+    },
+};
+
+/// Photons are terminated after this many bounces even if Russian roulette
+/// keeps them alive.
+const MAX_PHOTON_DEPTH: u32 = 64;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Photon {
+    pub position: Vector3<f32>,
+    /// Direction the photon arrived from, pointing away from the surface.
+    pub direction: Vector3<f32>,
+    pub power: Color,
+}
+
+/// Photons stored at diffuse surfaces, kept in a balanced kd-tree for
+/// fixed-radius density estimation.
+#[derive(Debug)]
+pub struct PhotonMap {
+    /// Photons in kd-tree order: the median of every range is the node
+    /// splitting it, with the lower half before and the upper half after it.
+    photons: Vec<Photon>,
+    /// Split axis of the node stored at the same index.
+    axes: Vec<u8>,
+    radius: f32,
+}
+
+/// Which photon paths are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PhotonPaths {
+    /// Only photons that reached a diffuse surface through one or more
+    /// specular bounces, straight from an emitter.
+    Caustic,
+    /// Photons at every diffuse bounce, including light from the sky.
+    Global,
+}
+
+impl PhotonMap {
+    /// Traces `count` photons from the emissive objects and keeps the ones
+    /// forming caustics. Estimates gather photons within `radius`.
+    pub fn caustic(world: &World, count: u32, radius: f32) -> Self {
+        Self::trace(world, count, radius, PhotonPaths::Caustic)
+    }
+
+    /// Traces `count` photons from the emissive objects and the sky and keeps
+    /// them at every diffuse surface they hit. Estimates gather photons within
+    /// `radius`.
+    pub fn global(world: &World, count: u32, radius: f32) -> Self {
+        Self::trace(world, count, radius, PhotonPaths::Global)
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    fn trace(
+        world: &World,
+        count: u32,
+        radius: f32,
+        paths: PhotonPaths,
+    ) -> Self {
+        let lights = world.lights();
+        let sky = match paths {
+            PhotonPaths::Global => world.bounding_sphere(),
+            PhotonPaths::Caustic => None,
+        };
+        // Split photons evenly between the sky and all emitters together.
+        let sky_probability = match (sky.is_some(), lights.is_empty()) {
+            (false, _) => 0.0,
+            (true, true) => 1.0,
+            (true, false) => 0.5,
+        };
+
+        let mut photons = Vec::new();
+        if lights.is_empty() && sky.is_none() {
+            return Self::build(photons, radius);
+        }
+
+        for _ in 0..count {
+            let emitted = if random_f32() < sky_probability {
+                let (center, sky_radius) = sky.unwrap();
+                let (ray, power) = emit_from_sky(world, center, sky_radius);
+                Some((ray, power / sky_probability))
+            } else {
+                emit_from_light(world)
+                    .map(|(ray, power)| (ray, power / (1.0 - sky_probability)))
+            };
+            if let Some((ray, power)) = emitted {
+                trace_photon(
+                    world,
+                    ray,
+                    power / count as f32,
+                    paths,
+                    &mut photons,
+                );
+            }
+        }
+
+        Self::build(photons, radius)
+    }
+
+    fn build(mut photons: Vec<Photon>, radius: f32) -> Self {
+        let mut axes = vec![0; photons.len()];
+        build_kd_tree(&mut photons, &mut axes);
+        Self {
+            photons,
+            axes,
+            radius,
+        }
+    }
+
+    /// Calls `f` for every photon within `radius` of `position`.
+    pub fn for_each_within(
+        &self,
+        position: &Vector3<f32>,
+        radius: f32,
+        mut f: impl FnMut(&Photon),
+    ) {
+        self.search(0, self.photons.len(), position, radius * radius, &mut f);
+    }
+
+    fn search(
+        &self,
+        start: usize,
+        end: usize,
+        position: &Vector3<f32>,
+        radius_sq: f32,
+        f: &mut impl FnMut(&Photon),
+    ) {
+        if start >= end {
+            return;
+        }
+        let mid = start + (end - start) / 2;
+        let photon = &self.photons[mid];
+        let axis = self.axes[mid] as usize;
+
+        if (photon.position - position).magnitude_squared() <= radius_sq {
+            f(photon);
+        }
+
+        let offset = position[axis] - photon.position[axis];
+        let (near, far) = if offset < 0.0 {
+            ((start, mid), (mid + 1, end))
+        } else {
+            ((mid + 1, end), (start, mid))
+        };
+        self.search(near.0, near.1, position, radius_sq, f);
+        if offset * offset <= radius_sq {
+            self.search(far.0, far.1, position, radius_sq, f);
+        }
+    }
+
+    /// Radiance reflected at `hit_info` along `ray`, estimated from the
+    /// photon density around the hit.
+    pub fn estimate(&self, ray: &Ray, hit_info: &HitInfo) -> Color {
+        if hit_info.material.is_volume() {
+            return Color::black();
+        }
+        let wo = -ray.direction().normalize();
+        let mut flux = Color::black();
+
+        self.for_each_within(&hit_info.position, self.radius, |photon| {
+            let cos = hit_info.normal.dot(&photon.direction).abs();
+            if cos > 0.0 {
+                let f =
+                    hit_info.material.eval(&wo, &photon.direction, hit_info);
+                flux = flux + f * photon.power / cos;
+            }
+        });
+
+        flux / (PI * self.radius * self.radius)
+    }
+}
+
+/// Reorders `photons` into an implicit balanced kd-tree, splitting each range
+/// at its median along the axis of largest extent.
+fn build_kd_tree(photons: &mut [Photon], axes: &mut [u8]) {
+    if photons.len() <= 1 {
+        return;
+    }
+
+    let (min, max) = photons.iter().fold(
+        (
+            Vector3::repeat(f32::INFINITY),
+            Vector3::repeat(f32::NEG_INFINITY),
+        ),
+        |(min, max), photon| {
+            (min.inf(&photon.position), max.sup(&photon.position))
+        },
+    );
+    let axis = (max - min).imax();
+
+    let mid = photons.len() / 2;
+    photons.select_nth_unstable_by(mid, |a, b| {
+        a.position[axis].total_cmp(&b.position[axis])
+    });
+    axes[mid] = axis as u8;
+
+    let (lower, upper) = photons.split_at_mut(mid);
+    let (lower_axes, upper_axes) = axes.split_at_mut(mid);
+    build_kd_tree(lower, lower_axes);
+    build_kd_tree(&mut upper[1..], &mut upper_axes[1..]);
+}
+
+/// Starts a photon on a random emitter. Returns the photon ray and its power
+/// for a single photon.
+fn emit_from_light(world: &World) -> Option<(Ray, Color)> {
+    let lights = world.lights();
+    let object = lights[random_index(lights.len())];
+    let light = world.object(object);
+
+    let sample = light.sample_area(random_vector2())?;
+    let pdf_pos = sample.pdf / lights.len() as f32;
+    let direction = cosine_hemisphere(&sample.normal, random_vector2());
+    let pdf_dir = cosine_hemisphere_pdf(&sample.normal, &direction);
+    if pdf_pos <= 0.0 || pdf_dir <= 0.0 {
+        return None;
+    }
+
+    let hit_info = HitInfo {
+        t: 0.0,
+        position: sample.position,
+        local_position: sample.position,
+        normal: sample.normal,
+        tangent: Vector3::zeros(),
+        bitangent: Vector3::zeros(),
+        u: sample.u,
+        v: sample.v,
+        footprint: Footprint::default(),
+        material: light.material(),
+    };
+    let le = light.material().emitted(
+        &Ray::new(sample.position + direction, -direction),
+        &hit_info,
+    );
+    let cos = sample.normal.dot(&direction);
+
+    Some((
+        Ray::new(sample.position, direction),
+        le * (cos / (pdf_pos * pdf_dir)),
+    ))
+}
+
+/// Starts a photon from the sky, entering the sphere around the scene at
+/// `center` with `radius` from a uniformly chosen direction.
+fn emit_from_sky(
+    world: &World,
+    center: Vector3<f32>,
+    radius: f32,
+) -> (Ray, Color) {
+    let to_sky = uniform_sphere(random_vector2());
+    let (tangent, bitangent) = orthonormal_basis(&to_sky);
+    let disk: Vector2<f32> = concentric_disk(random_vector2()) * radius;
+    let origin =
+        center + radius * to_sky + disk.x * tangent + disk.y * bitangent;
+
+    let pdf_dir = uniform_sphere_pdf();
+    let pdf_pos = (PI * radius * radius).recip();
+
+    (
+        Ray::new(origin, -to_sky),
+        world.sky(&to_sky) / (pdf_dir * pdf_pos),
+    )
+}
+
+fn trace_photon(
+    world: &World,
+    mut ray: Ray,
+    mut power: Color,
+    paths: PhotonPaths,
+    photons: &mut Vec<Photon>,
+) {
+    // Caustic photons are stored at their first diffuse hit, so any earlier
+    // bounce was specular.
+    let mut bounced = false;
+
+    for _ in 0..MAX_PHOTON_DEPTH {
+        let Some(hit_info) = world
+            .hit_object(&ray, RAY_EPSILON, f32::INFINITY)
+            .map(|(_, info)| info)
+        else {
+            return;
+        };
+        let Some(scatter_info) = hit_info.material.scatter(&ray, &hit_info)
+        else {
+            return;
+        };
+
+        if scatter_info.pdf.is_some() {
+            // Densities are estimated over surfaces only, so photons
+            // scattered inside media aren't stored.
+            let photon = (!hit_info.material.is_volume()).then(|| {
+                Photon {
+                    position: hit_info.position,
+                    direction: -ray.direction().normalize(),
+                    power,
+                }
+            });
+            match paths {
+                PhotonPaths::Caustic => {
+                    if bounced {
+                        photons.extend(photon);
+                    }
+                    return;
+                }
+                PhotonPaths::Global => photons.extend(photon),
+            }
+        }
+        bounced = true;
+
+        // Survive with the probability of the scattered power, so surviving
+        // photons keep roughly the same power.
+        let survival = scatter_info.attenuation.max_component().min(1.0);
+        if survival <= 0.0 || random_f32() >= survival {
+            return;
+        }
+        power = power * scatter_info.attenuation / survival;
+        ray = scatter_info.scattered_ray;
+    }
+}
+
+/// Photon mapping integrator for progressive photon mapping: camera paths
+/// are followed through specular bounces and media and end with a density
+/// estimate at the first diffuse surface.
+#[derive(Debug, Clone, Copy)]
+pub struct PhotonMappingIntegrator<'a> {
+    max_depth: u32,
+    photon_map: &'a PhotonMap,
+}
+
+impl<'a> PhotonMappingIntegrator<'a> {
+    pub fn new(max_depth: u32, photon_map: &'a PhotonMap) -> Self {
+        Self {
+            max_depth,
+            photon_map,
+        }
+    }
+}
+
+impl Integrator for PhotonMappingIntegrator<'_> {
+    fn li(&self, ray: &Ray, world: &World) -> Color {
+        let mut ray = *ray;
+        let mut throughput = Color::gray(1.0);
+        let mut radiance = Color::black();
+
+        for _ in 0..self.max_depth {
+            let Some((_, hit_info)) =
+                world.hit_object(&ray, RAY_EPSILON, f32::INFINITY)
+            else {
+                return radiance + throughput * world.sky(&ray.direction());
+            };
+
+            radiance = radiance +
+                throughput * hit_info.material.emitted(&ray, &hit_info);
+
+            let Some(scatter_info) = hit_info.material.scatter(&ray, &hit_info)
+            else {
+                return radiance;
+            };
+
+            if scatter_info.pdf.is_some() && !hit_info.material.is_volume() {
+                return radiance +
+                    throughput * self.photon_map.estimate(&ray, &hit_info);
+            }
+
+            throughput = throughput * scatter_info.attenuation;
+            ray = scatter_info.scattered_ray;
+        }
+
+        radiance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_matches_a_brute_force_scan() {
+        let mut rng = fastrand::Rng::with_seed(3);
+        let mut point = || {
+            Vector3::new(rng.f32(), rng.f32(), rng.f32()) * 4.0 -
+                Vector3::repeat(2.0)
+        };
+        // Scattered photons, plus a lattice whose coordinates tie on every
+        // split axis. The power tells the photons apart.
+        let positions: Vec<_> = (0..2_000)
+            .map(|_| point())
+            .chain((0..125).map(|i| {
+                Vector3::new(
+                    (i % 5) as f32,
+                    (i / 5 % 5) as f32,
+                    (i / 25) as f32,
+                ) * 0.5
+            }))
+            .collect();
+        let photons = positions
+            .iter()
+            .enumerate()
+            .map(|(i, &position)| {
+                Photon {
+                    position,
+                    direction: Vector3::z(),
+                    power: Color::gray(i as f32),
+                }
+            })
+            .collect();
+        let photon_map = PhotonMap::build(photons, 0.1);
+
+        for query in (0..20).map(|_| point()).chain([Vector3::repeat(1.0)]) {
+            for radius in [0.05, 0.3, 0.5, 1.0] {
+                let mut found = Vec::new();
+                photon_map.for_each_within(&query, radius, |photon| {
+                    found.push(photon.power.r() as usize);
+                });
+                found.sort_unstable();
+
+                let expected: Vec<_> = (0..positions.len())
+                    .filter(|&i| {
+                        (positions[i] - query).magnitude_squared() <=
+                            radius * radius
+                    })
+                    .collect();
+                assert_eq!(found, expected, "{query:?} within {radius}");
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::f32::consts::PI;
+
+use super::{sample_light, Integrator, RAY_EPSILON};
+use crate::{
+    color::Color,
+    world::{material::Scatter, ray::Ray, World},
+};
+
+/// Whitted-style ray tracing: specular surfaces are followed recursively,
+/// diffuse surfaces only receive light sampled from emitters plus the sky
+/// above their normal as an unshadowed ambient term.
+#[derive(Debug, Clone, Copy)]
+pub struct Whitted {
+    max_depth: u32,
+}
+
+impl Whitted {
+    pub fn new(max_depth: u32) -> Self {
+        Self { max_depth }
+    }
+}
+
+impl Integrator for Whitted {
+    fn li(&self, ray: &Ray, world: &World) -> Color {
+        let mut ray = *ray;
+        let mut throughput = Color::gray(1.0);
+        let mut radiance = Color::black();
+
+        for _ in 0..self.max_depth {
+            let Some((_, hit_info)) =
+                world.hit_object(&ray, RAY_EPSILON, f32::INFINITY)
+            else {
+                return radiance + throughput * world.sky(&ray.direction());
+            };
+
+            radiance = radiance +
+                throughput * hit_info.material.emitted(&ray, &hit_info);
+
+            let Some(scatter_info) = hit_info.material.scatter(&ray, &hit_info)
+            else {
+                return radiance;
+            };
+
+            if scatter_info.pdf.is_some() {
+                let wo = -ray.direction().normalize();
+                let normal = if hit_info.normal.dot(&wo) < 0.0 {
+                    -hit_info.normal
+                } else {
+                    hit_info.normal
+                };
+                let ambient = PI *
+                    hit_info.material.eval(&wo, &normal, &hit_info) *
+                    world.sky(&normal);
+                let direct = sample_light(world, &ray, &hit_info, false);
+                return radiance + throughput * (direct + ambient);
+            }
+
+            throughput = throughput * scatter_info.attenuation;
+            ray = scatter_info.scattered_ray;
+        }
+
+        radiance
+    }
+}
@@ -0,0 +1,11 @@
+extern crate nalgebra as na;
+
+pub mod camera;
+pub mod color;
+pub mod integrator;
+pub mod math;
+pub mod procedural;
+pub mod sampler;
+pub mod sampling;
+pub mod scene;
+pub mod world;
@@ -1,27 +1,17 @@
-extern crate nalgebra as na;
-
-mod camera;
-mod color;
-mod math;
-mod sampler;
-mod scene;
-mod world;
-
-use na::Vector3;
-use scene::{RenderInfo, Scene};
-
-use crate::{
+use nalgebra::Vector3;
+use raytracer::{
     camera::Camera,
     color::Color,
+    integrator::IntegratorKind,
     sampler::{Image2DSampler, Sampler2D},
+    scene::{RenderInfo, Scene},
     world::{
         material::{metal::Metallic, Material},
-        object::{sphere::Sphere, Object},
+        object::{plane::Plane, sphere::Sphere, Object},
         World,
     },
 };
 
-
 fn make_world() -> World {
     // let earth_image =
     //     Image::load_from_png(File::open("res/earthmap.png").unwrap()).unwrap();
@@ -36,10 +26,8 @@ fn make_world() -> World {
         Vector3::new(0.0, 0.0, -2.5),
         1.0,
         Material::Metallic(Metallic::new(
-            Sampler2D::Image(Image2DSampler::new(
-                image1,
-            )),
-            1.0,
+            Sampler2D::Image(Image2DSampler::new(image1)),
+            Sampler2D::Static(1.0),
         )),
     )));
     world.add_object(Object::Sphere(Sphere::new(
@@ -47,7 +35,7 @@ fn make_world() -> World {
         0.5,
         Material::Metallic(Metallic::new(
             Sampler2D::Static(Color::gray(1.0)),
-            0.0,
+            Sampler2D::Static(0.0),
         )),
     )));
 
@@ -56,16 +44,16 @@ fn make_world() -> World {
         0.5,
         Material::Metallic(Metallic::new(
             Sampler2D::Static(Color::gray(1.0)),
-            0.5,
+            Sampler2D::Static(0.5),
         )),
     )));
 
-    world.add_object(Object::Sphere(Sphere::new(
-        Vector3::new(0.0, -1001.0, 0.0),
-        1000.0,
+    world.add_object(Object::Plane(Plane::new(
+        Vector3::new(0.0, -1.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
         Material::Metallic(Metallic::new(
             Sampler2D::Static(Color::rgb(0.5, 0.5, 0.8)),
-            1.0,
+            Sampler2D::Static(1.0),
         )),
     )));
     world
@@ -77,13 +65,33 @@ fn main() {
 
     let scene = Scene::new(world, camera);
 
-    let image = scene.render(RenderInfo {
+    let mut info = RenderInfo {
         width: 1280,
         height: 720,
         max_depth: 256,
         gamma: 2.2,
         samples: 128,
-    });
+        integrator: IntegratorKind::Path,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--integrator" => {
+                let value = args.next().unwrap_or_default();
+                info.integrator = value.parse().unwrap_or_else(|err| {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                });
+            }
+            _ => {
+                eprintln!("unknown argument `{arg}`");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let image = scene.render(info);
 
     image.save("out/render.png").unwrap();
     println!("Output saved to out/render.png");
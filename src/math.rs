@@ -1,21 +1,293 @@
-use na::Vector3;
+use std::cell::{Cell, RefCell};
+
+use na::{Vector2, Vector3};
+
+use crate::sampling::{uniform_hemisphere, uniform_sphere};
+
+thread_local! {
+    /// Replaces `fastrand` as the source of [`random_f32`] on this thread,
+    /// see [`with_sample_stream`].
+    static SAMPLE_STREAM: RefCell<Option<Box<dyn FnMut() -> f32>>> =
+        const { RefCell::new(None) };
+    /// Whether [`SAMPLE_STREAM`] is set, so that [`random_f32`] only borrows
+    /// it while a stream is in use.
+    static STREAM_ACTIVE: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Uniform random number in `[0, 1)`. Everything that samples light paths
+/// draws from this, so the numbers can be replaced by
+/// [`with_sample_stream`].
+#[inline(always)]
+pub fn random_f32() -> f32 {
+    if !STREAM_ACTIVE.get() {
+        return fastrand::f32();
+    }
+    SAMPLE_STREAM.with(|stream| {
+        match stream.borrow_mut().as_mut() {
+            Some(next) => next(),
+            None => fastrand::f32(),
+        }
+    })
+}
+
+/// Uniform random index in `0..len`. `len` must not be zero.
+pub fn random_index(len: usize) -> usize {
+    ((random_f32() * len as f32) as usize).min(len - 1)
+}
+
+/// Number in `[0, 1)` that looks random but is fixed for `p`, for choices
+/// that have to come out the same every time a point is looked at.
+pub fn hash_f32(p: &Vector3<f32>) -> f32 {
+    let mut hash = 0x9E37_79B9_u32;
+    for x in p.iter() {
+        hash = (hash ^ x.to_bits()).wrapping_mul(0x85EB_CA6B);
+        hash ^= hash >> 13;
+    }
+    hash = hash.wrapping_mul(0xC2B2_AE35);
+    hash ^= hash >> 16;
+    (hash >> 8) as f32 / (1 << 24) as f32
+}
+
+/// Runs `f` with [`random_f32`] on this thread returning the numbers produced
+/// by `stream` instead of independent ones. The previous stream is restored
+/// afterwards, also when `f` panics.
+pub fn with_sample_stream<T>(
+    stream: impl FnMut() -> f32 + 'static,
+    f: impl FnOnce() -> T,
+) -> T {
+    let previous =
+        SAMPLE_STREAM.with(|current| current.replace(Some(Box::new(stream))));
+    STREAM_ACTIVE.set(true);
+    let _restore = StreamGuard { previous };
+    f()
+}
+
+/// Puts back the stream replaced by [`with_sample_stream`] when dropped.
+struct StreamGuard {
+    previous: Option<Box<dyn FnMut() -> f32>>,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        STREAM_ACTIVE.set(previous.is_some());
+        SAMPLE_STREAM.with(|current| current.replace(previous));
+    }
+}
+
+#[inline(always)]
+pub fn random_vector2() -> Vector2<f32> {
+    Vector2::new(random_f32(), random_f32())
+}
 
 pub fn random_vector3_in_unit_sphere() -> Vector3<f32> {
-    loop {
-        let x = fastrand::f32();
-        let y = fastrand::f32();
-        let z = fastrand::f32();
+    uniform_sphere(random_vector2()) * random_f32().cbrt()
+}
+
+pub fn random_vector3_in_unit_hemisphere(
+    normal: &Vector3<f32>,
+) -> Vector3<f32> {
+    uniform_hemisphere(normal, random_vector2()) * random_f32().cbrt()
+}
+
+/// Builds two unit vectors that, together with `n`, form a right-handed
+/// orthonormal basis. `n` must be normalized.
+///
+/// Uses the branchless construction from Duff et al., "Building an
+/// Orthonormal Basis, Revisited" (JCGT 2017).
+pub fn orthonormal_basis(n: &Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let sign = 1.0f32.copysign(n.z);
+    let a = -1.0 / (sign + n.z);
+    let b = n.x * n.y * a;
+    let tangent =
+        Vector3::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x);
+    let bitangent = Vector3::new(b, sign + n.y * n.y * a, -n.y);
+    (tangent, bitangent)
+}
+
+/// Real roots `(t0, t1)` with `t0 <= t1` of `a t² + b t + c`, computed in a
+/// way that avoids cancellation. A single root of a linear equation is
+/// returned twice.
+pub fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+    if q == 0.0 {
+        return Some((0.0, 0.0));
+    }
+    let (t0, t1) = (q / a, c / q);
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+/// Largest real root of the monic cubic `x³ + a x² + b x + c`.
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    let q = (a * a - 3.0 * b) / 9.0;
+    let r = (2.0 * a * a * a - 9.0 * a * b + 27.0 * c) / 54.0;
+    let shift = a / 3.0;
+
+    if r * r < q * q * q {
+        // Three real roots, the largest is the one for k = 0.
+        let theta = (r / (q * q * q).sqrt()).clamp(-1.0, 1.0).acos();
+        -2.0 * q.sqrt() * (theta / 3.0).cos() - shift
+    } else {
+        let s = -(r.abs() + (r * r - q * q * q).sqrt()).cbrt().copysign(r);
+        let t = if s == 0.0 { 0.0 } else { q / s };
+        s + t - shift
+    }
+}
+
+/// Real roots of `a x⁴ + b x³ + c x² + d x + e` in ascending order, as an
+/// array whose first `n` entries are set along with `n`.
+///
+/// Uses Ferrari's method on the depressed quartic and polishes each root
+/// with Newton's method, which keeps the roots accurate enough for ray
+/// intersections with tori.
+pub fn solve_quartic(
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+) -> ([f64; 4], usize) {
+    let mut roots = [0.0; 4];
+    let mut count = 0;
+    if a == 0.0 {
+        return (roots, count);
+    }
+
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+    // Substituting x = y - b/4 gives y⁴ + p y² + q y + r.
+    let shift = b / 4.0;
+    let p = c - 6.0 * shift * shift;
+    let q = d - 2.0 * c * shift + 8.0 * shift * shift * shift;
+    let r = e - d * shift + c * shift * shift - 3.0 * shift.powi(4);
+
+    let mut push_quadratic = |a: f64, b: f64, c: f64| {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return;
+        }
+        let root = discriminant.sqrt();
+        for y in [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)] {
+            roots[count] = y - shift;
+            count += 1;
+        }
+    };
 
-        if x * x + y * y + z * z <= 1.0 {
-            return Vector3::new(x, y, z);
+    if q.abs() < 1e-12 {
+        // Biquadratic: solve for y² first.
+        let discriminant = p * p - 4.0 * r;
+        if discriminant >= 0.0 {
+            let root = discriminant.sqrt();
+            for z in [(-p - root) / 2.0, (-p + root) / 2.0] {
+                if z >= 0.0 {
+                    push_quadratic(1.0, 0.0, -z);
+                }
+            }
+        }
+    } else {
+        // A positive root m of the resolvent cubic splits the quartic into
+        // (y² + s y + u)(y² - s y + v) with s = √(2m).
+        let m = largest_cubic_root(p, 0.25 * p * p - r, -0.125 * q * q);
+        if m > 0.0 {
+            let s = (2.0 * m).sqrt();
+            let half = 0.5 * p + m;
+            let offset = q / (2.0 * s);
+            push_quadratic(1.0, s, half - offset);
+            push_quadratic(1.0, -s, half + offset);
         }
     }
+
+    for root in &mut roots[..count] {
+        for _ in 0..2 {
+            let x = *root;
+            let f = (((x + b) * x + c) * x + d) * x + e;
+            let df = ((4.0 * x + 3.0 * b) * x + 2.0 * c) * x + d;
+            if df != 0.0 {
+                *root = x - f / df;
+            }
+        }
+    }
+    roots[..count].sort_by(f64::total_cmp);
+    (roots, count)
 }
 
-pub fn random_vector3_in_unit_hemisphere() -> Vector3<f32> {
-    let mut sph = random_vector3_in_unit_sphere();
-    sph.x *= sph.x.signum();
-    sph.y *= sph.y.signum();
-    sph.z *= sph.z.signum();
-    return sph;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(found: ([f64; 4], usize), expected: &[f64]) {
+        let (roots, count) = found;
+        assert_eq!(count, expected.len(), "{roots:?}");
+        for (root, expected) in roots[..count].iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-9, "{root} != {expected}");
+        }
+    }
+
+    #[test]
+    fn hash_is_uniform_over_nearby_points() {
+        let n = 100_000;
+        let values: Vec<f32> = (0..n)
+            .map(|i| hash_f32(&Vector3::new(i as f32 * 1e-3, 1.0, 2.0)))
+            .collect();
+        assert!(values.iter().all(|x| (0.0..1.0).contains(x)));
+        let below_quarter = values.iter().filter(|&&x| x < 0.25).count();
+        assert!((below_quarter as f32 / n as f32 - 0.25).abs() < 0.01);
+        assert_eq!(hash_f32(&Vector3::x()), hash_f32(&Vector3::x()));
+    }
+
+    #[test]
+    fn sample_streams_are_restored() {
+        let outer = with_sample_stream(
+            || 0.25,
+            || {
+                let inner = with_sample_stream(|| 0.75, random_f32);
+                (inner, random_f32())
+            },
+        );
+        assert_eq!(outer, (0.75, 0.25));
+
+        let panicked = std::panic::catch_unwind(|| {
+            with_sample_stream(|| 0.5, || panic!("stream user failed"))
+        });
+        assert!(panicked.is_err());
+        assert!((0..64).any(|_| random_f32() != 0.5));
+    }
+
+    #[test]
+    fn quadratic_roots_are_sorted() {
+        assert_eq!(solve_quadratic(1.0, -3.0, 2.0), Some((1.0, 2.0)));
+        assert_eq!(solve_quadratic(-1.0, 3.0, -2.0), Some((1.0, 2.0)));
+        assert_eq!(solve_quadratic(0.0, 2.0, -4.0), Some((2.0, 2.0)));
+        assert_eq!(solve_quadratic(1.0, 0.0, 1.0), None);
+    }
+
+    #[test]
+    fn quartic_finds_distinct_roots() {
+        // (x + 2)(x - 0.5)(x - 1)(x - 3)
+        assert_roots(solve_quartic(1.0, -2.5, -4.0, 8.5, -3.0), &[
+            -2.0, 0.5, 1.0, 3.0,
+        ]);
+        // 2 (x - 1)(x - 2)(x² + 1)
+        assert_roots(solve_quartic(2.0, -6.0, 6.0, -6.0, 4.0), &[1.0, 2.0]);
+        assert_roots(solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0), &[]);
+    }
+
+    #[test]
+    fn quartic_handles_biquadratics() {
+        // (x² - 1)(x² - 4)
+        assert_roots(solve_quartic(1.0, 0.0, -5.0, 0.0, 4.0), &[
+            -2.0, -1.0, 1.0, 2.0,
+        ]);
+    }
 }
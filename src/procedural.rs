@@ -0,0 +1,324 @@
+use std::f32::consts::TAU;
+
+use na::Vector3;
+
+use crate::{
+    color::Color,
+    sampler::{Sample2D, Sample3D},
+};
+
+/// Gradient noise after Ken Perlin's improved noise, hashed with a seeded
+/// permutation of the lattice.
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    /// Permutation of 0..256, repeated once so that chained lookups don't
+    /// need wrapping.
+    permutation: Box<[u8; 512]>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut values: Vec<u8> = (0..=255).collect();
+        fastrand::Rng::with_seed(seed).shuffle(&mut values);
+        Self {
+            permutation: Box::new(std::array::from_fn(|i| values[i % 256])),
+        }
+    }
+
+    /// Pseudo-random byte for the lattice cell at `(x, y, z)`.
+    #[inline(always)]
+    fn hash(&self, x: i32, y: i32, z: i32) -> u8 {
+        let p = &self.permutation;
+        let h = p[(x & 255) as usize] as usize + (y & 255) as usize;
+        let h = p[h] as usize + (z & 255) as usize;
+        p[h]
+    }
+
+    /// Noise at `p` in `[-1, 1]`, zero at the lattice points.
+    pub fn noise(&self, p: &Vector3<f32>) -> f32 {
+        let cell = p.map(f32::floor);
+        let f = p - cell;
+        let (x, y, z) = (cell.x as i32, cell.y as i32, cell.z as i32);
+        let w = f.map(|t| t * t * t * (t * (t * 6.0 - 15.0) + 10.0));
+
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            let offset = f - Vector3::new(dx as f32, dy as f32, dz as f32);
+            gradient(self.hash(x + dx, y + dy, z + dz), &offset)
+        };
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        lerp(
+            lerp(
+                lerp(corner(0, 0, 0), corner(1, 0, 0), w.x),
+                lerp(corner(0, 1, 0), corner(1, 1, 0), w.x),
+                w.y,
+            ),
+            lerp(
+                lerp(corner(0, 0, 1), corner(1, 0, 1), w.x),
+                lerp(corner(0, 1, 1), corner(1, 1, 1), w.x),
+                w.y,
+            ),
+            w.z,
+        )
+    }
+
+    /// Fractal Brownian motion: `octaves` layers of noise, each at twice the
+    /// frequency and half the amplitude of the previous one, in about
+    /// `[-1, 1]`.
+    pub fn fbm(&self, p: &Vector3<f32>, octaves: u32) -> f32 {
+        self.octaves(p, octaves, |noise| noise)
+    }
+
+    /// Like [`Perlin::fbm`] with the absolute value of every layer, which
+    /// creases the noise where it crosses zero. In about `[0, 1]`.
+    pub fn turbulence(&self, p: &Vector3<f32>, octaves: u32) -> f32 {
+        self.octaves(p, octaves, f32::abs)
+    }
+
+    fn octaves(
+        &self,
+        p: &Vector3<f32>,
+        octaves: u32,
+        layer: impl Fn(f32) -> f32,
+    ) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 0.5;
+        let mut p = *p;
+        for _ in 0..octaves.max(1) {
+            sum += amplitude * layer(self.noise(&p));
+            amplitude *= 0.5;
+            p *= 2.0;
+        }
+        // The layers' amplitudes add up to just under one.
+        sum / (1.0 - amplitude * 2.0)
+    }
+
+    /// Distance from `p` to the closest of a set of points scattered one per
+    /// lattice cell, and a value in `[0, 1)` identifying that point.
+    pub fn worley(&self, p: &Vector3<f32>) -> (f32, f32) {
+        let cell = p.map(f32::floor);
+        let (x, y, z) = (cell.x as i32, cell.y as i32, cell.z as i32);
+        let mut closest = (f32::INFINITY, 0.0);
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let h = self.hash(x + dx, y + dy, z + dz);
+                    let jitter = |salt: usize| {
+                        self.permutation[h as usize + salt] as f32 / 256.0
+                    };
+                    let point = cell +
+                        Vector3::new(
+                            dx as f32 + jitter(1),
+                            dy as f32 + jitter(2),
+                            dz as f32 + jitter(3),
+                        );
+                    let distance = (point - p).magnitude();
+                    if distance < closest.0 {
+                        closest = (distance, h as f32 / 256.0);
+                    }
+                }
+            }
+        }
+        closest
+    }
+}
+
+/// Dot product of `offset` with one of twelve gradients towards the edges of
+/// a cube, picked by `hash`.
+#[inline(always)]
+fn gradient(hash: u8, offset: &Vector3<f32>) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { offset.x } else { offset.y };
+    let v = match h {
+        0..=3 => offset.y,
+        12 | 14 => offset.x,
+        _ => offset.z,
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// Scalar pattern of a [`Procedural`] texture, in `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    /// Alternating unit cells.
+    Checker,
+    /// Fractal noise, see [`Perlin::fbm`].
+    Noise { octaves: u32 },
+    /// Creased fractal noise, see [`Perlin::turbulence`].
+    Turbulence { octaves: u32 },
+    /// Stripes across x, bent by turbulence of the given strength.
+    Marble { octaves: u32, distortion: f32 },
+    /// Rings around the z axis, `rings` per unit, bent by noise of the given
+    /// strength.
+    Wood { rings: f32, distortion: f32 },
+    /// Distance to the closest of a set of scattered points, growing from
+    /// zero at the points, see [`Perlin::worley`].
+    Worley,
+    /// A random value for each cell around the scattered points, see
+    /// [`Perlin::worley`].
+    Voronoi,
+}
+
+/// Texture computed from a [`Pattern`] instead of looked up in an image.
+///
+/// As a 2D texture, the pattern lies on the plane z = 0 with texture
+/// coordinates as x and y. Colors blend from `low` to `high` as the pattern
+/// goes from zero to one, and as a scalar map the pattern itself is the
+/// value.
+#[derive(Debug, Clone)]
+pub struct Procedural {
+    pattern: Pattern,
+    noise: Perlin,
+    /// Pattern cells per unit.
+    frequency: f32,
+    low: Color,
+    high: Color,
+}
+
+impl Procedural {
+    /// Black to white `pattern` with a cell per unit.
+    pub fn new(pattern: Pattern) -> Self {
+        Self {
+            pattern,
+            noise: Perlin::new(0),
+            frequency: 1.0,
+            low: Color::black(),
+            high: Color::gray(1.0),
+        }
+    }
+
+    pub fn with_frequency(mut self, frequency: f32) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    pub fn with_colors(mut self, low: Color, high: Color) -> Self {
+        self.low = low;
+        self.high = high;
+        self
+    }
+
+    /// Picks another variant of the noise based patterns.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.noise = Perlin::new(seed);
+        self
+    }
+
+    /// Pattern at `p`, in `[0, 1]`.
+    pub fn value(&self, p: &Vector3<f32>) -> f32 {
+        let p = p * self.frequency;
+        let value = match self.pattern {
+            Pattern::Checker => {
+                let sum = p.x.floor() + p.y.floor() + p.z.floor();
+                sum.rem_euclid(2.0)
+            }
+            Pattern::Noise { octaves } => {
+                0.5 + 0.5 * self.noise.fbm(&p, octaves)
+            }
+            Pattern::Turbulence { octaves } => {
+                self.noise.turbulence(&p, octaves)
+            }
+            Pattern::Marble {
+                octaves,
+                distortion,
+            } => {
+                let turbulence = self.noise.turbulence(&p, octaves);
+                0.5 + 0.5 * (TAU * (p.x + distortion * turbulence)).sin()
+            }
+            Pattern::Wood { rings, distortion } => {
+                let radius = p.x.hypot(p.y) + distortion * self.noise.noise(&p);
+                (rings * radius).rem_euclid(1.0)
+            }
+            Pattern::Worley => self.noise.worley(&p).0,
+            Pattern::Voronoi => self.noise.worley(&p).1,
+        };
+        value.clamp(0.0, 1.0)
+    }
+
+    /// Color of the pattern at `p`.
+    pub fn color(&self, p: &Vector3<f32>) -> Color {
+        let t = self.value(p);
+        self.low * (1.0 - t) + self.high * t
+    }
+}
+
+impl Sample2D<Color> for Procedural {
+    fn sample(&self, u: f32, v: f32) -> Color {
+        self.color(&Vector3::new(u, v, 0.0))
+    }
+}
+
+impl Sample2D<f32> for Procedural {
+    fn sample(&self, u: f32, v: f32) -> f32 {
+        self.value(&Vector3::new(u, v, 0.0))
+    }
+}
+
+impl Sample3D<Color> for Procedural {
+    fn sample(&self, position: &Vector3<f32>) -> Color {
+        self.color(position)
+    }
+}
+
+impl Sample3D<f32> for Procedural {
+    fn sample(&self, position: &Vector3<f32>) -> f32 {
+        self.value(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_point(rng: &mut fastrand::Rng) -> Vector3<f32> {
+        Vector3::new(rng.f32(), rng.f32(), rng.f32()) * 100.0 -
+            Vector3::repeat(50.0)
+    }
+
+    #[test]
+    fn noise_is_bounded_and_zero_on_the_lattice() {
+        let perlin = Perlin::new(7);
+        let mut rng = fastrand::Rng::with_seed(1);
+        for _ in 0..10_000 {
+            let p = random_point(&mut rng);
+            assert!(perlin.noise(&p).abs() <= 1.0);
+            assert_eq!(perlin.noise(&p.map(f32::floor)), 0.0);
+        }
+    }
+
+    #[test]
+    fn patterns_stay_in_unit_range() {
+        let patterns = [
+            Pattern::Checker,
+            Pattern::Noise { octaves: 6 },
+            Pattern::Turbulence { octaves: 6 },
+            Pattern::Marble {
+                octaves: 4,
+                distortion: 5.0,
+            },
+            Pattern::Wood {
+                rings: 8.0,
+                distortion: 0.3,
+            },
+            Pattern::Worley,
+            Pattern::Voronoi,
+        ];
+        let mut rng = fastrand::Rng::with_seed(2);
+        for pattern in patterns {
+            let texture = Procedural::new(pattern);
+            for _ in 0..1_000 {
+                let value = texture.value(&random_point(&mut rng));
+                assert!((0.0..=1.0).contains(&value), "{pattern:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn checker_alternates_between_cells() {
+        let checker = Procedural::new(Pattern::Checker);
+        let value = |x, y| checker.value(&Vector3::new(x, y, 0.0));
+        assert_eq!(value(0.5, 0.5), 0.0);
+        assert_eq!(value(1.5, 0.5), 1.0);
+        assert_eq!(value(-0.5, 0.5), 1.0);
+        assert_eq!(value(1.5, 1.5), 0.0);
+    }
+}
@@ -1,59 +1,891 @@
-use image::Rgb;
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::{Add, Div, Mul},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use crate::color::Color;
+use image::{ColorType, Rgba};
+use na::{Matrix2, Vector2, Vector3};
+
+use crate::{color::Color, procedural::Procedural, world::object::HitInfo};
+
+/// Greatest ratio between the axes of an EWA footprint. Longer footprints
+/// are widened, trading blur for a bounded number of texel lookups.
+const MAX_ANISOTROPY: f32 = 8.0;
+/// Falloff of the Gaussian weighting texels in an EWA footprint.
+const EWA_ALPHA: f32 = 2.0;
 
 pub trait Sample2D<T> {
     fn sample(&self, u: f32, v: f32) -> T;
+
+    /// Average over the area `footprint` covers around `(u, v)`, which is a
+    /// plain lookup for samplers that don't filter.
+    fn sample_filtered(&self, u: f32, v: f32, _footprint: &Footprint) -> T {
+        self.sample(u, v)
+    }
+}
+
+/// Texture defined throughout space rather than on surfaces, so that it
+/// doesn't stretch over curved objects.
+pub trait Sample3D<T> {
+    fn sample(&self, position: &Vector3<f32>) -> T;
+}
+
+/// Where [`Sampler2D::Solid`] textures are evaluated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureSpace {
+    /// At the position of hits in the world.
+    World,
+    /// At the position of hits in the space the object was modelled in, so
+    /// that the texture follows transformed and moving objects.
+    Object,
+}
+
+/// Change of the texture coordinates from one camera sample to the next
+/// along each axis of the image, which is the area a lookup should cover.
+/// All zero for point lookups.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Footprint {
+    pub du_dx: f32,
+    pub dv_dx: f32,
+    pub du_dy: f32,
+    pub dv_dy: f32,
+}
+
+/// How [`Image2DSampler`] reconstructs the image between pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFilter {
+    /// Closest pixel of the full image.
+    Nearest,
+    /// Blend of the four closest pixels of the full image.
+    Bilinear,
+    /// Catmull-Rom spline through the 16 closest pixels of the full image,
+    /// sharper than bilinear where the image is magnified.
+    Bicubic,
+    /// Bilinear lookups in the two mipmap levels closest to the size of the
+    /// footprint, blended.
+    Trilinear,
+    /// Elliptical weighted average over the footprint, which keeps detail
+    /// along the short axis of stretched footprints, such as on surfaces
+    /// seen at grazing angles.
+    Ewa,
+}
+
+/// How [`Image2DSampler`] continues the image outside the unit square of
+/// texture coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    /// Tiles the image.
+    Repeat,
+    /// Extends the pixels along the border.
+    Clamp,
+    /// Tiles the image, flipping every other tile so that edges meet.
+    Mirror,
+}
+
+impl WrapMode {
+    /// Pixel standing in for index `i` of a row or column of `size` pixels.
+    #[inline(always)]
+    fn wrap(self, i: i64, size: u32) -> usize {
+        let size = size as i64;
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::Clamp => i.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size {
+                    i
+                } else {
+                    2 * size - 1 - i
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+/// Part of the pixels an [`Image2DSampler`] gives as a scalar, e.g. for
+/// roughness or opacity maps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    /// Opacity, one for images without an alpha channel.
+    Alpha,
+    Luminance,
+}
+
+impl Channel {
+    /// The channel of `color`, which has no alpha.
+    #[inline(always)]
+    fn of(self, color: Color) -> f32 {
+        match self {
+            Channel::Red => color.r(),
+            Channel::Green => color.g(),
+            Channel::Blue => color.b(),
+            Channel::Alpha => 1.0,
+            Channel::Luminance => color.luminance(),
+        }
+    }
+}
+
+/// How the values stored in an image relate to light.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// Encoded with the sRGB transfer curve, as most 8-bit color images
+    /// are.
+    Srgb,
+    /// Proportional to light, as in HDR images.
+    Linear,
+    /// Data that isn't a color, such as heights or roughness, read as
+    /// stored like linear values.
+    Raw,
+}
+
+impl ColorSpace {
+    /// Color space `image` is assumed to be in: linear for floating point
+    /// images, sRGB for all others.
+    pub fn of(image: &image::DynamicImage) -> Self {
+        match image.color() {
+            ColorType::Rgb32F | ColorType::Rgba32F => ColorSpace::Linear,
+            _ => ColorSpace::Srgb,
+        }
+    }
+
+    /// Linear value of a stored `value` in `[0, 1]`, or above for HDR
+    /// images.
+    #[inline(always)]
+    fn decode(self, value: f32) -> f32 {
+        match self {
+            ColorSpace::Srgb if value <= 0.04045 => value / 12.92,
+            ColorSpace::Srgb => ((value + 0.055) / 1.055).powf(2.4),
+            ColorSpace::Linear | ColorSpace::Raw => value,
+        }
+    }
+}
+
+/// Pixels of an image prepared for filtering, shared between the
+/// [`Image2DSampler`]s that look it up.
+#[derive(Debug)]
+pub struct Mipmap {
+    /// Pyramid from the full image down to a single pixel, every level half
+    /// the size of the previous one.
+    levels: Vec<MipLevel<Color>>,
+    /// Pyramid of the alpha channel, if the image has one.
+    alpha: Option<Vec<MipLevel<f32>>>,
+}
+
+/// Loads every image once per color space, handing out the same [`Mipmap`]
+/// to all textures using it.
+#[derive(Debug, Default)]
+pub struct TextureCache {
+    mipmaps: HashMap<(PathBuf, ColorSpace), Arc<Mipmap>>,
 }
 
 #[derive(Debug)]
 pub struct Image2DSampler {
-    image: image::ImageBuffer<Rgb<u8>, Vec<u8>>,
+    mipmap: Arc<Mipmap>,
+    filter: TextureFilter,
+    /// Channel sampled as a scalar.
+    channel: Channel,
+    wrap: WrapMode,
+    /// Applied to texture coordinates before lookups, see
+    /// [`Image2DSampler::with_uv_transform`].
+    uv_matrix: Matrix2<f32>,
+    uv_offset: Vector2<f32>,
+}
+
+#[derive(Debug)]
+struct MipLevel<T> {
+    width: u32,
+    height: u32,
+    texels: Vec<T>,
+}
+
+/// Value stored in the pixels of a [`MipLevel`], which filters blend.
+trait Texel:
+    Copy + Add<Output = Self> + Mul<f32, Output = Self> + Div<f32, Output = Self>
+{
+    fn zero() -> Self;
+
+    /// Drops negative parts, which filters with negative weights leave next
+    /// to sharp edges.
+    fn clamp_negative(self) -> Self;
 }
 
-pub type Sampler2DFunction<T> = fn(u: f32, v: f32) -> T;
+impl Texel for Color {
+    fn zero() -> Self {
+        Color::black()
+    }
+
+    fn clamp_negative(self) -> Self {
+        Color::rgb(self.r().max(0.0), self.g().max(0.0), self.b().max(0.0))
+    }
+}
+
+impl Texel for f32 {
+    fn zero() -> Self {
+        0.0
+    }
+
+    fn clamp_negative(self) -> Self {
+        self.max(0.0)
+    }
+}
+
+/// Texture computed by a closure, which may capture state such as other
+/// textures.
+pub struct Sampler2DFunction<T>(Box<dyn Fn(f32, f32) -> T + Send + Sync>);
+
+impl<T> Sampler2DFunction<T> {
+    pub fn new(
+        function: impl Fn(f32, f32) -> T + Send + Sync + 'static,
+    ) -> Self {
+        Self(Box::new(function))
+    }
+}
+
+impl<T> fmt::Debug for Sampler2DFunction<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Sampler2DFunction")
+    }
+}
 
 #[derive(Debug)]
 pub enum Sampler2D<T: Copy>
 where
     Image2DSampler: Sample2D<T>,
+    Procedural: Sample2D<T> + Sample3D<T>,
 {
     Static(T),
     Image(Image2DSampler),
+    Procedural(Procedural),
+    /// Solid texture evaluated at the position of hits, see
+    /// [`Sampler2D::sample_hit`]. Sampled at texture coordinates, it lies on
+    /// the plane z = 0 with them as x and y.
+    Solid {
+        texture: Procedural,
+        space: TextureSpace,
+    },
     Function(Sampler2DFunction<T>),
 }
 
 impl<T: Copy> Sampler2D<T>
 where
     Image2DSampler: Sample2D<T>,
+    Procedural: Sample2D<T> + Sample3D<T>,
 {
     #[inline(always)]
     pub fn sample(&self, u: f32, v: f32) -> T {
         match self {
             Sampler2D::Static(fixed) => *fixed,
             Sampler2D::Image(image_sampler) => image_sampler.sample(u, v),
-            Sampler2D::Function(func) => func(u, v),
+            Sampler2D::Procedural(texture) |
+            Sampler2D::Solid { texture, .. } => Sample2D::sample(texture, u, v),
+            Sampler2D::Function(Sampler2DFunction(func)) => func(u, v),
+        }
+    }
+
+    /// Like [`Sampler2D::sample`], filtering images over `footprint`.
+    #[inline(always)]
+    pub fn sample_filtered(&self, u: f32, v: f32, footprint: &Footprint) -> T {
+        match self {
+            Sampler2D::Static(fixed) => *fixed,
+            Sampler2D::Image(image_sampler) => {
+                image_sampler.sample_filtered(u, v, footprint)
+            }
+            Sampler2D::Procedural(texture) |
+            Sampler2D::Solid { texture, .. } => Sample2D::sample(texture, u, v),
+            Sampler2D::Function(Sampler2DFunction(func)) => func(u, v),
         }
     }
+
+    /// Texture computed by `function` at texture coordinates.
+    pub fn function(
+        function: impl Fn(f32, f32) -> T + Send + Sync + 'static,
+    ) -> Self {
+        Sampler2D::Function(Sampler2DFunction::new(function))
+    }
+
+    /// Value at a hit: solid textures at its position, everything else at
+    /// its texture coordinates filtered over its footprint.
+    #[inline(always)]
+    pub fn sample_hit(&self, hit_info: &HitInfo) -> T {
+        match self {
+            Sampler2D::Solid { texture, space } => {
+                let position = match space {
+                    TextureSpace::World => &hit_info.position,
+                    TextureSpace::Object => &hit_info.local_position,
+                };
+                Sample3D::sample(texture, position)
+            }
+            _ => {
+                self.sample_filtered(
+                    hit_info.u,
+                    hit_info.v,
+                    &hit_info.footprint,
+                )
+            }
+        }
+    }
+}
+
+impl Mipmap {
+    /// Converts the colors of `image` from `color_space` to linear values,
+    /// which filters blend, keeping the precision of 16-bit and floating
+    /// point images. Alpha is always linear.
+    pub fn new(image: image::DynamicImage, color_space: ColorSpace) -> Self {
+        let has_alpha = image.color().has_alpha();
+        let image = image.into_rgba32f();
+        let (width, height) = image.dimensions();
+        let decode = |value| color_space.decode(value);
+        let levels = MipLevel::pyramid(
+            width,
+            height,
+            image
+                .pixels()
+                .map(|&Rgba([r, g, b, _])| {
+                    Color::rgb(decode(r), decode(g), decode(b))
+                })
+                .collect(),
+        );
+        let alpha = has_alpha.then(|| {
+            MipLevel::pyramid(
+                width,
+                height,
+                image.pixels().map(|&Rgba([.., a])| a).collect(),
+            )
+        });
+
+        Self { levels, alpha }
+    }
+
+    #[inline(always)]
+    pub fn width(&self) -> u32 {
+        self.levels[0].width
+    }
+
+    #[inline(always)]
+    pub fn height(&self) -> u32 {
+        self.levels[0].height
+    }
+}
+
+impl TextureCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pixels of the image at `path` read in `color_space`, loaded on first
+    /// use.
+    pub fn mipmap(
+        &mut self,
+        path: impl AsRef<Path>,
+        color_space: ColorSpace,
+    ) -> image::ImageResult<Arc<Mipmap>> {
+        // Different spellings of a path share the entry of the file.
+        let path = path.as_ref();
+        let key = (
+            path.canonicalize().unwrap_or_else(|_| path.to_owned()),
+            color_space,
+        );
+        if let Some(mipmap) = self.mipmaps.get(&key) {
+            return Ok(mipmap.clone());
+        }
+        let mipmap = Arc::new(Mipmap::new(image::open(path)?, color_space));
+        self.mipmaps.insert(key, mipmap.clone());
+        Ok(mipmap)
+    }
+
+    /// Texture of the image at `path`, sharing its pixels with all other
+    /// textures loaded from the same path in the same color space.
+    pub fn load(
+        &mut self,
+        path: impl AsRef<Path>,
+        color_space: ColorSpace,
+    ) -> image::ImageResult<Image2DSampler> {
+        self.mipmap(path, color_space).map(Image2DSampler::shared)
+    }
 }
 
 impl Image2DSampler {
-    pub fn new(
+    /// Builds the mipmap pyramid of `image`, filtered trilinearly and
+    /// repeated. As a scalar, the image gives its luminance. The image is
+    /// read in the color space [`ColorSpace::of`] assumes.
+    pub fn new(image: image::DynamicImage) -> Self {
+        let color_space = ColorSpace::of(&image);
+        Self::from_image(image, color_space)
+    }
+
+    /// Like [`Image2DSampler::new`] with the image read in `color_space`.
+    pub fn from_image(
         image: image::DynamicImage,
+        color_space: ColorSpace,
     ) -> Self {
+        Self::shared(Arc::new(Mipmap::new(image, color_space)))
+    }
+
+    /// Like [`Image2DSampler::new`] with pixels that are already loaded,
+    /// e.g. by a [`TextureCache`].
+    pub fn shared(mipmap: Arc<Mipmap>) -> Self {
         Self {
-            image: image.into_rgb8(),
+            mipmap,
+            filter: TextureFilter::Trilinear,
+            channel: Channel::Luminance,
+            wrap: WrapMode::Repeat,
+            uv_matrix: Matrix2::identity(),
+            uv_offset: Vector2::zeros(),
+        }
+    }
+
+    pub fn with_filter(mut self, filter: TextureFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Picks the channel the image gives when sampled as a scalar.
+    pub fn with_channel(mut self, channel: Channel) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Scales texture coordinates by `scale`, rotates them counterclockwise
+    /// by `rotation` radians and moves them by `offset` before lookups, so a
+    /// scale of two fits the image twice across the surface.
+    pub fn with_uv_transform(
+        mut self,
+        scale: Vector2<f32>,
+        rotation: f32,
+        offset: Vector2<f32>,
+    ) -> Self {
+        let (sin, cos) = rotation.sin_cos();
+        self.uv_matrix =
+            Matrix2::new(cos, -sin, sin, cos) * Matrix2::from_diagonal(&scale);
+        self.uv_offset = offset;
+        self
+    }
+
+    #[inline(always)]
+    pub fn width(&self) -> u32 {
+        self.mipmap.width()
+    }
+
+    #[inline(always)]
+    pub fn height(&self) -> u32 {
+        self.mipmap.height()
+    }
+
+    /// Color of the pixel in column `x` and row `y`, rows counting from the
+    /// top of the image.
+    #[inline(always)]
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        self.mipmap.levels[0].texel(x as i64, y as i64, WrapMode::Clamp)
+    }
+
+    #[inline(always)]
+    fn transform(&self, u: f32, v: f32) -> (f32, f32) {
+        let uv = self.uv_matrix * Vector2::new(u, v) + self.uv_offset;
+        (uv.x, uv.y)
+    }
+
+    /// `footprint` in transformed texture coordinates.
+    fn transform_footprint(&self, footprint: &Footprint) -> Footprint {
+        let dx =
+            self.uv_matrix * Vector2::new(footprint.du_dx, footprint.dv_dx);
+        let dy =
+            self.uv_matrix * Vector2::new(footprint.du_dy, footprint.dv_dy);
+        Footprint {
+            du_dx: dx.x,
+            dv_dx: dx.y,
+            du_dy: dy.x,
+            dv_dy: dy.y,
         }
     }
+
+    /// Mipmap level whose pixels are `width` wide in texture coordinates,
+    /// fractional between levels.
+    fn level_for(&self, width: f32) -> f32 {
+        let size = self.width().max(self.height()) as f32;
+        (width * size)
+            .log2()
+            .clamp(0.0, (self.mipmap.levels.len() - 1) as f32)
+    }
+
+    /// Lookup in `levels` with the sampler's filter.
+    fn lookup<T: Texel>(
+        &self,
+        levels: &[MipLevel<T>],
+        u: f32,
+        v: f32,
+        footprint: &Footprint,
+    ) -> T {
+        let (u, v) = self.transform(u, v);
+        let footprint = self.transform_footprint(footprint);
+        match self.filter {
+            TextureFilter::Nearest => levels[0].nearest(u, v, self.wrap),
+            TextureFilter::Bilinear => levels[0].bilinear(u, v, self.wrap),
+            TextureFilter::Bicubic => levels[0].bicubic(u, v, self.wrap),
+            TextureFilter::Trilinear => {
+                self.trilinear(levels, u, v, &footprint)
+            }
+            TextureFilter::Ewa => self.ewa(levels, u, v, &footprint),
+        }
+    }
+
+    fn trilinear<T: Texel>(
+        &self,
+        levels: &[MipLevel<T>],
+        u: f32,
+        v: f32,
+        footprint: &Footprint,
+    ) -> T {
+        let width = (footprint.du_dx.hypot(footprint.dv_dx))
+            .max(footprint.du_dy.hypot(footprint.dv_dy));
+        let level = self.level_for(width);
+        let below = level.floor() as usize;
+        let fraction = level - below as f32;
+        if fraction == 0.0 {
+            return levels[below].bilinear(u, v, self.wrap);
+        }
+        levels[below].bilinear(u, v, self.wrap) * (1.0 - fraction) +
+            levels[below + 1].bilinear(u, v, self.wrap) * fraction
+    }
+
+    fn ewa<T: Texel>(
+        &self,
+        levels: &[MipLevel<T>],
+        u: f32,
+        v: f32,
+        footprint: &Footprint,
+    ) -> T {
+        let mut major = (footprint.du_dx, footprint.dv_dx);
+        let mut minor = (footprint.du_dy, footprint.dv_dy);
+        if major.0.hypot(major.1) < minor.0.hypot(minor.1) {
+            std::mem::swap(&mut major, &mut minor);
+        }
+        let major_length = major.0.hypot(major.1);
+        let mut minor_length = minor.0.hypot(minor.1);
+        if minor_length * MAX_ANISOTROPY < major_length && minor_length > 0.0 {
+            let scale = major_length / (minor_length * MAX_ANISOTROPY);
+            minor = (minor.0 * scale, minor.1 * scale);
+            minor_length *= scale;
+        }
+        if minor_length == 0.0 {
+            return self.trilinear(levels, u, v, footprint);
+        }
+
+        let level = self.level_for(minor_length);
+        let last = levels.len() - 1;
+        if level >= last as f32 {
+            // The footprint covers the whole texture, whose average is all
+            // that is left. The ellipse would span ever more texels.
+            return levels[last].bilinear(u, v, self.wrap);
+        }
+        let below = level.floor() as usize;
+        let fraction = level - below as f32;
+        let sample =
+            |level: usize| levels[level].ewa(u, v, major, minor, self.wrap);
+        if fraction == 0.0 {
+            return sample(below);
+        }
+        sample(below) * (1.0 - fraction) + sample(below + 1) * fraction
+    }
+}
+
+impl<T: Texel> MipLevel<T> {
+    /// Levels from `texels`, given row by row, down to a single texel.
+    fn pyramid(width: u32, height: u32, texels: Vec<T>) -> Vec<Self> {
+        let mut levels = vec![MipLevel {
+            width,
+            height,
+            texels,
+        }];
+        while let Some(level) = levels.last().unwrap().downsampled() {
+            levels.push(level);
+        }
+        levels
+    }
+
+    /// Level at half the size, averaging blocks of 2x2 texels, or `None`
+    /// for a single texel.
+    fn downsampled(&self) -> Option<Self> {
+        if self.width == 1 && self.height == 1 {
+            return None;
+        }
+        let (width, height) =
+            ((self.width / 2).max(1), (self.height / 2).max(1));
+        let texels = (0..height as i64)
+            .flat_map(|y| (0..width as i64).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let texel = |x, y| self.texel(x, y, WrapMode::Clamp);
+                (texel(2 * x, 2 * y) +
+                    texel(2 * x + 1, 2 * y) +
+                    texel(2 * x, 2 * y + 1) +
+                    texel(2 * x + 1, 2 * y + 1)) *
+                    0.25
+            })
+            .collect();
+        Some(Self {
+            width,
+            height,
+            texels,
+        })
+    }
+
+    /// Texel at column `x` and row `y`, continuing the level outside its
+    /// bounds according to `wrap`.
+    #[inline(always)]
+    fn texel(&self, x: i64, y: i64, wrap: WrapMode) -> T {
+        let x = wrap.wrap(x, self.width);
+        let y = wrap.wrap(y, self.height);
+        self.texels[y * self.width as usize + x]
+    }
+
+    /// Position of `(u, v)` in texels, with texel centers at whole numbers.
+    #[inline(always)]
+    fn to_texels(&self, u: f32, v: f32) -> (f32, f32) {
+        (
+            u * self.width as f32 - 0.5,
+            (1.0 - v) * self.height as f32 - 0.5,
+        )
+    }
+
+    fn nearest(&self, u: f32, v: f32, wrap: WrapMode) -> T {
+        let s = u * self.width as f32;
+        let t = (1.0 - v) * self.height as f32;
+        self.texel(s.floor() as i64, t.floor() as i64, wrap)
+    }
+
+    fn bilinear(&self, u: f32, v: f32, wrap: WrapMode) -> T {
+        let (s, t) = self.to_texels(u, v);
+        let (x, y) = (s.floor(), t.floor());
+        let (fx, fy) = (s - x, t - y);
+        let (x, y) = (x as i64, y as i64);
+        let texel = |x, y| self.texel(x, y, wrap);
+        (texel(x, y) * (1.0 - fx) + texel(x + 1, y) * fx) * (1.0 - fy) +
+            (texel(x, y + 1) * (1.0 - fx) + texel(x + 1, y + 1) * fx) * fy
+    }
+
+    fn bicubic(&self, u: f32, v: f32, wrap: WrapMode) -> T {
+        let (s, t) = self.to_texels(u, v);
+        let (x, y) = (s.floor(), t.floor());
+        let (weights_x, weights_y) = (catmull_rom(s - x), catmull_rom(t - y));
+        let (x, y) = (x as i64 - 1, y as i64 - 1);
+        let mut sum = T::zero();
+        for (j, weight_y) in (0..).zip(weights_y) {
+            for (i, weight_x) in (0..).zip(weights_x) {
+                sum = sum +
+                    self.texel(x + i, y + j, wrap) * (weight_x * weight_y);
+            }
+        }
+        // The spline overshoots next to sharp edges.
+        sum.clamp_negative()
+    }
+
+    /// Gaussian weighted average over the ellipse spanned by the `major` and
+    /// `minor` axes, given in texture coordinates.
+    fn ewa(
+        &self,
+        u: f32,
+        v: f32,
+        major: (f32, f32),
+        minor: (f32, f32),
+        wrap: WrapMode,
+    ) -> T {
+        let (s, t) = self.to_texels(u, v);
+        let (width, height) = (self.width as f32, self.height as f32);
+        // Rows run against v, which doesn't matter to the symmetric ellipse.
+        let (major, minor) = (
+            (major.0 * width, major.1 * height),
+            (minor.0 * width, minor.1 * height),
+        );
+
+        // Implicit ellipse a s² + b s t + c t² < 1, grown by a texel so
+        // that even tiny footprints cover some texels.
+        let mut a = major.1 * major.1 + minor.1 * minor.1 + 1.0;
+        let mut b = -2.0 * (major.0 * major.1 + minor.0 * minor.1);
+        let mut c = major.0 * major.0 + minor.0 * minor.0 + 1.0;
+        let f = 1.0 / (a * c - b * b * 0.25);
+        a *= f;
+        b *= f;
+        c *= f;
+
+        let det = 4.0 * a * c - b * b;
+        let s_radius = 2.0 * (c / det).sqrt();
+        let t_radius = 2.0 * (a / det).sqrt();
+        let (s0, s1) = ((s - s_radius).ceil(), (s + s_radius).floor());
+        let (t0, t1) = ((t - t_radius).ceil(), (t + t_radius).floor());
+
+        let edge = (-EWA_ALPHA).exp();
+        let mut sum = T::zero();
+        let mut weights = 0.0;
+        for y in t0 as i64..=t1 as i64 {
+            let dt = y as f32 - t;
+            for x in s0 as i64..=s1 as i64 {
+                let ds = x as f32 - s;
+                let r2 = a * ds * ds + b * ds * dt + c * dt * dt;
+                if r2 < 1.0 {
+                    let weight = (-EWA_ALPHA * r2).exp() - edge;
+                    sum = sum + self.texel(x, y, wrap) * weight;
+                    weights += weight;
+                }
+            }
+        }
+        if weights > 0.0 {
+            sum / weights
+        } else {
+            self.bilinear(u, v, wrap)
+        }
+    }
+}
+
+/// Weights of the four pixels around a point `f` past the second one in a
+/// Catmull-Rom spline.
+#[inline(always)]
+fn catmull_rom(f: f32) -> [f32; 4] {
+    let (f2, f3) = (f * f, f * f * f);
+    [
+        -0.5 * f3 + f2 - 0.5 * f,
+        1.5 * f3 - 2.5 * f2 + 1.0,
+        -1.5 * f3 + 2.0 * f2 + 0.5 * f,
+        0.5 * f3 - 0.5 * f2,
+    ]
 }
 
 impl Sample2D<Color> for Image2DSampler {
+    /// Point lookup with the sampler's filter.
     fn sample(&self, u: f32, v: f32) -> Color {
-        let factor = 1.0;
-        let x = (u * factor).fract().abs() * self.image.width() as f32;
-        let y = (((1.0 - v) * factor).fract().abs()) *
-            self.image.height() as f32;
-        let (x, y) = (x as u32, y as u32);
-        Color::from_rgb_24(*self.image.get_pixel(x, y), 1.0)
+        self.sample_filtered(u, v, &Footprint::default())
+    }
+
+    fn sample_filtered(&self, u: f32, v: f32, footprint: &Footprint) -> Color {
+        self.lookup(&self.mipmap.levels, u, v, footprint)
+    }
+}
+
+impl Sample2D<f32> for Image2DSampler {
+    /// Point lookup with the sampler's filter.
+    fn sample(&self, u: f32, v: f32) -> f32 {
+        self.sample_filtered(u, v, &Footprint::default())
+    }
+
+    fn sample_filtered(&self, u: f32, v: f32, footprint: &Footprint) -> f32 {
+        match (self.channel, &self.mipmap.alpha) {
+            (Channel::Alpha, Some(alpha)) => {
+                self.lookup(alpha, u, v, footprint)
+            }
+            (channel, _) => {
+                channel.of(self.lookup(&self.mipmap.levels, u, v, footprint))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::*;
+
+    #[test]
+    fn wrap_modes_fold_indices() {
+        let wrapped = |wrap: WrapMode| {
+            (-5..9).map(|i| wrap.wrap(i, 4)).collect::<Vec<_>>()
+        };
+        assert_eq!(wrapped(WrapMode::Repeat), [
+            3, 0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0
+        ]);
+        assert_eq!(wrapped(WrapMode::Clamp), [
+            0, 0, 0, 0, 0, 0, 1, 2, 3, 3, 3, 3, 3, 3
+        ]);
+        assert_eq!(wrapped(WrapMode::Mirror), [
+            3, 3, 2, 1, 0, 0, 1, 2, 3, 3, 2, 1, 0, 0
+        ]);
+    }
+
+    #[test]
+    fn texture_cache_shares_pixels() {
+        // Unique per process, so concurrent test runs don't share the file.
+        let name = format!("texture_cache_test_{}.png", std::process::id());
+        let path = std::env::temp_dir().join(&name);
+        image::RgbImage::from_pixel(4, 2, Rgb([255, 0, 0]))
+            .save(&path)
+            .unwrap();
+        let respelled = path.parent().unwrap().join(".").join(&name);
+
+        let mut cache = TextureCache::new();
+        let first = cache.mipmap(&path, ColorSpace::Srgb).unwrap();
+        let second = cache.load(respelled, ColorSpace::Srgb).unwrap();
+        assert!(Arc::ptr_eq(&first, &second.mipmap));
+        assert_eq!((second.width(), second.height()), (4, 2));
+        let raw = cache.mipmap(&path, ColorSpace::Raw).unwrap();
+        assert!(!Arc::ptr_eq(&first, &raw));
+        let missing = path.with_extension("missing.png");
+        assert!(cache.load(missing, ColorSpace::Srgb).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn images_are_decoded_to_linear_values() {
+        let gray = image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            1,
+            1,
+            Rgb([188, 188, 188]),
+        ));
+        let srgb = Image2DSampler::new(gray.clone()).pixel(0, 0);
+        assert!((srgb.r() - 0.5).abs() < 0.005, "{srgb:?}");
+        let raw = Image2DSampler::from_image(gray, ColorSpace::Raw);
+        assert_eq!(raw.pixel(0, 0).r(), 188.0 / 255.0);
+
+        let deep = image::DynamicImage::ImageRgb16(
+            image::ImageBuffer::from_pixel(1, 1, Rgb([1, 1, 1])),
+        );
+        let deep = Image2DSampler::from_image(deep, ColorSpace::Raw);
+        assert_eq!(deep.pixel(0, 0).r(), 1.0 / 65535.0);
+
+        let hdr = image::DynamicImage::ImageRgb32F(
+            image::ImageBuffer::from_pixel(1, 1, Rgb([4.0, 0.25, 0.0])),
+        );
+        assert_eq!(ColorSpace::of(&hdr), ColorSpace::Linear);
+        let hdr = Image2DSampler::new(hdr).pixel(0, 0);
+        assert_eq!((hdr.r(), hdr.g(), hdr.b()), (4.0, 0.25, 0.0));
+    }
+
+    #[test]
+    fn point_samples_use_the_filter() {
+        let ramp = image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(
+            2,
+            1,
+            |x, _| Rgb([255 * x as u8; 3]),
+        ));
+        let sampler = |filter| {
+            Image2DSampler::from_image(ramp.clone(), ColorSpace::Raw)
+                .with_filter(filter)
+        };
+        let nearest: Color = sampler(TextureFilter::Nearest).sample(0.6, 0.5);
+        assert_eq!(nearest.r(), 1.0);
+        let bilinear: Color = sampler(TextureFilter::Bilinear).sample(0.5, 0.5);
+        assert!((bilinear.r() - 0.5).abs() < 1e-5, "{bilinear:?}");
+        let red: f32 = sampler(TextureFilter::Bilinear)
+            .with_channel(Channel::Red)
+            .sample(0.5, 0.5);
+        assert!((red - 0.5).abs() < 1e-5, "{red}");
+    }
+
+    #[test]
+    fn catmull_rom_weights_sum_to_one() {
+        for i in 0..=10 {
+            let weights = catmull_rom(i as f32 / 10.0);
+            assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        }
+        assert_eq!(catmull_rom(0.0), [0.0, 1.0, 0.0, 0.0]);
     }
 }
@@ -0,0 +1,394 @@
+//! Warping functions from the unit square to common sampling domains.
+//!
+//! Every function takes its random numbers explicitly as a point in
+//! `[0, 1)^2`, so the same code can be driven by `fastrand`, stratified
+//! patterns or a primary-sample-space mutator. Each warp has a matching
+//! `*_pdf` function returning the density with respect to the measure of its
+//! domain (solid angle for directions, area for points).
+
+use std::f32::consts::{FRAC_1_PI, FRAC_PI_2, FRAC_PI_4, TAU};
+
+use na::{Vector2, Vector3};
+
+use crate::math::orthonormal_basis;
+
+#[inline(always)]
+fn to_world(normal: &Vector3<f32>, local: Vector3<f32>) -> Vector3<f32> {
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    tangent * local.x + bitangent * local.y + normal * local.z
+}
+
+/// Uniformly distributed direction on the unit sphere.
+pub fn uniform_sphere(u: Vector2<f32>) -> Vector3<f32> {
+    let z = 1.0 - 2.0 * u.x;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = TAU * u.y;
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+#[inline(always)]
+pub fn uniform_sphere_pdf() -> f32 {
+    0.25 * FRAC_1_PI
+}
+
+/// Uniformly distributed direction on the hemisphere around `normal`.
+/// `normal` must be normalized.
+pub fn uniform_hemisphere(
+    normal: &Vector3<f32>,
+    u: Vector2<f32>,
+) -> Vector3<f32> {
+    let z = u.x;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = TAU * u.y;
+    to_world(normal, Vector3::new(r * phi.cos(), r * phi.sin(), z))
+}
+
+#[inline(always)]
+pub fn uniform_hemisphere_pdf(
+    normal: &Vector3<f32>,
+    direction: &Vector3<f32>,
+) -> f32 {
+    if normal.dot(direction) < 0.0 {
+        0.0
+    } else {
+        0.5 * FRAC_1_PI
+    }
+}
+
+/// Cosine-weighted direction on the hemisphere around `normal`, using
+/// Malley's method on top of [`concentric_disk`]. `normal` must be
+/// normalized.
+pub fn cosine_hemisphere(
+    normal: &Vector3<f32>,
+    u: Vector2<f32>,
+) -> Vector3<f32> {
+    let d = concentric_disk(u);
+    let z = (1.0 - d.x * d.x - d.y * d.y).max(0.0).sqrt();
+    to_world(normal, Vector3::new(d.x, d.y, z))
+}
+
+/// Density of [`cosine_hemisphere`]; `direction` must be normalized.
+#[inline(always)]
+pub fn cosine_hemisphere_pdf(
+    normal: &Vector3<f32>,
+    direction: &Vector3<f32>,
+) -> f32 {
+    normal.dot(direction).max(0.0) * FRAC_1_PI
+}
+
+/// Point on the unit disk using Shirley's concentric mapping, which keeps
+/// strata of the square adjacent on the disk.
+pub fn concentric_disk(u: Vector2<f32>) -> Vector2<f32> {
+    let offset = 2.0 * u - Vector2::new(1.0, 1.0);
+    if offset.x == 0.0 && offset.y == 0.0 {
+        return Vector2::zeros();
+    }
+
+    let (r, theta) = if offset.x.abs() > offset.y.abs() {
+        (offset.x, FRAC_PI_4 * (offset.y / offset.x))
+    } else {
+        (offset.y, FRAC_PI_2 - FRAC_PI_4 * (offset.x / offset.y))
+    };
+    r * Vector2::new(theta.cos(), theta.sin())
+}
+
+#[inline(always)]
+pub fn concentric_disk_pdf() -> f32 {
+    FRAC_1_PI
+}
+
+/// Uniformly distributed direction inside the cone around `axis` with
+/// half-angle `acos(cos_theta_max)`. `axis` must be normalized.
+pub fn uniform_cone(
+    axis: &Vector3<f32>,
+    cos_theta_max: f32,
+    u: Vector2<f32>,
+) -> Vector3<f32> {
+    let cos_theta = 1.0 - u.x * (1.0 - cos_theta_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = TAU * u.y;
+    to_world(
+        axis,
+        Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta),
+    )
+}
+
+#[inline(always)]
+pub fn uniform_cone_pdf(cos_theta_max: f32) -> f32 {
+    1.0 / (TAU * (1.0 - cos_theta_max))
+}
+
+/// Direction distributed by the Henyey-Greenstein phase function around the
+/// propagation `direction`, which must be normalized. Positive `g` favors
+/// forward scattering, negative `g` backward scattering.
+pub fn henyey_greenstein(
+    direction: &Vector3<f32>,
+    g: f32,
+    u: Vector2<f32>,
+) -> Vector3<f32> {
+    let cos_theta = if g.abs() < 1e-3 {
+        1.0 - 2.0 * u.x
+    } else {
+        let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u.x);
+        ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+    };
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = TAU * u.y;
+    to_world(
+        direction,
+        Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta),
+    )
+}
+
+/// Solid angle density of [`henyey_greenstein`] for scattering by an angle
+/// with cosine `cos_theta`.
+#[inline(always)]
+pub fn henyey_greenstein_pdf(g: f32, cos_theta: f32) -> f32 {
+    let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+    (1.0 - g * g) / (2.0 * TAU * denom * denom.max(0.0).sqrt())
+}
+
+/// Barycentric coordinates `(b0, b1, b2)` of a uniformly distributed point
+/// on a triangle, using the area-preserving square-to-triangle map.
+pub fn uniform_triangle_barycentric(u: Vector2<f32>) -> Vector3<f32> {
+    let (b0, b1) = if u.x < u.y {
+        let b0 = u.x / 2.0;
+        (b0, u.y - b0)
+    } else {
+        let b1 = u.y / 2.0;
+        (u.x - b1, b1)
+    };
+    Vector3::new(b0, b1, 1.0 - b0 - b1)
+}
+
+/// Uniformly distributed point on the triangle `(p0, p1, p2)`.
+pub fn uniform_triangle(
+    p0: &Vector3<f32>,
+    p1: &Vector3<f32>,
+    p2: &Vector3<f32>,
+    u: Vector2<f32>,
+) -> Vector3<f32> {
+    let b = uniform_triangle_barycentric(u);
+    p0 * b.x + p1 * b.y + p2 * b.z
+}
+
+/// Area density of [`uniform_triangle`].
+#[inline(always)]
+pub fn uniform_triangle_pdf(
+    p0: &Vector3<f32>,
+    p1: &Vector3<f32>,
+    p2: &Vector3<f32>,
+) -> f32 {
+    2.0 / (p1 - p0).cross(&(p2 - p0)).magnitude()
+}
+
+/// Multiple importance sampling weight for a sample drawn `n_f` times from
+/// the strategy with density `f_pdf`, combined with `n_g` samples from a
+/// strategy with density `g_pdf` (Veach's power heuristic, beta = 2).
+#[inline(always)]
+pub fn power_heuristic(n_f: u32, f_pdf: f32, n_g: u32, g_pdf: f32) -> f32 {
+    let f = n_f as f32 * f_pdf;
+    let g = n_g as f32 * g_pdf;
+    if f.is_infinite() {
+        return 1.0;
+    }
+    if f == 0.0 {
+        return 0.0;
+    }
+    // Written as a ratio so large finite densities don't overflow when
+    // squared.
+    let ratio = g / f;
+    1.0 / (1.0 + ratio * ratio)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+
+    const SAMPLES: usize = 20_000;
+
+    fn random_u(rng: &mut fastrand::Rng) -> Vector2<f32> {
+        Vector2::new(rng.f32(), rng.f32())
+    }
+
+    fn random_normal(rng: &mut fastrand::Rng) -> Vector3<f32> {
+        uniform_sphere(random_u(rng))
+    }
+
+    #[test]
+    fn orthonormal_basis_is_orthonormal() {
+        let mut rng = fastrand::Rng::with_seed(1);
+        for _ in 0..SAMPLES {
+            let n = random_normal(&mut rng);
+            let (t, b) = orthonormal_basis(&n);
+            assert!((t.magnitude() - 1.0).abs() < 1e-4);
+            assert!((b.magnitude() - 1.0).abs() < 1e-4);
+            assert!(t.dot(&b).abs() < 1e-4);
+            assert!(t.dot(&n).abs() < 1e-4);
+            assert!(b.dot(&n).abs() < 1e-4);
+            assert!((t.cross(&b) - n).magnitude() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn uniform_sphere_covers_both_halves() {
+        let mut rng = fastrand::Rng::with_seed(2);
+        let mut upper = 0;
+        for _ in 0..SAMPLES {
+            let d = uniform_sphere(random_u(&mut rng));
+            assert!((d.magnitude() - 1.0).abs() < 1e-4);
+            if d.z > 0.0 {
+                upper += 1;
+            }
+        }
+        let ratio = upper as f32 / SAMPLES as f32;
+        assert!((ratio - 0.5).abs() < 0.02, "upper ratio {ratio}");
+    }
+
+    #[test]
+    fn hemispheres_are_oriented_around_normal() {
+        let mut rng = fastrand::Rng::with_seed(3);
+        for _ in 0..SAMPLES {
+            let n = random_normal(&mut rng);
+            let uniform = uniform_hemisphere(&n, random_u(&mut rng));
+            let cosine = cosine_hemisphere(&n, random_u(&mut rng));
+            assert!((uniform.magnitude() - 1.0).abs() < 1e-4);
+            assert!((cosine.magnitude() - 1.0).abs() < 1e-4);
+            assert!(uniform.dot(&n) >= -1e-5);
+            assert!(cosine.dot(&n) >= -1e-5);
+            assert!(uniform_hemisphere_pdf(&n, &-n) == 0.0);
+            assert!(cosine_hemisphere_pdf(&n, &-n) == 0.0);
+        }
+    }
+
+    #[test]
+    fn cosine_hemisphere_mean_cosine() {
+        let mut rng = fastrand::Rng::with_seed(4);
+        // E[cos] is 2/3 under cosine weighting and 1/2 under uniform.
+        let n = Vector3::new(0.0, 1.0, 0.0);
+        let (mut cosine, mut uniform) = (0.0, 0.0);
+        for _ in 0..SAMPLES {
+            cosine += cosine_hemisphere(&n, random_u(&mut rng)).dot(&n);
+            uniform += uniform_hemisphere(&n, random_u(&mut rng)).dot(&n);
+        }
+        let cosine = cosine / SAMPLES as f32;
+        let uniform = uniform / SAMPLES as f32;
+        assert!((cosine - 2.0 / 3.0).abs() < 0.02, "cosine mean {cosine}");
+        assert!((uniform - 0.5).abs() < 0.02, "uniform mean {uniform}");
+    }
+
+    #[test]
+    fn pdfs_integrate_to_one() {
+        let mut rng = fastrand::Rng::with_seed(5);
+        // Monte Carlo estimate of each pdf's integral using uniform sphere
+        // samples as the proposal.
+        let n = random_normal(&mut rng);
+        let axis = random_normal(&mut rng);
+        let cos_theta_max = 0.8;
+        let (mut uniform, mut cosine, mut cone, mut hg) = (0.0, 0.0, 0.0, 0.0);
+        for _ in 0..SAMPLES * 10 {
+            let d = uniform_sphere(random_u(&mut rng));
+            uniform += uniform_hemisphere_pdf(&n, &d);
+            cosine += cosine_hemisphere_pdf(&n, &d);
+            if d.dot(&axis) >= cos_theta_max {
+                cone += uniform_cone_pdf(cos_theta_max);
+            }
+            hg += henyey_greenstein_pdf(0.3, d.dot(&axis));
+        }
+        let scale = 1.0 / (SAMPLES * 10) as f32 / uniform_sphere_pdf();
+        assert!((uniform * scale - 1.0).abs() < 0.03);
+        assert!((cosine * scale - 1.0).abs() < 0.03);
+        assert!((cone * scale - 1.0).abs() < 0.05);
+        assert!((hg * scale - 1.0).abs() < 0.03);
+    }
+
+    #[test]
+    fn henyey_greenstein_mean_cosine() {
+        let mut rng = fastrand::Rng::with_seed(6);
+        // The mean cosine of the Henyey-Greenstein distribution is `g`.
+        for g in [-0.6, 0.0, 0.8] {
+            let direction = random_normal(&mut rng);
+            let mut mean = 0.0;
+            for _ in 0..SAMPLES {
+                let d = henyey_greenstein(&direction, g, random_u(&mut rng));
+                assert!((d.magnitude() - 1.0).abs() < 1e-4);
+                mean += d.dot(&direction);
+            }
+            let mean = mean / SAMPLES as f32;
+            assert!((mean - g).abs() < 0.02, "mean cosine {mean} for g {g}");
+        }
+    }
+
+    #[test]
+    fn concentric_disk_is_uniform() {
+        let mut rng = fastrand::Rng::with_seed(7);
+        let mut inner = 0;
+        for _ in 0..SAMPLES {
+            let p = concentric_disk(random_u(&mut rng));
+            assert!(p.magnitude() <= 1.0 + 1e-5);
+            if p.magnitude() < 0.5 {
+                inner += 1;
+            }
+        }
+        // The inner disk of radius 1/2 holds a quarter of the area.
+        let ratio = inner as f32 / SAMPLES as f32;
+        assert!((ratio - 0.25).abs() < 0.02, "inner ratio {ratio}");
+        assert_eq!(concentric_disk(Vector2::new(0.5, 0.5)), Vector2::zeros());
+        assert!((concentric_disk_pdf() * PI - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn uniform_cone_stays_inside_cone() {
+        let mut rng = fastrand::Rng::with_seed(8);
+        for _ in 0..SAMPLES {
+            let axis = random_normal(&mut rng);
+            let d = uniform_cone(&axis, 0.9, random_u(&mut rng));
+            assert!((d.magnitude() - 1.0).abs() < 1e-4);
+            assert!(d.dot(&axis) >= 0.9 - 1e-4);
+        }
+    }
+
+    #[test]
+    fn uniform_triangle_is_inside_and_uniform() {
+        let mut rng = fastrand::Rng::with_seed(9);
+        let p0 = Vector3::new(0.0, 0.0, 0.0);
+        let p1 = Vector3::new(2.0, 0.0, 0.0);
+        let p2 = Vector3::new(0.0, 2.0, 0.0);
+        let mut corner = 0;
+        for _ in 0..SAMPLES {
+            let b = uniform_triangle_barycentric(random_u(&mut rng));
+            assert!(b.iter().all(|&c| (-1e-6..=1.0 + 1e-6).contains(&c)));
+            assert!((b.sum() - 1.0).abs() < 1e-5);
+
+            let p = uniform_triangle(&p0, &p1, &p2, random_u(&mut rng));
+            assert!(p.x >= -1e-6 && p.y >= -1e-6 && p.x + p.y <= 2.0 + 1e-5);
+            // The sub-triangle at p0 with half-length legs has 1/4 of the
+            // area.
+            if p.x + p.y < 1.0 {
+                corner += 1;
+            }
+        }
+        let ratio = corner as f32 / SAMPLES as f32;
+        assert!((ratio - 0.25).abs() < 0.02, "corner ratio {ratio}");
+        assert!((uniform_triangle_pdf(&p0, &p1, &p2) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn power_heuristic_stays_finite_for_huge_densities() {
+        // Distant hits on infinite planes turn area densities into huge
+        // solid angle densities, squaring them used to overflow to NaN.
+        for pdf in [1e20, 1e30, f32::MAX, f32::INFINITY] {
+            let weight = power_heuristic(1, pdf, 1, pdf);
+            assert!(weight.is_finite(), "{pdf}: {weight}");
+            assert!((0.0..=1.0).contains(&weight), "{pdf}: {weight}");
+
+            assert_eq!(power_heuristic(1, pdf, 1, 0.5), 1.0);
+            assert_eq!(power_heuristic(1, 0.5, 1, pdf), 0.0);
+            assert_eq!(power_heuristic(1, 0.0, 1, pdf), 0.0);
+        }
+        assert_eq!(power_heuristic(1, 1e20, 1, 1e20), 0.5);
+        assert_eq!(power_heuristic(0, 1.0, 1, 1.0), 0.0);
+    }
+}
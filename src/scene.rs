@@ -1,11 +1,28 @@
 use std::{
-    num::NonZeroUsize, sync::{mpsc, Arc, Mutex}, time::Instant
+    num::NonZeroUsize,
+    sync::{mpsc, Arc, Mutex},
+    time::Instant,
 };
 
 use image::{ImageBuffer, Rgb};
 
-use crate::{camera::Camera, color::Color, world::World};
-
+use crate::{
+    camera::Camera,
+    color::Color,
+    integrator::{
+        ambient_occlusion::AmbientOcclusion,
+        bidirectional::BidirectionalIntegrator,
+        debug::DebugIntegrator,
+        direct::DirectIntegrator,
+        metropolis::{Metropolis, PathSample},
+        path::PathIntegrator,
+        photon::{PhotonMap, PhotonMappingIntegrator},
+        whitted::Whitted,
+        Integrator, IntegratorKind,
+    },
+    math::random_f32,
+    world::World,
+};
 
 pub struct Scene {
     world: World,
@@ -19,6 +36,16 @@ pub struct RenderInfo {
     pub max_depth: u32,
     pub gamma: f32,
     pub samples: u32,
+    pub integrator: IntegratorKind,
+}
+
+impl RenderInfo {
+    /// Distance between camera samples in screen coordinates along each
+    /// axis: a pixel, shrinking as more samples share it, down to an eighth.
+    fn sample_spacing(&self) -> (f32, f32) {
+        let scale = (1.0 / (self.samples as f32).sqrt()).max(0.125);
+        (scale / self.width as f32, scale / self.height as f32)
+    }
 }
 
 struct ChunkInfo {
@@ -28,18 +55,161 @@ struct ChunkInfo {
     height: u32,
 }
 
+/// Linear radiance estimates, before gamma correction and quantization.
+pub type HdrImage = ImageBuffer<Rgb<f32>, Vec<f32>>;
+
 struct Chunk {
     x: u32,
     y: u32,
-    image: ImageBuffer<Rgb<u8>, Vec<u8>>,
+    image: HdrImage,
 }
 
 impl Scene {
-    pub fn new(world: World, camera: Camera) -> Self {
+    pub fn new(mut world: World, camera: Camera) -> Self {
+        world.build_bvh();
         Self { world, camera }
     }
 
+    /// Gives access to the world between renders, e.g. to animate instances.
+    /// Call [`World::refit_bvh`] after moving objects.
+    #[inline(always)]
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
     pub fn render(&self, info: RenderInfo) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let image = self.render_hdr(info);
+        // Debug colors are not radiance and are written as they are.
+        let gamma = match info.integrator {
+            IntegratorKind::Debug(_) => 1.0,
+            _ => info.gamma,
+        };
+
+        ImageBuffer::from_fn(info.width, info.height, |x, y| {
+            Color::from_rgb_f32(image[(x, y)]).to_rgb(gamma)
+        })
+    }
+
+    /// Renders the scene without tone mapping.
+    pub fn render_hdr(&self, info: RenderInfo) -> HdrImage {
+        match info.integrator {
+            IntegratorKind::Path => {
+                self.render_with(info, &PathIntegrator::new(info.max_depth))
+            }
+            IntegratorKind::PathCaustics { photons, radius } => {
+                let caustics = PhotonMap::caustic(&self.world, photons, radius);
+                eprintln!("[RENDER] Caustic photons: {}", caustics.len());
+                self.render_with(
+                    info,
+                    &PathIntegrator::with_caustics(info.max_depth, &caustics),
+                )
+            }
+            IntegratorKind::ProgressivePhotonMapping {
+                photons,
+                radius,
+                alpha,
+            } => self.render_progressive(info, photons, radius, alpha),
+            IntegratorKind::Direct => {
+                self.render_with(info, &DirectIntegrator::new(info.max_depth))
+            }
+            IntegratorKind::AmbientOcclusion { distance } => {
+                self.render_with(info, &AmbientOcclusion::new(distance))
+            }
+            IntegratorKind::Whitted => {
+                self.render_with(info, &Whitted::new(info.max_depth))
+            }
+            IntegratorKind::Bidirectional => {
+                self.render_with(
+                    info,
+                    &BidirectionalIntegrator::new(info.max_depth),
+                )
+            }
+            IntegratorKind::Metropolis {
+                mutation_size,
+                large_step_probability,
+                bootstrap_samples,
+                chains,
+            } => {
+                let integrator = PathIntegrator::new(info.max_depth);
+                let metropolis = Metropolis::new(
+                    mutation_size,
+                    large_step_probability,
+                    bootstrap_samples,
+                    chains,
+                );
+                metropolis.render(
+                    info.width,
+                    info.height,
+                    info.samples,
+                    &|| {
+                        let u = random_f32();
+                        let v = random_f32();
+                        PathSample {
+                            u,
+                            v,
+                            radiance: self.camera.trace(
+                                u,
+                                v,
+                                info.sample_spacing(),
+                                &self.world,
+                                &integrator,
+                            ),
+                        }
+                    },
+                )
+            }
+            IntegratorKind::Debug(mode) => {
+                self.render_with(
+                    info,
+                    &DebugIntegrator::new(mode, info.max_depth),
+                )
+            }
+        }
+    }
+
+    /// Renders one sample per pixel for each of `info.samples` photon passes
+    /// and averages them, shrinking the gather radius after every pass.
+    fn render_progressive(
+        &self,
+        info: RenderInfo,
+        photons: u32,
+        mut radius: f32,
+        alpha: f32,
+    ) -> HdrImage {
+        let pass_info = RenderInfo { samples: 1, ..info };
+        let mut image = HdrImage::new(info.width, info.height);
+
+        for pass in 0..info.samples {
+            eprintln!(
+                "[RENDER] Photon pass {}/{} (radius {radius:.4})",
+                pass + 1,
+                info.samples
+            );
+            let photon_map = PhotonMap::global(&self.world, photons, radius);
+            let integrator =
+                PhotonMappingIntegrator::new(info.max_depth, &photon_map);
+            let pass_image = self.render_with(pass_info, &integrator);
+
+            let weight = (pass + 1) as f32;
+            for (sum, sample) in image.pixels_mut().zip(pass_image.pixels()) {
+                let sum_color = Color::from_rgb_f32(*sum);
+                let sample_color = Color::from_rgb_f32(*sample);
+                *sum = (sum_color + (sample_color - sum_color) / weight)
+                    .to_rgb_f32();
+            }
+
+            let i = weight;
+            radius *= ((i + alpha) / (i + 1.0)).sqrt();
+        }
+
+        image
+    }
+
+    fn render_with(
+        &self,
+        info: RenderInfo,
+        integrator: &impl Integrator,
+    ) -> HdrImage {
         let thread_count = std::thread::available_parallelism()
             .map(NonZeroUsize::get)
             .unwrap_or(1);
@@ -51,7 +221,7 @@ impl Scene {
         eprintln!("[RENDER] Chunks: {}", chunk_count);
 
         let (completed_tx, completed) = mpsc::channel();
-        
+
         let completed_chunks = std::thread::scope(|scope| {
             let chunks = Arc::new(Mutex::new(chunks));
 
@@ -59,7 +229,7 @@ impl Scene {
                 let chunks = Arc::clone(&chunks);
                 let tx = completed_tx.clone();
                 scope.spawn(|| {
-                    self.spawn_worker(info, chunks, tx);
+                    self.spawn_worker(info, integrator, chunks, tx);
                 });
             }
 
@@ -112,6 +282,7 @@ impl Scene {
     fn spawn_worker(
         &self,
         render_info: RenderInfo,
+        integrator: &impl Integrator,
         chunks: Arc<Mutex<Vec<ChunkInfo>>>,
         completed: mpsc::Sender<Chunk>,
     ) {
@@ -128,22 +299,22 @@ impl Scene {
                 for x in 0..chunk.width {
                     let mut color = Color::black();
                     for _ in 0..render_info.samples {
-                        let u = ((chunk.x + x) as f32 + fastrand::f32()) /
+                        let u = ((chunk.x + x) as f32 + random_f32()) /
                             render_info.width as f32;
-                        let v = ((chunk.y + y) as f32 + fastrand::f32()) /
+                        let v = ((chunk.y + y) as f32 + random_f32()) /
                             render_info.height as f32;
 
                         color = color +
                             self.camera.trace(
                                 u,
                                 v,
-                                render_info.max_depth,
+                                render_info.sample_spacing(),
                                 &self.world,
+                                integrator,
                             );
                     }
                     color = color / render_info.samples as f32;
-                    *image.get_pixel_mut(x, y) =
-                        color.to_rgb(render_info.gamma);
+                    *image.get_pixel_mut(x, y) = color.to_rgb_f32();
                 }
             }
 
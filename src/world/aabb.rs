@@ -0,0 +1,138 @@
+use na::Vector3;
+
+use super::ray::Ray;
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    #[inline(always)]
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// Box containing nothing, the identity for [`Aabb::union`].
+    #[inline(always)]
+    pub fn empty() -> Self {
+        Self {
+            min: Vector3::repeat(f32::INFINITY),
+            max: Vector3::repeat(f32::NEG_INFINITY),
+        }
+    }
+
+    #[inline(always)]
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    /// Box shared by both boxes, which is empty if they don't overlap.
+    #[inline(always)]
+    pub fn intersection(&self, other: &Aabb) -> Self {
+        Self {
+            min: self.min.sup(&other.min),
+            max: self.max.inf(&other.max),
+        }
+    }
+
+    #[inline(always)]
+    pub fn include(&self, point: &Vector3<f32>) -> Self {
+        Self {
+            min: self.min.inf(point),
+            max: self.max.sup(point),
+        }
+    }
+
+    /// Box grown by a small margin on every side, so flat objects still get
+    /// a box with volume.
+    #[inline(always)]
+    pub fn padded(&self) -> Self {
+        let margin = Vector3::repeat(1e-4);
+        Self {
+            min: self.min - margin,
+            max: self.max + margin,
+        }
+    }
+
+    /// The eight corners of the box.
+    pub fn corners(&self) -> [Vector3<f32>; 8] {
+        std::array::from_fn(|i| {
+            Vector3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            )
+        })
+    }
+
+    #[inline(always)]
+    pub fn centroid(&self) -> Vector3<f32> {
+        0.5 * (self.min + self.max)
+    }
+
+    #[inline(always)]
+    pub fn extent(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    #[inline(always)]
+    pub fn longest_axis(&self) -> usize {
+        self.extent().imax()
+    }
+
+    #[inline(always)]
+    pub fn surface_area(&self) -> f32 {
+        let e = self.extent().sup(&Vector3::zeros());
+        2.0 * (e.x * e.y + e.y * e.z + e.z * e.x)
+    }
+
+    /// Slab test. Returns the parametric entry distance if the ray overlaps
+    /// the box within `[t_min, t_max]`.
+    #[inline(always)]
+    pub fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
+        self.overlap(ray, t_min, t_max).map(|(entry, _)| entry)
+    }
+
+    /// Parametric entry and exit distances of the part of the ray within
+    /// `[t_min, t_max]` that lies inside the box.
+    #[inline(always)]
+    pub fn overlap(
+        &self,
+        ray: &Ray,
+        mut t_min: f32,
+        mut t_max: f32,
+    ) -> Option<(f32, f32)> {
+        let origin = ray.origin();
+        let direction = ray.direction();
+
+        for axis in 0..3 {
+            let inv = direction[axis].recip();
+            let mut t0 = (self.min[axis] - origin[axis]) * inv;
+            let mut t1 = (self.max[axis] - origin[axis]) * inv;
+            if inv < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // `max`/`min` drop the NaN that appears for rays lying exactly in
+            // a slab plane.
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return None;
+            }
+        }
+
+        Some((t_min, t_max))
+    }
+}
+
+/// Objects with a finite extent that can be put into a BVH.
+pub trait Bounded {
+    /// Box enclosing the object, or `None` if it is unbounded.
+    fn bounding_box(&self) -> Option<Aabb>;
+}
@@ -0,0 +1,279 @@
+use super::{aabb::Aabb, ray::Ray};
+
+/// Maximum number of items kept in a single leaf.
+const MAX_LEAF_SIZE: usize = 4;
+/// Number of buckets used to evaluate the surface area heuristic.
+const SAH_BUCKETS: usize = 12;
+/// Depth below which nodes are split in the middle rather than by the
+/// surface area heuristic, so that the hierarchy stays shallow enough for
+/// the traversal stack.
+const SAH_MAX_DEPTH: usize = 32;
+/// Size of the traversal stack, which holds at most one more node than the
+/// hierarchy is deep.
+const STACK_SIZE: usize = 64;
+
+/// Bounding volume hierarchy over a list of boxes.
+///
+/// The hierarchy only stores indices into the list it was built from, the
+/// items themselves are intersected through the closure given to
+/// [`Bvh::traverse`].
+#[derive(Debug, Default)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+}
+
+#[derive(Debug)]
+struct BvhNode {
+    bounds: Aabb,
+    /// First item of a leaf, or the index of the second child of an interior
+    /// node (the first child directly follows its parent).
+    offset: usize,
+    /// Number of items in a leaf, zero for interior nodes.
+    count: usize,
+    axis: usize,
+}
+
+impl Bvh {
+    pub fn new(bounds: &[Aabb]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(bounds.len() * 2),
+            indices: (0..bounds.len()).collect(),
+        };
+        if !bounds.is_empty() {
+            bvh.build(bounds, 0, bounds.len(), 0);
+        }
+        bvh
+    }
+
+    /// Box enclosing everything in the hierarchy.
+    pub fn bounds(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map(|node| node.bounds)
+            .unwrap_or_else(Aabb::empty)
+    }
+
+    /// Recomputes all node boxes from updated item boxes while keeping the
+    /// tree topology, which is much cheaper than a rebuild when items only
+    /// move a little.
+    pub fn refit(&mut self, bounds: &[Aabb]) {
+        // Children always come after their parent, so a reverse sweep sees
+        // them before the parent.
+        for i in (0..self.nodes.len()).rev() {
+            let node = &self.nodes[i];
+            let new_bounds = if node.count > 0 {
+                self.indices[node.offset..node.offset + node.count]
+                    .iter()
+                    .fold(Aabb::empty(), |acc, &index| {
+                        acc.union(&bounds[index])
+                    })
+            } else {
+                self.nodes[i + 1]
+                    .bounds
+                    .union(&self.nodes[node.offset].bounds)
+            };
+            self.nodes[i].bounds = new_bounds;
+        }
+    }
+
+    fn build(
+        &mut self,
+        bounds: &[Aabb],
+        start: usize,
+        end: usize,
+        depth: usize,
+    ) -> usize {
+        let node_bounds = self.indices[start..end]
+            .iter()
+            .fold(Aabb::empty(), |acc, &index| acc.union(&bounds[index]));
+        let node_index = self.nodes.len();
+        self.nodes.push(BvhNode {
+            bounds: node_bounds,
+            offset: start,
+            count: end - start,
+            axis: 0,
+        });
+
+        let count = end - start;
+        if count <= MAX_LEAF_SIZE {
+            return node_index;
+        }
+
+        let centroid_bounds = self.indices[start..end]
+            .iter()
+            .fold(Aabb::empty(), |acc, &index| {
+                acc.include(&bounds[index].centroid())
+            });
+        let axis = centroid_bounds.longest_axis();
+        let axis_min = centroid_bounds.min[axis];
+        let axis_extent = centroid_bounds.extent()[axis];
+        if axis_extent <= 0.0 {
+            return node_index;
+        }
+
+        let bucket_of = |index: usize| {
+            let offset =
+                (bounds[index].centroid()[axis] - axis_min) / axis_extent;
+            ((offset * SAH_BUCKETS as f32) as usize).min(SAH_BUCKETS - 1)
+        };
+
+        let mut buckets = [(0usize, Aabb::empty()); SAH_BUCKETS];
+        for &index in &self.indices[start..end] {
+            let bucket = &mut buckets[bucket_of(index)];
+            bucket.0 += 1;
+            bucket.1 = bucket.1.union(&bounds[index]);
+        }
+
+        // Cost of splitting after each bucket, relative to the leaf cost.
+        let mut best_split = 0;
+        let mut best_cost = f32::INFINITY;
+        for split in 0..SAH_BUCKETS - 1 {
+            let (left, right) = buckets.split_at(split + 1);
+            let side = |side: &[(usize, Aabb)]| {
+                side.iter().fold((0, Aabb::empty()), |acc, bucket| {
+                    (acc.0 + bucket.0, acc.1.union(&bucket.1))
+                })
+            };
+            let (left_count, left_bounds) = side(left);
+            let (right_count, right_bounds) = side(right);
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+            let cost = left_count as f32 * left_bounds.surface_area() +
+                right_count as f32 * right_bounds.surface_area();
+            if cost < best_cost {
+                best_cost = cost;
+                best_split = split;
+            }
+        }
+
+        let mut mid = start;
+        if best_cost.is_finite() && depth < SAH_MAX_DEPTH {
+            for i in start..end {
+                if bucket_of(self.indices[i]) <= best_split {
+                    self.indices.swap(i, mid);
+                    mid += 1;
+                }
+            }
+        }
+        if mid == start || mid == end {
+            // All centroids fell into one bucket, split in the middle.
+            mid = (start + end) / 2;
+            self.indices[start..end].sort_by(|&a, &b| {
+                bounds[a].centroid()[axis]
+                    .total_cmp(&bounds[b].centroid()[axis])
+            });
+        }
+
+        self.build(bounds, start, mid, depth + 1);
+        let second = self.build(bounds, mid, end, depth + 1);
+
+        let node = &mut self.nodes[node_index];
+        node.offset = second;
+        node.count = 0;
+        node.axis = axis;
+        node_index
+    }
+
+    /// Finds the closest item along `ray`.
+    ///
+    /// `hit` is called with an item index and the current closest distance
+    /// and returns the distance and payload of an intersection closer than
+    /// that, if any. Every visited node and tested item is added to `cost`.
+    pub fn traverse<T>(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        cost: &mut u32,
+        mut hit: impl FnMut(usize, f32) -> Option<(f32, T)>,
+    ) -> Option<T> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut closest = t_max;
+        let mut result = None;
+        let mut stack = [0u32; STACK_SIZE];
+        let mut stack_len = 1;
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let node_index = stack[stack_len] as usize;
+            let node = &self.nodes[node_index];
+            *cost += 1;
+            if node.bounds.hit(ray, t_min, closest).is_none() {
+                continue;
+            }
+
+            if node.count > 0 {
+                for &index in
+                    &self.indices[node.offset..node.offset + node.count]
+                {
+                    *cost += 1;
+                    if let Some((t, payload)) = hit(index, closest) {
+                        closest = t;
+                        result = Some(payload);
+                    }
+                }
+            } else {
+                // Visit the child on the near side first.
+                let (near, far) = if ray.direction()[node.axis] < 0.0 {
+                    (node.offset, node_index + 1)
+                } else {
+                    (node_index + 1, node.offset)
+                };
+                stack[stack_len] = far as u32;
+                stack[stack_len + 1] = near as u32;
+                stack_len += 2;
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Vector3;
+
+    use super::*;
+
+    fn depth(bvh: &Bvh, node_index: usize) -> usize {
+        let node = &bvh.nodes[node_index];
+        if node.count > 0 {
+            1
+        } else {
+            1 + depth(bvh, node_index + 1).max(depth(bvh, node.offset))
+        }
+    }
+
+    #[test]
+    fn skewed_hierarchies_fit_the_traversal_stack() {
+        // Boxes spaced exponentially make the surface area heuristic peel
+        // off a single box per level.
+        let bounds: Vec<Aabb> = (0..200)
+            .map(|i| {
+                let corner = Vector3::x() * 1.5f32.powi(i);
+                Aabb::new(corner, corner + Vector3::repeat(0.5))
+            })
+            .collect();
+        let bvh = Bvh::new(&bounds);
+        assert!(depth(&bvh, 0) < STACK_SIZE);
+
+        // Many boxes share faces, so only the distances are compared.
+        for aabb in &bounds {
+            let ray = Ray::new(aabb.centroid() - Vector3::z(), Vector3::z());
+            let expected = bounds
+                .iter()
+                .filter_map(|other| other.hit(&ray, 0.0, f32::INFINITY))
+                .min_by(f32::total_cmp);
+            let found =
+                bvh.traverse(&ray, 0.0, f32::INFINITY, &mut 0, |j, t_max| {
+                    bounds[j].hit(&ray, 0.0, t_max).map(|t| (t, t))
+                });
+            assert_eq!(found, expected);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use na::Vector3;
+
+use crate::{
+    color::Color, math::orthonormal_basis, sampler::Sampler2D,
+    world::object::HitInfo,
+};
+
+/// Offset of the texture coordinates for finite differences of bump maps
+/// when a hit has no footprint.
+const BUMP_DELTA: f32 = 0.0005;
+
+/// Detail that materials add to the normals of a surface without changing
+/// its shape, oriented by the tangents of hits.
+#[derive(Debug)]
+pub enum Bump {
+    /// Normals in tangent space stored as colors, with red along the
+    /// tangent, green along the bitangent and blue along the normal, each
+    /// mapped from `[-1, 1]` to `[0, 1]`. Images should be read as
+    /// [`ColorSpace::Raw`](crate::sampler::ColorSpace::Raw).
+    NormalMap(Sampler2D<Color>),
+    /// Heights along the normal, `scale` for a value of one.
+    HeightMap { heights: Sampler2D<f32>, scale: f32 },
+}
+
+impl Bump {
+    /// Shading normal at `hit_info`, on the same side as its normal.
+    pub fn normal(&self, hit_info: &HitInfo) -> Vector3<f32> {
+        let normal = hit_info.normal;
+        let (tangent, bitangent) = tangents(hit_info);
+        match self {
+            Bump::NormalMap(map) => {
+                let color = map.sample_hit(hit_info);
+                let t = (tangent - normal * normal.dot(&tangent)).normalize();
+                // Mirrored texture coordinates flip the bitangent.
+                let mut b = normal.cross(&t);
+                if b.dot(&bitangent) < 0.0 {
+                    b = -b;
+                }
+                let mapped = |channel: f32| 2.0 * channel - 1.0;
+                (mapped(color.r()) * t +
+                    mapped(color.g()) * b +
+                    mapped(color.b()) * normal)
+                    .try_normalize(0.0)
+                    .unwrap_or(normal)
+            }
+            Bump::HeightMap { heights, scale } => {
+                // Forward differences over about the footprint, following
+                // the surface so that solid textures are stepped as well.
+                let footprint = &hit_info.footprint;
+                let delta = |a: f32, b: f32| {
+                    let delta = 0.5 * (a.abs() + b.abs());
+                    if delta > 0.0 {
+                        delta
+                    } else {
+                        BUMP_DELTA
+                    }
+                };
+                let du = delta(footprint.du_dx, footprint.du_dy);
+                let dv = delta(footprint.dv_dx, footprint.dv_dy);
+                let shifted = |du: f32, dv: f32| {
+                    let offset = du * tangent + dv * bitangent;
+                    heights.sample_hit(&HitInfo {
+                        position: hit_info.position + offset,
+                        local_position: hit_info.local_position + offset,
+                        u: hit_info.u + du,
+                        v: hit_info.v + dv,
+                        ..*hit_info
+                    })
+                };
+                let height = heights.sample_hit(hit_info);
+                let slope_u = scale * (shifted(du, 0.0) - height) / du;
+                let slope_v = scale * (shifted(0.0, dv) - height) / dv;
+
+                let bumped = (tangent + slope_u * normal)
+                    .cross(&(bitangent + slope_v * normal))
+                    .try_normalize(0.0)
+                    .unwrap_or(normal);
+                if bumped.dot(&normal) < 0.0 {
+                    -bumped
+                } else {
+                    bumped
+                }
+            }
+        }
+    }
+}
+
+/// Tangent and bitangent of `hit_info`, made up from the normal where the
+/// hit has none.
+#[inline(always)]
+fn tangents(hit_info: &HitInfo) -> (Vector3<f32>, Vector3<f32>) {
+    let (tangent, bitangent) = (hit_info.tangent, hit_info.bitangent);
+    if tangent.cross(&bitangent).magnitude_squared() > 0.0 {
+        (tangent, bitangent)
+    } else {
+        orthonormal_basis(&hit_info.normal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        material::{lambertian::Lambertian, Material},
+        object::{quad::Quad, Hit},
+        ray::Ray,
+    };
+
+    /// Quad across the xy plane facing +z, with `u` along x and `v` along
+    /// y, hit in its middle.
+    fn hit_quad(material: Material, test: impl FnOnce(&HitInfo)) {
+        let quad = Quad::new(
+            Vector3::new(-1.0, -1.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(0.0, 2.0, 0.0),
+            material,
+        );
+        let ray = Ray::new(Vector3::new(0.1, 0.2, 5.0), -Vector3::z());
+        test(&quad.hit(&ray, 0.0, f32::INFINITY).unwrap());
+    }
+
+    fn gray() -> Lambertian {
+        Lambertian::new(Sampler2D::Static(Color::gray(0.5)))
+    }
+
+    #[test]
+    fn flat_normal_maps_keep_the_normal() {
+        let flat = Sampler2D::Static(Color::rgb(0.5, 0.5, 1.0));
+        hit_quad(Material::Lambertian(gray().with_normal_map(flat)), |hit| {
+            // Hits keep the geometric normal, the material bumps it.
+            assert_eq!(hit.normal, Vector3::z());
+            let shading = hit.material.shading_normal(hit);
+            assert!((shading - hit.normal).magnitude() < 1e-6, "{shading:?}");
+        });
+    }
+
+    #[test]
+    fn height_ramps_tilt_the_normal_downhill() {
+        // Rising along u, so towards +x, by half the quad's width.
+        let ramp = Sampler2D::function(|u, _| u);
+        let bumped = gray().with_bump_map(ramp, 1.0);
+        hit_quad(Material::Lambertian(bumped), |hit| {
+            let shading = hit.material.shading_normal(hit);
+            assert!((shading.magnitude() - 1.0).abs() < 1e-5);
+            // The slope is a half along x, so the normal leans back by it.
+            let expected = Vector3::new(-0.5, 0.0, 1.0).normalize();
+            assert!((shading - expected).magnitude() < 1e-3, "{shading:?}");
+        });
+    }
+}
@@ -0,0 +1,73 @@
+use na::Vector3;
+
+use super::{Scatter, ScatterInfo};
+use crate::{
+    color::Color,
+    math::random_f32,
+    world::{object::HitInfo, ray::Ray},
+};
+
+/// Smooth glass-like interface that either reflects or refracts, choosing
+/// stochastically according to the Fresnel reflectance.
+#[derive(Debug)]
+pub struct Dielectric {
+    ior: f32,
+}
+
+impl Dielectric {
+    pub fn new(ior: f32) -> Self {
+        Self { ior }
+    }
+}
+
+/// Schlick's approximation of the Fresnel reflectance.
+#[inline(always)]
+fn reflectance(cos_theta: f32, eta: f32) -> f32 {
+    let r0 = ((1.0 - eta) / (1.0 + eta)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cos_theta).powi(5)
+}
+
+#[inline(always)]
+pub fn reflect(
+    direction: &Vector3<f32>,
+    normal: &Vector3<f32>,
+) -> Vector3<f32> {
+    direction - 2.0 * direction.dot(normal) * normal
+}
+
+impl Scatter for Dielectric {
+    #[inline(always)]
+    fn scatter(&self, ray: &Ray, hit_info: &HitInfo) -> Option<ScatterInfo> {
+        let direction = ray.direction().normalize();
+        let (normal, eta) = if direction.dot(&hit_info.normal) < 0.0 {
+            (hit_info.normal, self.ior.recip())
+        } else {
+            (-hit_info.normal, self.ior)
+        };
+
+        let cos_theta = (-direction.dot(&normal)).min(1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+        let scattered = if eta * sin_theta > 1.0 ||
+            reflectance(cos_theta, eta) > random_f32()
+        {
+            reflect(&direction, &normal)
+        } else {
+            let perpendicular = eta * (direction + cos_theta * normal);
+            let parallel =
+                -(1.0 - perpendicular.magnitude_squared()).abs().sqrt() *
+                    normal;
+            perpendicular + parallel
+        };
+
+        Some(ScatterInfo {
+            attenuation: Color::gray(1.0),
+            scattered_ray: Ray::with_time(
+                hit_info.position,
+                scattered,
+                ray.time(),
+            ),
+            pdf: None,
+        })
+    }
+}
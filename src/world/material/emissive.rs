@@ -0,0 +1,37 @@
+use super::{Scatter, ScatterInfo};
+use crate::{
+    color::Color,
+    sampler::Sampler2D,
+    world::{object::HitInfo, ray::Ray},
+};
+
+/// Diffuse area light. Emits from the side the surface normal points to and
+/// absorbs everything that hits it.
+#[derive(Debug)]
+pub struct Emissive {
+    emission: Sampler2D<Color>,
+    strength: Sampler2D<f32>,
+}
+
+impl Emissive {
+    pub fn new(emission: Sampler2D<Color>, strength: Sampler2D<f32>) -> Self {
+        Self { emission, strength }
+    }
+}
+
+impl Scatter for Emissive {
+    #[inline(always)]
+    fn scatter(&self, _ray: &Ray, _hit_info: &HitInfo) -> Option<ScatterInfo> {
+        None
+    }
+
+    #[inline(always)]
+    fn emitted(&self, ray: &Ray, hit_info: &HitInfo) -> Color {
+        if ray.direction().dot(&hit_info.normal) < 0.0 {
+            self.strength.sample_hit(hit_info) *
+                self.emission.sample_hit(hit_info)
+        } else {
+            Color::black()
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use na::Vector3;
+
+use super::{bump::Bump, Scatter, ScatterInfo};
+use crate::{
+    color::Color,
+    math::random_vector2,
+    sampler::Sampler2D,
+    sampling::{cosine_hemisphere, cosine_hemisphere_pdf},
+    world::{object::HitInfo, ray::Ray},
+};
+
+/// Ideal diffuse reflector. Two-sided: the normal is flipped towards the
+/// viewer before scattering.
+#[derive(Debug)]
+pub struct Lambertian {
+    albedo: Sampler2D<Color>,
+    opacity: Sampler2D<f32>,
+    bump: Option<Bump>,
+}
+
+impl Lambertian {
+    pub fn new(albedo: Sampler2D<Color>) -> Self {
+        Self {
+            albedo,
+            opacity: Sampler2D::Static(1.0),
+            bump: None,
+        }
+    }
+
+    /// Masks the surface: rays pass through it where `opacity` is below
+    /// one, as often as it is below one.
+    pub fn with_opacity(mut self, opacity: Sampler2D<f32>) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    #[inline(always)]
+    pub fn opacity(&self, hit_info: &HitInfo) -> f32 {
+        self.opacity.sample_hit(hit_info)
+    }
+
+    /// Perturbs the normals by a tangent space normal map, see
+    /// [`Bump::NormalMap`].
+    pub fn with_normal_map(mut self, normals: Sampler2D<Color>) -> Self {
+        self.bump = Some(Bump::NormalMap(normals));
+        self
+    }
+
+    /// Perturbs the normals as if the surface were raised by `heights`
+    /// times `scale`, see [`Bump::HeightMap`].
+    pub fn with_bump_map(
+        mut self,
+        heights: Sampler2D<f32>,
+        scale: f32,
+    ) -> Self {
+        self.bump = Some(Bump::HeightMap { heights, scale });
+        self
+    }
+
+    #[inline(always)]
+    pub fn bump(&self) -> Option<&Bump> {
+        self.bump.as_ref()
+    }
+}
+
+#[inline(always)]
+fn facing_normal(wo: &Vector3<f32>, hit_info: &HitInfo) -> Vector3<f32> {
+    if hit_info.normal.dot(wo) < 0.0 {
+        -hit_info.normal
+    } else {
+        hit_info.normal
+    }
+}
+
+/// Diffuse reflection off `hit_info` with the given albedo, shared with
+/// materials that have a diffuse part.
+#[inline(always)]
+pub(super) fn scatter_diffuse(
+    albedo: Color,
+    ray: &Ray,
+    hit_info: &HitInfo,
+) -> ScatterInfo {
+    let normal = facing_normal(&-ray.direction(), hit_info);
+    let direction = cosine_hemisphere(&normal, random_vector2());
+
+    ScatterInfo {
+        attenuation: albedo,
+        scattered_ray: Ray::with_time(hit_info.position, direction, ray.time()),
+        pdf: Some(cosine_hemisphere_pdf(&normal, &direction)),
+    }
+}
+
+/// Density of [`scatter_diffuse`] producing `wi` from `wo`.
+#[inline(always)]
+pub(super) fn diffuse_pdf(
+    wo: &Vector3<f32>,
+    wi: &Vector3<f32>,
+    hit_info: &HitInfo,
+) -> f32 {
+    cosine_hemisphere_pdf(&facing_normal(wo, hit_info), wi)
+}
+
+impl Scatter for Lambertian {
+    #[inline(always)]
+    fn scatter(&self, ray: &Ray, hit_info: &HitInfo) -> Option<ScatterInfo> {
+        Some(scatter_diffuse(
+            self.albedo.sample_hit(hit_info),
+            ray,
+            hit_info,
+        ))
+    }
+
+    #[inline(always)]
+    fn eval(
+        &self,
+        wo: &Vector3<f32>,
+        wi: &Vector3<f32>,
+        hit_info: &HitInfo,
+    ) -> Color {
+        diffuse_pdf(wo, wi, hit_info) * self.albedo.sample_hit(hit_info)
+    }
+
+    #[inline(always)]
+    fn pdf(
+        &self,
+        wo: &Vector3<f32>,
+        wi: &Vector3<f32>,
+        hit_info: &HitInfo,
+    ) -> f32 {
+        diffuse_pdf(wo, wi, hit_info)
+    }
+}
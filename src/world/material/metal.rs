@@ -1,40 +1,148 @@
-use na::{Reflection3, Unit};
+use na::{Reflection3, Unit, Vector3};
 
-use super::{Scatter, ScatterInfo};
+use super::{
+    bump::Bump,
+    lambertian::{diffuse_pdf, scatter_diffuse},
+    Scatter, ScatterInfo,
+};
 use crate::{
     color::Color,
-    math::random_vector3_in_unit_hemisphere,
+    math::{hash_f32, random_vector3_in_unit_hemisphere},
     sampler::Sampler2D,
     world::{object::HitInfo, ray::Ray},
 };
 
+/// Metal whose reflection is blurred by `roughness`.
+///
+/// Where `metalness` is below one, the surface is in that part a diffuse
+/// reflector of the same albedo, as in the metal-roughness maps of PBR
+/// assets. Which of the two a hit is picked by hashing its position, so that
+/// scattering and evaluating the same hit agree.
 #[derive(Debug)]
 pub struct Metallic {
     albedo: Sampler2D<Color>,
-    roughness: f32,
+    roughness: Sampler2D<f32>,
+    metalness: Sampler2D<f32>,
+    opacity: Sampler2D<f32>,
+    bump: Option<Bump>,
 }
 
 impl Metallic {
-    pub fn new(albedo: Sampler2D<Color>, roughness: f32) -> Self {
-        Self { albedo, roughness }
+    pub fn new(albedo: Sampler2D<Color>, roughness: Sampler2D<f32>) -> Self {
+        Self {
+            albedo,
+            roughness,
+            metalness: Sampler2D::Static(1.0),
+            opacity: Sampler2D::Static(1.0),
+            bump: None,
+        }
+    }
+
+    pub fn with_metalness(mut self, metalness: Sampler2D<f32>) -> Self {
+        self.metalness = metalness;
+        self
+    }
+
+    /// Masks the surface, see [`Lambertian::with_opacity`].
+    ///
+    /// [`Lambertian::with_opacity`]: super::lambertian::Lambertian::with_opacity
+    pub fn with_opacity(mut self, opacity: Sampler2D<f32>) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    #[inline(always)]
+    pub fn opacity(&self, hit_info: &HitInfo) -> f32 {
+        self.opacity.sample_hit(hit_info)
+    }
+
+    /// Perturbs the normals by a tangent space normal map, see
+    /// [`Bump::NormalMap`].
+    pub fn with_normal_map(mut self, normals: Sampler2D<Color>) -> Self {
+        self.bump = Some(Bump::NormalMap(normals));
+        self
+    }
+
+    /// Perturbs the normals as if the surface were raised by `heights`
+    /// times `scale`, see [`Bump::HeightMap`].
+    pub fn with_bump_map(
+        mut self,
+        heights: Sampler2D<f32>,
+        scale: f32,
+    ) -> Self {
+        self.bump = Some(Bump::HeightMap { heights, scale });
+        self
+    }
+
+    #[inline(always)]
+    pub fn bump(&self) -> Option<&Bump> {
+        self.bump.as_ref()
+    }
+
+    /// Whether the surface reflects diffusely at `hit_info`.
+    #[inline(always)]
+    fn is_diffuse(&self, hit_info: &HitInfo) -> bool {
+        match &self.metalness {
+            Sampler2D::Static(metalness) if *metalness >= 1.0 => false,
+            metalness => {
+                hash_f32(&hit_info.position) >= metalness.sample_hit(hit_info)
+            }
+        }
     }
 }
 
 impl Scatter for Metallic {
     #[inline(always)]
     fn scatter(&self, ray: &Ray, hit_info: &HitInfo) -> Option<ScatterInfo> {
+        let attenuation = self.albedo.sample_hit(hit_info);
+        if self.is_diffuse(hit_info) {
+            return Some(scatter_diffuse(attenuation, ray, hit_info));
+        }
+
         let surface_normal = hit_info.normal +
-            self.roughness * random_vector3_in_unit_hemisphere();
+            self.roughness.sample_hit(hit_info) *
+                random_vector3_in_unit_hemisphere(&hit_info.normal);
         let mut scatter_direction = ray.direction();
 
         Reflection3::new(Unit::new_normalize(surface_normal), 0.0)
             .reflect(&mut scatter_direction);
 
-        let attenuation = self.albedo.sample(hit_info.u, hit_info.v);
-
         Some(ScatterInfo {
             attenuation,
-            scattered_ray: Ray::new(hit_info.position, scatter_direction),
+            scattered_ray: Ray::with_time(
+                hit_info.position,
+                scatter_direction,
+                ray.time(),
+            ),
+            pdf: None,
         })
     }
+
+    #[inline(always)]
+    fn eval(
+        &self,
+        wo: &Vector3<f32>,
+        wi: &Vector3<f32>,
+        hit_info: &HitInfo,
+    ) -> Color {
+        if self.is_diffuse(hit_info) {
+            diffuse_pdf(wo, wi, hit_info) * self.albedo.sample_hit(hit_info)
+        } else {
+            Color::black()
+        }
+    }
+
+    #[inline(always)]
+    fn pdf(
+        &self,
+        wo: &Vector3<f32>,
+        wi: &Vector3<f32>,
+        hit_info: &HitInfo,
+    ) -> f32 {
+        if self.is_diffuse(hit_info) {
+            diffuse_pdf(wo, wi, hit_info)
+        } else {
+            0.0
+        }
+    }
 }
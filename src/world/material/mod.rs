@@ -1,28 +1,188 @@
+pub mod bump;
+pub mod dielectric;
+pub mod emissive;
+pub mod lambertian;
 pub mod metal;
+pub mod volume;
 
-use self::metal::Metallic;
+use na::Vector3;
+
+use self::{
+    bump::Bump, dielectric::Dielectric, emissive::Emissive,
+    lambertian::Lambertian, metal::Metallic, volume::Volume,
+};
 use super::{object::HitInfo, ray::Ray};
 use crate::color::Color;
 
 #[derive(Debug)]
 pub enum Material {
     Metallic(Metallic),
+    Lambertian(Lambertian),
+    Dielectric(Dielectric),
+    Emissive(Emissive),
+    Volume(Volume),
 }
 
+/// Surface interaction of a material.
+///
+/// Directions passed to `eval` and `pdf` are normalized and point away from
+/// the surface: `wo` towards the viewer and `wi` towards the light.
 pub trait Scatter {
     fn scatter(&self, ray: &Ray, hit_info: &HitInfo) -> Option<ScatterInfo>;
+
+    /// Radiance emitted from the surface back along `ray`.
+    fn emitted(&self, _ray: &Ray, _hit_info: &HitInfo) -> Color {
+        Color::black()
+    }
+
+    /// BSDF times the cosine of `wi`, or the phase function inside a
+    /// medium. Lobes that can only be sampled, such as
+    /// mirrors and glass, evaluate to black.
+    fn eval(
+        &self,
+        _wo: &Vector3<f32>,
+        _wi: &Vector3<f32>,
+        _hit_info: &HitInfo,
+    ) -> Color {
+        Color::black()
+    }
+
+    /// Solid angle density with which `scatter` produces `wi` from `wo`.
+    fn pdf(
+        &self,
+        _wo: &Vector3<f32>,
+        _wi: &Vector3<f32>,
+        _hit_info: &HitInfo,
+    ) -> f32 {
+        0.0
+    }
 }
 
 pub struct ScatterInfo {
+    /// BSDF times cosine divided by the sampling density.
     pub attenuation: Color,
     pub scattered_ray: Ray,
+    /// Density of the scattered direction, or `None` if the lobe can't be
+    /// evaluated with `eval` (specular and other sample-only lobes).
+    pub pdf: Option<f32>,
+}
+
+impl Material {
+    #[inline(always)]
+    pub fn is_emissive(&self) -> bool {
+        matches!(self, Material::Emissive(_))
+    }
+
+    /// Whether the material scatters inside a medium rather than at a
+    /// surface, so the hit normal carries no meaning.
+    #[inline(always)]
+    pub fn is_volume(&self) -> bool {
+        matches!(self, Material::Volume(_))
+    }
+
+    /// Chance that a ray stops at `hit_info` rather than passing through
+    /// the surface.
+    #[inline(always)]
+    pub fn opacity(&self, hit_info: &HitInfo) -> f32 {
+        match self {
+            Material::Lambertian(lambert) => lambert.opacity(hit_info),
+            Material::Metallic(metal) => metal.opacity(hit_info),
+            _ => 1.0,
+        }
+    }
+
+    /// Detail the material adds to the normals of surfaces.
+    #[inline(always)]
+    pub fn bump(&self) -> Option<&Bump> {
+        match self {
+            Material::Lambertian(lambert) => lambert.bump(),
+            Material::Metallic(metal) => metal.bump(),
+            _ => None,
+        }
+    }
+
+    /// Normal the material shades `hit_info` with: the geometric normal of
+    /// the hit, perturbed by the material's bump if it has one.
+    #[inline(always)]
+    pub fn shading_normal(&self, hit_info: &HitInfo) -> Vector3<f32> {
+        match self.bump() {
+            Some(bump) => bump.normal(hit_info),
+            None => hit_info.normal,
+        }
+    }
+
+    /// `hit_info` with the shading normal in place of the geometric one.
+    /// Hits keep their geometric normal, so only scattering pays for bumps,
+    /// and shadow rays and visibility tests never do.
+    #[inline(always)]
+    fn shaded<'a>(&self, hit_info: &HitInfo<'a>) -> HitInfo<'a> {
+        HitInfo {
+            normal: self.shading_normal(hit_info),
+            ..*hit_info
+        }
+    }
 }
 
 impl Scatter for Material {
     #[inline(always)]
     fn scatter(&self, ray: &Ray, hit_info: &HitInfo) -> Option<ScatterInfo> {
         match self {
-            Material::Metallic(metal) => metal.scatter(ray, hit_info),
+            Material::Metallic(metal) => {
+                metal.scatter(ray, &self.shaded(hit_info))
+            }
+            Material::Lambertian(lambert) => {
+                lambert.scatter(ray, &self.shaded(hit_info))
+            }
+            Material::Dielectric(glass) => glass.scatter(ray, hit_info),
+            Material::Emissive(light) => light.scatter(ray, hit_info),
+            Material::Volume(volume) => volume.scatter(ray, hit_info),
+        }
+    }
+
+    #[inline(always)]
+    fn emitted(&self, ray: &Ray, hit_info: &HitInfo) -> Color {
+        match self {
+            Material::Emissive(light) => light.emitted(ray, hit_info),
+            Material::Volume(volume) => volume.emitted(ray, hit_info),
+            _ => Color::black(),
+        }
+    }
+
+    #[inline(always)]
+    fn eval(
+        &self,
+        wo: &Vector3<f32>,
+        wi: &Vector3<f32>,
+        hit_info: &HitInfo,
+    ) -> Color {
+        match self {
+            Material::Lambertian(lambert) => {
+                lambert.eval(wo, wi, &self.shaded(hit_info))
+            }
+            Material::Metallic(metal) => {
+                metal.eval(wo, wi, &self.shaded(hit_info))
+            }
+            Material::Volume(volume) => volume.eval(wo, wi, hit_info),
+            _ => Color::black(),
+        }
+    }
+
+    #[inline(always)]
+    fn pdf(
+        &self,
+        wo: &Vector3<f32>,
+        wi: &Vector3<f32>,
+        hit_info: &HitInfo,
+    ) -> f32 {
+        match self {
+            Material::Lambertian(lambert) => {
+                lambert.pdf(wo, wi, &self.shaded(hit_info))
+            }
+            Material::Metallic(metal) => {
+                metal.pdf(wo, wi, &self.shaded(hit_info))
+            }
+            Material::Volume(volume) => volume.pdf(wo, wi, hit_info),
+            _ => 0.0,
         }
     }
 }
@@ -0,0 +1,150 @@
+use na::Vector3;
+
+use super::{Scatter, ScatterInfo};
+use crate::{
+    color::Color,
+    math::random_vector2,
+    sampling::{
+        henyey_greenstein, henyey_greenstein_pdf, uniform_sphere,
+        uniform_sphere_pdf,
+    },
+    world::{object::HitInfo, ray::Ray, voxel::VoxelGrid},
+};
+
+/// Angular distribution of light scattered inside a medium.
+#[derive(Debug, Clone, Copy)]
+pub enum PhaseFunction {
+    /// Scatters equally in all directions.
+    Isotropic,
+    /// Henyey-Greenstein lobe with mean cosine `g` in `(-1, 1)`: positive
+    /// values scatter forward, as in fog and milk, negative values backward.
+    HenyeyGreenstein { g: f32 },
+}
+
+impl PhaseFunction {
+    /// Density of scattering light travelling along `direction` into `wi`.
+    #[inline(always)]
+    fn eval(&self, direction: &Vector3<f32>, wi: &Vector3<f32>) -> f32 {
+        match self {
+            PhaseFunction::Isotropic => uniform_sphere_pdf(),
+            PhaseFunction::HenyeyGreenstein { g } => {
+                henyey_greenstein_pdf(*g, direction.dot(wi))
+            }
+        }
+    }
+}
+
+/// Interior of a participating medium, see
+/// [`ConstantMedium`](crate::world::object::medium::ConstantMedium).
+///
+/// Light traversing it is scattered with probability `scattering /
+/// (absorption + scattering)` per interaction, tinted by `color`, and
+/// absorbed otherwise. Absorbing media can also emit, like fire.
+#[derive(Debug)]
+pub struct Volume {
+    absorption: f32,
+    scattering: f32,
+    color: Color,
+    phase: PhaseFunction,
+    /// Emitted radiance per unit of absorption, as a grid of intensities
+    /// scaling a color.
+    emission: Option<(VoxelGrid, Color)>,
+}
+
+impl Volume {
+    pub fn new(
+        absorption: f32,
+        scattering: f32,
+        color: Color,
+        phase: PhaseFunction,
+    ) -> Self {
+        Self {
+            absorption,
+            scattering,
+            color,
+            phase,
+            emission: None,
+        }
+    }
+
+    /// Makes the volume emit `color` scaled by the intensity interpolated
+    /// from `grid` wherever light is absorbed.
+    pub fn with_emission(mut self, grid: VoxelGrid, color: Color) -> Self {
+        self.emission = Some((grid, color));
+        self
+    }
+
+    /// Extinction coefficient: interactions per unit of distance.
+    #[inline(always)]
+    pub fn extinction(&self) -> f32 {
+        self.absorption + self.scattering
+    }
+
+    #[inline(always)]
+    fn albedo(&self) -> Color {
+        let extinction = self.extinction();
+        if extinction > 0.0 {
+            self.color * (self.scattering / extinction)
+        } else {
+            Color::black()
+        }
+    }
+}
+
+impl Scatter for Volume {
+    #[inline(always)]
+    fn scatter(&self, ray: &Ray, hit_info: &HitInfo) -> Option<ScatterInfo> {
+        let incoming = ray.direction().normalize();
+        let direction = match self.phase {
+            PhaseFunction::Isotropic => uniform_sphere(random_vector2()),
+            PhaseFunction::HenyeyGreenstein { g } => {
+                henyey_greenstein(&incoming, g, random_vector2())
+            }
+        };
+
+        Some(ScatterInfo {
+            attenuation: self.albedo(),
+            scattered_ray: Ray::with_time(
+                hit_info.position,
+                direction,
+                ray.time(),
+            ),
+            pdf: Some(self.phase.eval(&incoming, &direction)),
+        })
+    }
+
+    /// Emission at a collision, weighted by the probability that it is an
+    /// absorption.
+    #[inline(always)]
+    fn emitted(&self, _ray: &Ray, hit_info: &HitInfo) -> Color {
+        let Some((grid, color)) = &self.emission else {
+            return Color::black();
+        };
+        let extinction = self.extinction();
+        if extinction <= 0.0 {
+            return Color::black();
+        }
+        *color *
+            (grid.sample(&hit_info.position) * self.absorption / extinction)
+    }
+
+    #[inline(always)]
+    fn eval(
+        &self,
+        wo: &Vector3<f32>,
+        wi: &Vector3<f32>,
+        _hit_info: &HitInfo,
+    ) -> Color {
+        self.albedo() * self.phase.eval(&-wo, wi)
+    }
+
+    #[inline(always)]
+    fn pdf(
+        &self,
+        wo: &Vector3<f32>,
+        wi: &Vector3<f32>,
+        _hit_info: &HitInfo,
+    ) -> f32 {
+        self.phase.eval(&-wo, wi)
+    }
+}
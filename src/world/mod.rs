@@ -1,40 +1,490 @@
+pub mod aabb;
+pub mod bvh;
 pub mod material;
 pub mod object;
 pub mod ray;
+pub mod voxel;
+
+use na::Vector3;
 
 use self::{
+    aabb::Bounded,
+    bvh::Bvh,
     object::{Hit, HitInfo, Object},
-    ray::Ray,
+    ray::{Ray, RayDifferentials},
+};
+use crate::{
+    color::Color, integrator::RAY_EPSILON, math::random_f32, sampler::Footprint,
 };
 
 pub struct World {
     objects: Vec<Object>,
+    lights: Vec<usize>,
+    media: Vec<usize>,
+    sky_color: Color,
+    /// Hierarchy over `bounded`, rebuilt by [`World::build_bvh`] and refit by
+    /// [`World::refit_bvh`].
+    bvh: Option<Bvh>,
+    /// Indices of objects that have a bounding box.
+    bounded: Vec<usize>,
+    /// Indices of objects that don't, and are always tested.
+    unbounded: Vec<usize>,
 }
 
 impl World {
     pub fn new() -> Self {
         Self {
             objects: Vec::new(),
+            lights: Vec::new(),
+            media: Vec::new(),
+            sky_color: Color::rgb(0.2, 0.5, 1.0),
+            bvh: None,
+            bounded: Vec::new(),
+            unbounded: Vec::new(),
         }
     }
 
     pub fn add_object(&mut self, object: Object) {
-        self.objects.push(object)
+        // Some emitters can't be sampled, see `Moving` and `Transformed`.
+        if object.material().is_emissive() && object.is_sampleable() {
+            self.lights.push(self.objects.len());
+        }
+        if object.is_medium() {
+            self.media.push(self.objects.len());
+        }
+        self.objects.push(object);
+        self.bvh = None;
     }
-}
 
-impl Hit for World {
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo> {
+    /// Gives access to an object, e.g. to move an instance between frames.
+    /// Call [`World::refit_bvh`] after changing it.
+    #[inline(always)]
+    pub fn object_mut(&mut self, index: usize) -> &mut Object {
+        &mut self.objects[index]
+    }
+
+    pub fn set_sky_color(&mut self, sky_color: Color) {
+        self.sky_color = sky_color;
+    }
+
+    /// Builds the acceleration structure. Until this is called, and after
+    /// every change to the object list, rays are tested against every
+    /// object.
+    pub fn build_bvh(&mut self) {
+        self.bounded.clear();
+        self.unbounded.clear();
+        let mut bounds = Vec::new();
+
+        for (index, object) in self.objects.iter().enumerate() {
+            match object.bounding_box() {
+                Some(aabb) => {
+                    self.bounded.push(index);
+                    bounds.push(aabb);
+                }
+                None => self.unbounded.push(index),
+            }
+        }
+
+        self.bvh = Some(Bvh::new(&bounds));
+    }
+
+    /// Updates the acceleration structure after objects moved, keeping the
+    /// topology of the hierarchy. Only the top level over the objects is
+    /// touched, so the hierarchies of groups shared by moving instances are
+    /// kept as they are. Falls back to a rebuild if no hierarchy was built
+    /// yet or an object gained or lost its bounding box.
+    pub fn refit_bvh(&mut self) {
+        // Moving an instance can change whether it can be sampled as light,
+        // and a replaced object may have turned into a medium or out of one.
+        self.lights = (0..self.objects.len())
+            .filter(|&index| {
+                let object = &self.objects[index];
+                object.material().is_emissive() && object.is_sampleable()
+            })
+            .collect();
+        self.media = (0..self.objects.len())
+            .filter(|&index| self.objects[index].is_medium())
+            .collect();
+
+        let bounds: Option<Vec<_>> = self
+            .bounded
+            .iter()
+            .map(|&index| self.objects[index].bounding_box())
+            .collect();
+        let unbounded_changed = self
+            .unbounded
+            .iter()
+            .any(|&index| self.objects[index].bounding_box().is_some());
+
+        match (&mut self.bvh, bounds) {
+            (Some(bvh), Some(bounds)) if !unbounded_changed => {
+                bvh.refit(&bounds);
+            }
+            _ => self.build_bvh(),
+        }
+    }
+
+    /// Sphere `(center, radius)` enclosing all bounded objects, once the BVH
+    /// has been built.
+    pub fn bounding_sphere(&self) -> Option<(Vector3<f32>, f32)> {
+        let bounds = self.bvh.as_ref()?.bounds();
+        if bounds.min.x > bounds.max.x {
+            return None;
+        }
+        Some((bounds.centroid(), 0.5 * bounds.extent().magnitude()))
+    }
+
+    #[inline(always)]
+    pub fn object(&self, index: usize) -> &Object {
+        &self.objects[index]
+    }
+
+    /// Indices of all static objects with an emissive material.
+    #[inline(always)]
+    pub fn lights(&self) -> &[usize] {
+        &self.lights
+    }
+
+    /// Radiance arriving from the sky along `direction`.
+    #[inline(always)]
+    pub fn sky(&self, direction: &Vector3<f32>) -> Color {
+        let a = 0.5 * (direction.normalize().y + 1.0);
+        (1.0 - a) * Color::gray(1.0) + a * self.sky_color
+    }
+
+    /// Like [`Hit::hit`], but also returns the index of the object that was
+    /// hit.
+    #[inline(always)]
+    pub fn hit_object(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<(usize, HitInfo<'_>)> {
+        self.hit_object_counted(ray, t_min, t_max, &mut 0)
+    }
+
+    /// Like [`World::hit_object`], adding the number of visited BVH nodes
+    /// and intersection tests to `cost`.
+    pub fn hit_object_counted(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        cost: &mut u32,
+    ) -> Option<(usize, HitInfo<'_>)> {
+        self.hit_filtered(ray, t_min, t_max, cost, |_| true)
+    }
+
+    /// Like [`World::hit_object`], but passes through participating media.
+    /// Their attenuation is given by [`World::transmittance`] instead.
+    #[inline(always)]
+    pub fn hit_surface(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<(usize, HitInfo<'_>)> {
+        self.hit_filtered(ray, t_min, t_max, &mut 0, |object| {
+            !object.is_medium()
+        })
+    }
+
+    /// Fraction of light passing through all participating media along the
+    /// ray within `[t_min, t_max]`.
+    pub fn transmittance(&self, ray: &Ray, t_min: f32, t_max: f32) -> f32 {
+        let mut transmittance = 1.0;
+        for &index in &self.media {
+            transmittance *=
+                self.objects[index].transmittance(ray, t_min, t_max);
+            if transmittance == 0.0 {
+                break;
+            }
+        }
+        transmittance
+    }
+
+    /// Closest hit among the objects accepted by `filter`, with its
+    /// footprint if the ray has differentials. Rays pass through surfaces
+    /// with the chance that their material isn't opaque, so masked parts of
+    /// a surface neither show nor cast shadows. The normal of the hit is the
+    /// geometric one, materials bump it when they scatter.
+    fn hit_filtered(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        cost: &mut u32,
+        filter: impl Fn(&Object) -> bool,
+    ) -> Option<(usize, HitInfo<'_>)> {
+        let mut t_start = t_min;
+        loop {
+            let (index, mut hit_info) =
+                self.closest_hit(ray, t_start, t_max, cost, &filter)?;
+            let object = &self.objects[index];
+            if let Some(differentials) = ray.differentials() {
+                if !object.is_medium() {
+                    hit_info.footprint = footprint(&hit_info, &differentials);
+                }
+            }
+            let opacity = hit_info.material.opacity(&hit_info);
+            if opacity >= 1.0 || random_f32() < opacity {
+                return Some((index, hit_info));
+            }
+            // Continue as a ray spawned at the hit would.
+            t_start = hit_info.t + RAY_EPSILON;
+        }
+    }
+
+    fn closest_hit(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        cost: &mut u32,
+        filter: impl Fn(&Object) -> bool,
+    ) -> Option<(usize, HitInfo<'_>)> {
+        let Some(bvh) = &self.bvh else {
+            return self.hit_linear(
+                0..self.objects.len(),
+                ray,
+                t_min,
+                t_max,
+                cost,
+                &filter,
+            );
+        };
+
+        let unbounded = self.hit_linear(
+            self.unbounded.iter().copied(),
+            ray,
+            t_min,
+            t_max,
+            cost,
+            &filter,
+        );
+        let t_max = unbounded.as_ref().map_or(t_max, |(_, info)| info.t);
+
+        let bounded = bvh.traverse(ray, t_min, t_max, cost, |i, t_max| {
+            let index = self.bounded[i];
+            let object = &self.objects[index];
+            if !filter(object) {
+                return None;
+            }
+            object
+                .hit(ray, t_min, t_max)
+                .map(|info| (info.t, (index, info)))
+        });
+
+        bounded.or(unbounded)
+    }
+
+    fn hit_linear(
+        &self,
+        indices: impl Iterator<Item = usize>,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        cost: &mut u32,
+        filter: &impl Fn(&Object) -> bool,
+    ) -> Option<(usize, HitInfo<'_>)> {
         let mut closest_so_far = t_max;
-        let mut hit_info = None;
+        let mut hit = None;
 
-        for object in self.objects.iter() {
+        for index in indices {
+            let object = &self.objects[index];
+            if !filter(object) {
+                continue;
+            }
+            *cost += 1;
             if let Some(info) = object.hit(ray, t_min, closest_so_far) {
                 closest_so_far = info.t;
-                hit_info = Some(info);
+                hit = Some((index, info));
             }
         }
 
-        hit_info
+        hit
+    }
+}
+
+/// Footprint of a ray at `hit_info`, from where the rays through the
+/// neighbouring samples meet the tangent plane at the hit. The offsets are
+/// expressed in the tangent and bitangent by least squares. Axes along which
+/// a neighbour runs parallel to the plane, and hits without tangents, are
+/// left empty.
+fn footprint(
+    hit_info: &HitInfo,
+    differentials: &RayDifferentials,
+) -> Footprint {
+    let (normal, tangent, bitangent) =
+        (hit_info.normal, hit_info.tangent, hit_info.bitangent);
+    // Normal equations of the offset in terms of tangent and bitangent.
+    let (tt, tb, bb) = (
+        tangent.magnitude_squared(),
+        tangent.dot(&bitangent),
+        bitangent.magnitude_squared(),
+    );
+    let determinant = tt * bb - tb * tb;
+    if determinant <= f32::EPSILON * tt * bb {
+        return Footprint::default();
+    }
+
+    let offset = |origin: Vector3<f32>, direction: Vector3<f32>| {
+        let facing = normal.dot(&direction);
+        if facing == 0.0 {
+            return (0.0, 0.0);
+        }
+        let t = normal.dot(&(hit_info.position - origin)) / facing;
+        let delta = origin + t * direction - hit_info.position;
+        let (dt, db) = (tangent.dot(&delta), bitangent.dot(&delta));
+        (
+            (bb * dt - tb * db) / determinant,
+            (tt * db - tb * dt) / determinant,
+        )
+    };
+    let (du_dx, dv_dx) =
+        offset(differentials.x_origin, differentials.x_direction);
+    let (du_dy, dv_dy) =
+        offset(differentials.y_origin, differentials.y_direction);
+    Footprint {
+        du_dx,
+        dv_dx,
+        du_dy,
+        dv_dy,
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hit for World {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        self.hit_object(ray, t_min, t_max).map(|(_, info)| info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use na::Matrix4;
+
+    use super::*;
+    use crate::{
+        sampler::Sampler2D,
+        world::{
+            material::{
+                lambertian::Lambertian,
+                volume::{PhaseFunction, Volume},
+                Material,
+            },
+            object::{
+                medium::ConstantMedium, quad::Quad, sphere::Sphere,
+                transformed::Transformed,
+            },
+        },
+    };
+
+    fn sphere(center: Vector3<f32>) -> Object {
+        Object::Sphere(Sphere::new(
+            center,
+            1.0,
+            Material::Lambertian(Lambertian::new(Sampler2D::Static(
+                Color::gray(0.5),
+            ))),
+        ))
+    }
+
+    fn hit_t(world: &World, origin: Vector3<f32>) -> Option<f32> {
+        let ray = Ray::new(origin, Vector3::z());
+        world
+            .hit_object(&ray, RAY_EPSILON, f32::INFINITY)
+            .map(|(_, hit_info)| hit_info.t)
+    }
+
+    #[test]
+    fn refit_follows_moved_instances() {
+        let mut world = World::new();
+        world.add_object(sphere(Vector3::new(-5.0, 0.0, 0.0)));
+        world.add_object(Object::Transformed(Transformed::new(
+            Arc::new(sphere(Vector3::zeros())),
+            Matrix4::identity(),
+        )));
+        world.build_bvh();
+
+        let moved = Vector3::new(5.0, 0.0, 0.0);
+        assert_eq!(hit_t(&world, Vector3::new(0.0, 0.0, -10.0)), Some(9.0));
+        assert_eq!(hit_t(&world, moved - Vector3::new(0.0, 0.0, 10.0)), None);
+
+        let Object::Transformed(instance) = world.object_mut(1) else {
+            unreachable!()
+        };
+        instance.set_matrix(Matrix4::new_translation(&moved));
+        world.refit_bvh();
+
+        assert_eq!(hit_t(&world, Vector3::new(0.0, 0.0, -10.0)), None);
+        let t = hit_t(&world, moved - Vector3::new(0.0, 0.0, 10.0)).unwrap();
+        assert!((t - 9.0).abs() < 1e-4, "{t}");
+        assert!(hit_t(&world, Vector3::new(-5.0, 0.0, -10.0)).is_some());
+    }
+
+    #[test]
+    fn refit_finds_replaced_media() {
+        let mut world = World::new();
+        world.add_object(sphere(Vector3::zeros()));
+        world.build_bvh();
+
+        let ray = Ray::new(Vector3::new(0.0, 0.0, -10.0), Vector3::z());
+        assert_eq!(world.transmittance(&ray, 0.0, 20.0), 1.0);
+
+        let volume =
+            Volume::new(1.0, 0.0, Color::gray(1.0), PhaseFunction::Isotropic);
+        *world.object_mut(0) = Object::Medium(ConstantMedium::new(
+            sphere(Vector3::zeros()),
+            volume,
+        ));
+        world.refit_bvh();
+
+        let expected = (-2.0f32).exp();
+        let found = world.transmittance(&ray, 0.0, 20.0);
+        assert!((found - expected).abs() < 1e-4, "{found}");
+    }
+
+    #[test]
+    fn footprints_follow_the_tangents() {
+        // A parallelogram, so that both offsets mix into both coordinates.
+        let mut world = World::new();
+        world.add_object(Object::Quad(Quad::new(
+            Vector3::new(-1.0, -1.0, -5.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(1.0, 2.0, 0.0),
+            Material::Lambertian(Lambertian::new(Sampler2D::Static(
+                Color::gray(0.5),
+            ))),
+        )));
+        world.build_bvh();
+
+        let ray = Ray::new(Vector3::zeros(), Vector3::new(0.0, 0.0, -5.0))
+            .with_differentials(RayDifferentials {
+                x_origin: Vector3::zeros(),
+                x_direction: Vector3::new(0.01, 0.0, -5.0),
+                y_origin: Vector3::zeros(),
+                y_direction: Vector3::new(0.0, 0.02, -5.0),
+            });
+        let (_, hit_info) =
+            world.hit_object(&ray, RAY_EPSILON, f32::INFINITY).unwrap();
+        let footprint = hit_info.footprint;
+        let found = [
+            footprint.du_dx,
+            footprint.dv_dx,
+            footprint.du_dy,
+            footprint.dv_dy,
+        ];
+        for (found, expected) in found.iter().zip([0.005, 0.0, -0.005, 0.01]) {
+            assert!((found - expected).abs() < 1e-5, "{found:?}");
+        }
     }
 }
@@ -0,0 +1,236 @@
+use na::{Vector2, Vector3};
+
+use super::{
+    convex_intervals,
+    frame::{azimuth, azimuth_tangent, radial, Frame},
+    Hit, HitInfo, Interval, SampleSurface, SurfaceSample,
+};
+use crate::{
+    math::solve_quadratic,
+    sampler::Footprint,
+    world::{
+        aabb::{Aabb, Bounded},
+        material::Material,
+        ray::Ray,
+    },
+};
+
+/// Points within `radius` of the segment from `start` to `end`: a cylinder
+/// closed by two hemispheres.
+///
+/// `u` runs around the axis and `v` along it over the whole length,
+/// hemispheres included. Capsules aren't sampled as lights.
+#[derive(Debug)]
+pub struct Capsule {
+    frame: Frame,
+    length: f32,
+    radius: f32,
+    material: Material,
+}
+
+impl Capsule {
+    pub fn new(
+        start: Vector3<f32>,
+        end: Vector3<f32>,
+        radius: f32,
+        material: Material,
+    ) -> Self {
+        let axis = end - start;
+        let length = axis.magnitude();
+        let axis = if length > 0.0 {
+            axis / length
+        } else {
+            Vector3::z()
+        };
+        Self {
+            frame: Frame::new(start, axis),
+            length,
+            radius,
+            material,
+        }
+    }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+impl Hit for Capsule {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        let local = self.frame.ray_to_local(ray);
+        let (o, d) = (local.origin(), local.direction());
+        let radius_sq = self.radius * self.radius;
+        let sphere = |center: f32| {
+            let oc = o - center * Vector3::z();
+            solve_quadratic(
+                d.magnitude_squared(),
+                2.0 * oc.dot(&d),
+                oc.magnitude_squared() - radius_sq,
+            )
+        };
+
+        // The side and each hemisphere only count within their range along
+        // the axis, and the hemispheres' normals point away from their
+        // centers.
+        let parts = [
+            (
+                solve_quadratic(
+                    d.x * d.x + d.y * d.y,
+                    2.0 * (o.x * d.x + o.y * d.y),
+                    o.x * o.x + o.y * o.y - radius_sq,
+                ),
+                (0.0, self.length),
+                None,
+            ),
+            (sphere(0.0), (f32::NEG_INFINITY, 0.0), Some(0.0)),
+            (
+                sphere(self.length),
+                (self.length, f32::INFINITY),
+                Some(self.length),
+            ),
+        ];
+
+        let mut t_max = t_max;
+        let mut closest = None;
+        for (roots, (low, high), center) in parts {
+            let Some((t0, t1)) = roots else {
+                continue;
+            };
+            for t in [t0, t1] {
+                let point = local.at(t);
+                if (t_min..=t_max).contains(&t) &&
+                    (low..=high).contains(&point.z)
+                {
+                    closest = Some((t, point, center));
+                    t_max = t;
+                    break;
+                }
+            }
+        }
+
+        let (t, point, center) = closest?;
+        let normal = match center {
+            Some(center) => point - center * Vector3::z(),
+            None => Vector3::new(point.x, point.y, 0.0),
+        };
+        let full_length = self.length + 2.0 * self.radius;
+        let v = (point.z + self.radius) / full_length;
+        // Along the meridian towards the top, taking the length of the
+        // whole capsule, which is exact only along the side. The poles have
+        // no meridian, like `radial` on the axis.
+        let meridian =
+            normal.xy().magnitude() * Vector3::z() - normal.z * radial(&point);
+        let bitangent = full_length *
+            meridian.try_normalize(0.0).unwrap_or_else(Vector3::zeros);
+        let position = ray.at(t);
+        Some(HitInfo {
+            t,
+            position,
+            local_position: position,
+            normal: self.frame.vector_to_world(&normal).normalize(),
+            tangent: self.frame.vector_to_world(&azimuth_tangent(&point)),
+            bitangent: self.frame.vector_to_world(&bitangent),
+            u: azimuth(&point),
+            v: v.clamp(0.0, 1.0),
+            footprint: Footprint::default(),
+            material: &self.material,
+        })
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        convex_intervals(self, ray)
+    }
+}
+
+impl Bounded for Capsule {
+    fn bounding_box(&self) -> Option<Aabb> {
+        let reach = Vector3::repeat(self.radius);
+        let start = self.frame.disk_bounds(0.0, 0.0);
+        let end = self.frame.disk_bounds(self.length, 0.0);
+        let bounds = start.union(&end);
+        Some(Aabb::new(bounds.min - reach, bounds.max + reach))
+    }
+}
+
+/// Capsules aren't sampled, see [`Capsule`].
+impl SampleSurface for Capsule {
+    fn area(&self) -> f32 {
+        0.0
+    }
+
+    fn sample_area(&self, _u: Vector2<f32>) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn sample_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn pdf_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _direction: &Vector3<f32>,
+    ) -> f32 {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    use super::*;
+    use crate::world::object::test_util::{
+        assert_surface, diffuse, hit_towards,
+    };
+
+    fn capsule() -> Capsule {
+        Capsule::new(Vector3::zeros(), Vector3::z() * 2.0, 1.0, diffuse())
+    }
+
+    #[test]
+    fn side_is_parametrized_by_its_tangents() {
+        let capsule = capsule();
+        let normal = Vector3::new(0.6, 0.8, 0.0);
+        let target = normal + Vector3::z();
+        assert_surface(&capsule, target + normal * 3.0, target, normal);
+        let hit_info = hit_towards(&capsule, target + normal, target);
+        assert!((hit_info.v - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn hemispheres_bulge_from_the_ends() {
+        let capsule = capsule();
+        let normal = Vector3::new(0.6, 0.8, 1.0) * FRAC_1_SQRT_2;
+        let target = Vector3::z() * 2.0 + normal;
+        let hit_info = hit_towards(&capsule, target + normal, target);
+        assert!((hit_info.normal - normal).magnitude() < 1e-4);
+        assert!(hit_info.bitangent.dot(&normal).abs() < 1e-4);
+        assert!(hit_info.bitangent.z > 0.0);
+
+        let below = -Vector3::z() + Vector3::x() * 0.5;
+        let hit_info = capsule
+            .hit(&Ray::new(below, Vector3::z()), 0.0, f32::INFINITY)
+            .unwrap();
+        assert!(hit_info.normal.z < 0.0);
+    }
+
+    #[test]
+    fn poles_have_finite_tangents() {
+        let capsule = capsule();
+        for (origin, pole) in [
+            (Vector3::z() * 5.0, Vector3::z() * 3.0),
+            (Vector3::z() * -5.0, -Vector3::z()),
+        ] {
+            let hit_info = hit_towards(&capsule, origin, pole);
+            assert!((hit_info.normal - pole.normalize()).magnitude() < 1e-5);
+            assert!(hit_info.tangent.iter().all(|x| x.is_finite()));
+            assert!(hit_info.bitangent.iter().all(|x| x.is_finite()));
+        }
+    }
+}
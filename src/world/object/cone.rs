@@ -0,0 +1,210 @@
+use na::{Vector2, Vector3};
+
+use super::{
+    convex_intervals,
+    frame::{azimuth, azimuth_tangent, cap_distance, radial, Frame},
+    Hit, HitInfo, Interval, SampleSurface, SurfaceSample,
+};
+use crate::{
+    math::solve_quadratic,
+    sampler::Footprint,
+    world::{
+        aabb::{Aabb, Bounded},
+        material::Material,
+        ray::Ray,
+    },
+};
+
+/// Cone from a capped circular base to its apex.
+///
+/// On the side `u` runs around the axis and `v` from the base to the apex,
+/// on the base `u` runs around the axis and `v` from the center to the rim.
+/// Cones aren't sampled as lights.
+#[derive(Debug)]
+pub struct Cone {
+    frame: Frame,
+    height: f32,
+    radius: f32,
+    material: Material,
+}
+
+impl Cone {
+    pub fn new(
+        base: Vector3<f32>,
+        apex: Vector3<f32>,
+        radius: f32,
+        material: Material,
+    ) -> Self {
+        let axis = apex - base;
+        let height = axis.magnitude();
+        Self {
+            frame: Frame::new(base, axis / height),
+            height,
+            radius,
+            material,
+        }
+    }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+impl Hit for Cone {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        let local = self.frame.ray_to_local(ray);
+        let (o, d) = (local.origin(), local.direction());
+        let mut t_max = t_max;
+        let mut closest = None;
+
+        // x² + y² = k² (h - z)², where k is the slope of the radius.
+        let k2 = (self.radius / self.height).powi(2);
+        let above = self.height - o.z;
+        if let Some((t0, t1)) = solve_quadratic(
+            d.x * d.x + d.y * d.y - k2 * d.z * d.z,
+            2.0 * (o.x * d.x + o.y * d.y + k2 * above * d.z),
+            o.x * o.x + o.y * o.y - k2 * above * above,
+        ) {
+            for t in [t0, t1] {
+                let point = local.at(t);
+                // The equation also holds on the mirrored cone above the
+                // apex, which the height range excludes.
+                if (t_min..=t_max).contains(&t) &&
+                    (0.0..=self.height).contains(&point.z)
+                {
+                    // The apex has no normal of its own, take the axis.
+                    let normal = Vector3::new(
+                        point.x,
+                        point.y,
+                        k2 * (self.height - point.z),
+                    )
+                    .try_normalize(0.0)
+                    .unwrap_or_else(Vector3::z);
+                    let uv = (azimuth(&point), point.z / self.height);
+                    let tangents = (
+                        azimuth_tangent(&point),
+                        self.height * Vector3::z() -
+                            self.radius * radial(&point),
+                    );
+                    closest = Some((t, normal, uv, tangents));
+                    t_max = t;
+                    break;
+                }
+            }
+        }
+
+        if let Some(t) = cap_distance(&local, 0.0, self.radius) {
+            if (t_min..=t_max).contains(&t) {
+                let point = local.at(t);
+                let uv =
+                    (azimuth(&point), point.xy().magnitude() / self.radius);
+                let tangents =
+                    (azimuth_tangent(&point), self.radius * radial(&point));
+                closest = Some((t, -Vector3::z(), uv, tangents));
+            }
+        }
+
+        let (t, normal, (u, v), (tangent, bitangent)) = closest?;
+        let position = ray.at(t);
+        Some(HitInfo {
+            t,
+            position,
+            local_position: position,
+            normal: self.frame.vector_to_world(&normal).normalize(),
+            tangent: self.frame.vector_to_world(&tangent),
+            bitangent: self.frame.vector_to_world(&bitangent),
+            u,
+            v,
+            footprint: Footprint::default(),
+            material: &self.material,
+        })
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        convex_intervals(self, ray)
+    }
+}
+
+impl Bounded for Cone {
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(
+            self.frame
+                .disk_bounds(0.0, self.radius)
+                .union(&self.frame.disk_bounds(self.height, 0.0))
+                .padded(),
+        )
+    }
+}
+
+/// Cones aren't sampled, see [`Cone`].
+impl SampleSurface for Cone {
+    fn area(&self) -> f32 {
+        0.0
+    }
+
+    fn sample_area(&self, _u: Vector2<f32>) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn sample_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn pdf_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _direction: &Vector3<f32>,
+    ) -> f32 {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::object::test_util::{
+        assert_surface, diffuse, hit_towards,
+    };
+
+    fn cone() -> Cone {
+        Cone::new(Vector3::zeros(), Vector3::z() * 2.0, 1.0, diffuse())
+    }
+
+    #[test]
+    fn side_is_parametrized_by_its_tangents() {
+        let cone = cone();
+        // Halfway up, the radius is halved and the slope is a half.
+        let target = Vector3::new(0.3, 0.4, 1.0);
+        let normal = Vector3::new(0.3, 0.4, 0.25).normalize();
+        assert_surface(&cone, target + normal * 3.0, target, normal);
+        let hit_info = hit_towards(&cone, target + normal, target);
+        assert!((hit_info.v - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn base_is_parametrized_by_its_tangents() {
+        let cone = cone();
+        let target = Vector3::new(0.3, 0.4, 0.0);
+        assert_surface(&cone, target - Vector3::z(), target, -Vector3::z());
+    }
+
+    #[test]
+    fn apex_has_a_normal() {
+        let cone = cone();
+        let hit_info =
+            hit_towards(&cone, Vector3::z() * 5.0, Vector3::z() * 2.0);
+        assert_eq!(hit_info.normal, Vector3::z());
+    }
+
+    #[test]
+    fn mirrored_cone_is_missed() {
+        let cone = cone();
+        let ray = Ray::new(Vector3::new(0.5, 0.0, 5.0), -Vector3::x());
+        assert!(cone.hit(&ray, 0.0, f32::INFINITY).is_none());
+    }
+}
@@ -0,0 +1,261 @@
+use na::{Vector2, Vector3};
+
+use super::{Hit, HitInfo, Interval, Object, SampleSurface, SurfaceSample};
+use crate::world::{
+    aabb::{Aabb, Bounded},
+    material::Material,
+    ray::Ray,
+};
+
+/// How a [`Csg`] node combines its operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOperation {
+    /// Inside either operand.
+    Union,
+    /// Inside both operands.
+    Intersection,
+    /// Inside the left operand but not the right one.
+    Difference,
+}
+
+impl CsgOperation {
+    #[inline(always)]
+    fn contains(self, left: bool, right: bool) -> bool {
+        match self {
+            CsgOperation::Union => left || right,
+            CsgOperation::Intersection => left && right,
+            CsgOperation::Difference => left && !right,
+        }
+    }
+}
+
+/// Constructive solid geometry node combining two closed objects.
+///
+/// Rays are intersected with the intervals of both operands, which are
+/// merged according to the operation. Surfaces keep the material and texture
+/// coordinates of the operand they come from, so the wall of a hole drilled
+/// with [`CsgOperation::Difference`] shows the right operand's material.
+/// CSG nodes aren't sampled as lights.
+#[derive(Debug)]
+pub struct Csg {
+    operation: CsgOperation,
+    left: Box<Object>,
+    right: Box<Object>,
+}
+
+impl Csg {
+    /// Panics if an operand isn't closed, see [`Object::is_closed`].
+    pub fn new(operation: CsgOperation, left: Object, right: Object) -> Self {
+        assert!(
+            left.is_closed() && right.is_closed(),
+            "CSG operands need to be closed objects"
+        );
+        Self {
+            operation,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    /// CSG nodes have no material of their own, this is the one of the left
+    /// operand. It only matters for emitters, which CSG nodes never are.
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        self.left.material()
+    }
+}
+
+impl Hit for Csg {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        self.intervals(ray)
+            .into_iter()
+            .flat_map(|interval| [interval.enter, interval.exit])
+            .find(|hit_info| (t_min..=t_max).contains(&hit_info.t))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        // Boundaries of both operands in order along the line, each with
+        // whether it belongs to the left operand and whether it enters it.
+        let mut boundaries: Vec<_> = [
+            (true, self.left.intervals(ray)),
+            (false, self.right.intervals(ray)),
+        ]
+        .into_iter()
+        .flat_map(|(is_left, intervals)| {
+            intervals.into_iter().flat_map(move |interval| {
+                [
+                    (is_left, true, interval.enter),
+                    (is_left, false, interval.exit),
+                ]
+            })
+        })
+        .collect();
+        boundaries.sort_by(|a, b| a.2.t.total_cmp(&b.2.t));
+
+        let (mut in_left, mut in_right) = (false, false);
+        let mut inside = false;
+        let mut enter = None;
+        let mut intervals = Vec::new();
+        for (is_left, entering, mut hit_info) in boundaries {
+            if is_left {
+                in_left = entering;
+            } else {
+                in_right = entering;
+            }
+            let now_inside = self.operation.contains(in_left, in_right);
+            if now_inside == inside {
+                continue;
+            }
+            inside = now_inside;
+
+            // The normal points out of the operand; it points out of the
+            // result too unless the operand is left where the result is
+            // entered or the other way round, as for subtracted surfaces.
+            // The bitangent flips along to keep the frame right-handed.
+            if entering != inside {
+                hit_info.normal = -hit_info.normal;
+                hit_info.bitangent = -hit_info.bitangent;
+            }
+            match enter.take() {
+                None => enter = Some(hit_info),
+                Some(enter) => {
+                    intervals.push(Interval {
+                        enter,
+                        exit: hit_info,
+                    })
+                }
+            }
+        }
+        intervals
+    }
+}
+
+impl Bounded for Csg {
+    fn bounding_box(&self) -> Option<Aabb> {
+        let left = self.left.bounding_box()?;
+        match self.operation {
+            CsgOperation::Union => {
+                Some(left.union(&self.right.bounding_box()?))
+            }
+            CsgOperation::Intersection => {
+                Some(left.intersection(&self.right.bounding_box()?))
+            }
+            CsgOperation::Difference => Some(left),
+        }
+    }
+}
+
+/// CSG nodes aren't sampled, see [`Csg`].
+impl SampleSurface for Csg {
+    fn area(&self) -> f32 {
+        0.0
+    }
+
+    fn sample_area(&self, _u: Vector2<f32>) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn sample_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn pdf_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _direction: &Vector3<f32>,
+    ) -> f32 {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::object::{
+        cylinder::Cylinder, sphere::Sphere, test_util::diffuse,
+    };
+
+    fn sphere(x: f32) -> Object {
+        Object::Sphere(Sphere::new(Vector3::x() * x, 1.0, diffuse()))
+    }
+
+    fn along_x(csg: &Csg) -> Vec<(f32, f32)> {
+        let ray = Ray::new(Vector3::x() * -5.0, Vector3::x());
+        csg.intervals(&ray)
+            .iter()
+            .map(|interval| (interval.enter.t - 5.0, interval.exit.t - 5.0))
+            .collect()
+    }
+
+    fn assert_close(found: &[(f32, f32)], expected: &[(f32, f32)]) {
+        assert_eq!(found.len(), expected.len(), "{found:?}");
+        for (found, expected) in found.iter().zip(expected) {
+            assert!(
+                (found.0 - expected.0).abs() < 1e-4 &&
+                    (found.1 - expected.1).abs() < 1e-4,
+                "{found:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn drilled_hole_is_empty() {
+        let drill = Object::Cylinder(Cylinder::new(
+            Vector3::z() * -2.0,
+            Vector3::z() * 2.0,
+            0.3,
+            diffuse(),
+        ));
+        let csg = Csg::new(CsgOperation::Difference, sphere(0.0), drill);
+
+        let down = Ray::new(Vector3::new(0.1, 0.0, 5.0), -Vector3::z());
+        assert!(csg.hit(&down, 0.0, f32::INFINITY).is_none());
+
+        assert_close(&along_x(&csg), &[(-1.0, -0.3), (0.3, 1.0)]);
+        // The walls of the hole face into it.
+        let ray = Ray::new(Vector3::x() * -5.0, Vector3::x());
+        let intervals = csg.intervals(&ray);
+        let walls = [intervals[0].exit.normal, intervals[1].enter.normal];
+        assert!((walls[0] - Vector3::x()).magnitude() < 1e-4);
+        assert!((walls[1] + Vector3::x()).magnitude() < 1e-4);
+        for wall in [&intervals[0].exit, &intervals[1].enter] {
+            let frame = wall.tangent.cross(&wall.bitangent);
+            assert!(frame.dot(&wall.normal) > 0.0);
+        }
+
+        let wall = csg.hit(&ray, 4.5, f32::INFINITY).unwrap();
+        assert!((wall.position.x + 0.3).abs() < 1e-4);
+        assert!((wall.normal - Vector3::x()).magnitude() < 1e-4);
+    }
+
+    #[test]
+    fn intersection_keeps_the_lens() {
+        let csg =
+            Csg::new(CsgOperation::Intersection, sphere(-0.5), sphere(0.5));
+        assert_close(&along_x(&csg), &[(-0.5, 0.5)]);
+
+        let ray = Ray::new(Vector3::x() * -5.0, Vector3::x());
+        let hit_info = csg.hit(&ray, 0.0, f32::INFINITY).unwrap();
+        assert!((hit_info.normal + Vector3::x()).magnitude() < 1e-4);
+
+        // Inside the left sphere only, above the lens.
+        let above = Ray::new(Vector3::new(-5.0, 0.9, 0.0), Vector3::x());
+        assert!(csg.hit(&above, 0.0, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn union_merges_overlapping_spheres() {
+        let csg = Csg::new(CsgOperation::Union, sphere(-0.5), sphere(0.5));
+        assert_close(&along_x(&csg), &[(-1.5, 1.5)]);
+
+        // The boundaries inside the other sphere are skipped.
+        let inside = Ray::new(Vector3::zeros(), Vector3::x());
+        let hit_info = csg.hit(&inside, 0.0, f32::INFINITY).unwrap();
+        assert!((hit_info.t - 1.5).abs() < 1e-4);
+        assert!((hit_info.normal - Vector3::x()).magnitude() < 1e-4);
+    }
+}
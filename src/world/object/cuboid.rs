@@ -0,0 +1,203 @@
+use na::{Vector2, Vector3};
+
+use super::{
+    convex_intervals, Hit, HitInfo, Interval, SampleSurface, SurfaceSample,
+};
+use crate::{
+    sampler::Footprint,
+    world::{
+        aabb::{Aabb, Bounded},
+        material::Material,
+        ray::Ray,
+    },
+};
+
+/// Axis-aligned box. Texture coordinates span each face along the next two
+/// axes in cyclic order, with `u` reversed on the faces towards the negative
+/// axes so that no face is mirrored when seen from outside.
+///
+/// Boxes aren't sampled as lights, emissive boxes are only found by rays
+/// hitting them. Use [`Quad`](super::quad::Quad)s for box-shaped lights.
+#[derive(Debug)]
+pub struct Cuboid {
+    bounds: Aabb,
+    material: Material,
+}
+
+impl Cuboid {
+    pub fn new(
+        min: Vector3<f32>,
+        max: Vector3<f32>,
+        material: Material,
+    ) -> Self {
+        Self {
+            bounds: Aabb::new(min.inf(&max), min.sup(&max)),
+            material,
+        }
+    }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+impl Hit for Cuboid {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        let origin = ray.origin();
+        let direction = ray.direction();
+
+        // Slab test that also remembers through which slab the ray enters
+        // and leaves.
+        let (mut t_enter, mut enter_axis) = (f32::NEG_INFINITY, 0);
+        let (mut t_exit, mut exit_axis) = (f32::INFINITY, 0);
+        for axis in 0..3 {
+            let inv = direction[axis].recip();
+            let mut t0 = (self.bounds.min[axis] - origin[axis]) * inv;
+            let mut t1 = (self.bounds.max[axis] - origin[axis]) * inv;
+            if inv < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > t_enter {
+                (t_enter, enter_axis) = (t0, axis);
+            }
+            if t1 < t_exit {
+                (t_exit, exit_axis) = (t1, axis);
+            }
+        }
+        if t_exit < t_enter {
+            return None;
+        }
+
+        let (t, axis) = if (t_min..=t_max).contains(&t_enter) {
+            (t_enter, enter_axis)
+        } else if (t_min..=t_max).contains(&t_exit) {
+            (t_exit, exit_axis)
+        } else {
+            return None;
+        };
+
+        let position = ray.at(t);
+        let local =
+            (position - self.bounds.min).component_div(&self.bounds.extent());
+        let side = if local[axis] > 0.5 { 1.0 } else { -1.0 };
+        let mut normal = Vector3::zeros();
+        normal[axis] = side;
+        let extent = self.bounds.extent();
+        let edge = |axis: usize| {
+            let mut edge = Vector3::zeros();
+            edge[axis] = extent[axis];
+            edge
+        };
+        let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+        let u = local[u_axis].clamp(0.0, 1.0);
+
+        Some(HitInfo {
+            t,
+            position,
+            local_position: position,
+            normal,
+            tangent: side * edge(u_axis),
+            bitangent: edge(v_axis),
+            u: if side > 0.0 { u } else { 1.0 - u },
+            v: local[v_axis].clamp(0.0, 1.0),
+            footprint: Footprint::default(),
+            material: &self.material,
+        })
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        convex_intervals(self, ray)
+    }
+}
+
+impl Bounded for Cuboid {
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds.padded())
+    }
+}
+
+/// Boxes aren't sampled, see [`Cuboid`].
+impl SampleSurface for Cuboid {
+    fn area(&self) -> f32 {
+        0.0
+    }
+
+    fn sample_area(&self, _u: Vector2<f32>) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn sample_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn pdf_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _direction: &Vector3<f32>,
+    ) -> f32 {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::object::test_util::{
+        assert_surface, diffuse, hit_towards,
+    };
+
+    fn cuboid() -> Cuboid {
+        Cuboid::new(
+            Vector3::new(1.0, 2.0, 3.0),
+            Vector3::new(-1.0, -2.0, -3.0),
+            diffuse(),
+        )
+    }
+
+    #[test]
+    fn faces_are_parametrized_by_their_tangents() {
+        let cuboid = cuboid();
+        let half = Vector3::new(1.0, 2.0, 3.0);
+        for axis in 0..3 {
+            for side in [-1.0, 1.0] {
+                let mut normal = Vector3::zeros();
+                normal[axis] = side;
+                // Off the center of the face along both of its axes.
+                let target = normal.component_mul(&half) +
+                    (Vector3::repeat(0.3) - normal.abs() * 0.3)
+                        .component_mul(&half);
+                assert_surface(&cuboid, target + normal * 2.0, target, normal);
+
+                let hit_info = hit_towards(&cuboid, target + normal, target);
+                let expected = if side > 0.0 { 0.65 } else { 0.35 };
+                assert!((hit_info.u - expected).abs() < 1e-5);
+                assert!((hit_info.v - 0.65).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn rays_leaving_the_box_hit_its_far_side() {
+        let cuboid = cuboid();
+        let ray = Ray::new(Vector3::zeros(), Vector3::x());
+        let hit_info = cuboid.hit(&ray, 0.0, f32::INFINITY).unwrap();
+        assert!((hit_info.t - 1.0).abs() < 1e-5);
+        assert_eq!(hit_info.normal, Vector3::x());
+
+        let ray = Ray::new(Vector3::new(1.5, 0.0, -5.0), Vector3::z());
+        assert!(cuboid.hit(&ray, 0.0, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn boxes_are_not_sampled() {
+        let cuboid = cuboid();
+        let origin = Vector3::new(0.0, 5.0, 0.0);
+        assert!(cuboid.sample_from(&origin, Vector2::repeat(0.5)).is_none());
+        assert_eq!(cuboid.pdf_from(&origin, &-Vector3::y()), 0.0);
+    }
+}
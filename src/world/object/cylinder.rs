@@ -0,0 +1,205 @@
+use na::{Vector2, Vector3};
+
+use super::{
+    convex_intervals,
+    frame::{azimuth, azimuth_tangent, cap_distance, radial, Frame},
+    Hit, HitInfo, Interval, SampleSurface, SurfaceSample,
+};
+use crate::{
+    math::solve_quadratic,
+    sampler::Footprint,
+    world::{
+        aabb::{Aabb, Bounded},
+        material::Material,
+        ray::Ray,
+    },
+};
+
+/// Cylinder between the centers of its two caps.
+///
+/// On the side `u` runs around the axis and `v` from `base` to `top`, on the
+/// caps `u` runs around the axis, reversed on the top one so that neither
+/// cap is mirrored seen from outside, and `v` from the center to the rim.
+/// Cylinders aren't sampled as lights.
+#[derive(Debug)]
+pub struct Cylinder {
+    frame: Frame,
+    height: f32,
+    radius: f32,
+    material: Material,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Vector3<f32>,
+        top: Vector3<f32>,
+        radius: f32,
+        material: Material,
+    ) -> Self {
+        let axis = top - base;
+        let height = axis.magnitude();
+        Self {
+            frame: Frame::new(base, axis / height),
+            height,
+            radius,
+            material,
+        }
+    }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+impl Hit for Cylinder {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        let local = self.frame.ray_to_local(ray);
+        let (o, d) = (local.origin(), local.direction());
+        let mut t_max = t_max;
+        let mut closest = None;
+
+        if let Some((t0, t1)) = solve_quadratic(
+            d.x * d.x + d.y * d.y,
+            2.0 * (o.x * d.x + o.y * d.y),
+            o.x * o.x + o.y * o.y - self.radius * self.radius,
+        ) {
+            for t in [t0, t1] {
+                let point = local.at(t);
+                if (t_min..=t_max).contains(&t) &&
+                    (0.0..=self.height).contains(&point.z)
+                {
+                    let normal = Vector3::new(point.x, point.y, 0.0);
+                    let uv = (azimuth(&point), point.z / self.height);
+                    let tangents =
+                        (azimuth_tangent(&point), self.height * Vector3::z());
+                    closest = Some((t, normal, uv, tangents));
+                    t_max = t;
+                    break;
+                }
+            }
+        }
+
+        for (height, side) in [(0.0, -1.0f32), (self.height, 1.0)] {
+            let Some(t) = cap_distance(&local, height, self.radius) else {
+                continue;
+            };
+            if (t_min..=t_max).contains(&t) {
+                let point = local.at(t);
+                // Reversed on the top cap, so that seen from outside `u`
+                // turns clockwise on both.
+                let u = azimuth(&point);
+                let u = if side > 0.0 { 1.0 - u } else { u };
+                let uv = (u, point.xy().magnitude() / self.radius);
+                let tangents = (
+                    -side * azimuth_tangent(&point),
+                    self.radius * radial(&point),
+                );
+                closest = Some((t, side * Vector3::z(), uv, tangents));
+                t_max = t;
+            }
+        }
+
+        let (t, normal, (u, v), (tangent, bitangent)) = closest?;
+        let position = ray.at(t);
+        Some(HitInfo {
+            t,
+            position,
+            local_position: position,
+            normal: self.frame.vector_to_world(&normal).normalize(),
+            tangent: self.frame.vector_to_world(&tangent),
+            bitangent: self.frame.vector_to_world(&bitangent),
+            u,
+            v,
+            footprint: Footprint::default(),
+            material: &self.material,
+        })
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        convex_intervals(self, ray)
+    }
+}
+
+impl Bounded for Cylinder {
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(
+            self.frame
+                .disk_bounds(0.0, self.radius)
+                .union(&self.frame.disk_bounds(self.height, self.radius)),
+        )
+    }
+}
+
+/// Cylinders aren't sampled, see [`Cylinder`].
+impl SampleSurface for Cylinder {
+    fn area(&self) -> f32 {
+        0.0
+    }
+
+    fn sample_area(&self, _u: Vector2<f32>) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn sample_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn pdf_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _direction: &Vector3<f32>,
+    ) -> f32 {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::object::test_util::{
+        assert_surface, diffuse, hit_towards,
+    };
+
+    fn cylinder() -> Cylinder {
+        Cylinder::new(Vector3::zeros(), Vector3::z() * 2.0, 1.0, diffuse())
+    }
+
+    #[test]
+    fn side_is_parametrized_by_its_tangents() {
+        let cylinder = cylinder();
+        let normal = Vector3::new(0.6, 0.8, 0.0);
+        let target = normal + Vector3::z() * 0.5;
+        assert_surface(&cylinder, target + normal * 3.0, target, normal);
+        let hit_info = hit_towards(&cylinder, target + normal, target);
+        assert!((hit_info.v - 0.25).abs() < 1e-5);
+        assert!(
+            (hit_info.u - 0.8f32.atan2(0.6) / std::f32::consts::TAU).abs() <
+                1e-3
+        );
+    }
+
+    #[test]
+    fn caps_are_parametrized_by_their_tangents() {
+        let cylinder = cylinder();
+        for (height, normal) in [(0.0, -Vector3::z()), (2.0, Vector3::z())] {
+            let target = Vector3::new(0.3, 0.4, height);
+            assert_surface(&cylinder, target + normal, target, normal);
+            let hit_info = hit_towards(&cylinder, target + normal, target);
+            assert!((hit_info.v - 0.5).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn rays_beside_the_cylinder_miss() {
+        let cylinder = cylinder();
+        let ray = Ray::new(Vector3::new(1.1, -5.0, 1.0), Vector3::y());
+        assert!(cylinder.hit(&ray, 0.0, f32::INFINITY).is_none());
+        let ray = Ray::new(Vector3::new(0.0, -5.0, 2.1), Vector3::y());
+        assert!(cylinder.hit(&ray, 0.0, f32::INFINITY).is_none());
+    }
+}
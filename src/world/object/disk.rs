@@ -0,0 +1,200 @@
+use std::f32::consts::{PI, TAU};
+
+use na::{Vector2, Vector3};
+
+use super::{
+    area_to_solid_angle, plane::plane_distance, Hit, HitInfo, SampleSurface,
+    SurfaceSample,
+};
+use crate::{
+    math::orthonormal_basis,
+    sampler::Footprint,
+    sampling::concentric_disk,
+    world::{
+        aabb::{Aabb, Bounded},
+        material::Material,
+        ray::Ray,
+    },
+};
+
+/// Flat disk facing along `normal`. Texture coordinates are the angle
+/// around the center as `u` and the distance from it as `v`.
+#[derive(Debug)]
+pub struct Disk {
+    center: Vector3<f32>,
+    normal: Vector3<f32>,
+    tangent: Vector3<f32>,
+    bitangent: Vector3<f32>,
+    radius: f32,
+    material: Material,
+}
+
+impl Disk {
+    pub fn new(
+        center: Vector3<f32>,
+        normal: Vector3<f32>,
+        radius: f32,
+        material: Material,
+    ) -> Self {
+        let normal = normal.normalize();
+        let (tangent, bitangent) = orthonormal_basis(&normal);
+        Self {
+            center,
+            normal,
+            tangent,
+            bitangent,
+            radius,
+            material,
+        }
+    }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// Texture coordinates of a point at `offset` from the center. The angle
+    /// turns from `tangent` away from `bitangent`, clockwise seen from the
+    /// front, so that the tangent frame of hits is right-handed.
+    #[inline(always)]
+    fn uv(&self, offset: &Vector3<f32>) -> (f32, f32) {
+        let phi = fast_math::atan2(
+            -offset.dot(&self.bitangent),
+            offset.dot(&self.tangent),
+        );
+        (
+            (phi / TAU).rem_euclid(1.0),
+            offset.magnitude() / self.radius,
+        )
+    }
+}
+
+impl Hit for Disk {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        let t = plane_distance(ray, &self.center, &self.normal)?;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        let position = ray.at(t);
+        let offset = position - self.center;
+        if offset.magnitude_squared() > self.radius * self.radius {
+            return None;
+        }
+
+        let (u, v) = self.uv(&offset);
+        let around = offset.dot(&self.bitangent) * self.tangent -
+            offset.dot(&self.tangent) * self.bitangent;
+        let outwards = offset.try_normalize(0.0).unwrap_or_else(Vector3::zeros);
+        Some(HitInfo {
+            t,
+            position,
+            local_position: position,
+            normal: self.normal,
+            tangent: TAU * around,
+            bitangent: self.radius * outwards,
+            u,
+            v,
+            footprint: Footprint::default(),
+            material: &self.material,
+        })
+    }
+}
+
+impl Bounded for Disk {
+    fn bounding_box(&self) -> Option<Aabb> {
+        // Along each axis the rim reaches as far as the disk plane tilts
+        // towards it.
+        let extent =
+            self.radius * self.normal.map(|n| (1.0 - n * n).max(0.0).sqrt());
+        Some(Aabb::new(self.center - extent, self.center + extent).padded())
+    }
+}
+
+impl SampleSurface for Disk {
+    fn area(&self) -> f32 {
+        PI * self.radius * self.radius
+    }
+
+    fn sample_area(&self, u: Vector2<f32>) -> Option<SurfaceSample> {
+        let p = self.radius * concentric_disk(u);
+        let offset = p.x * self.tangent + p.y * self.bitangent;
+        let (u, v) = self.uv(&offset);
+        Some(SurfaceSample {
+            position: self.center + offset,
+            normal: self.normal,
+            u,
+            v,
+            pdf: self.area().recip(),
+        })
+    }
+
+    fn sample_from(
+        &self,
+        origin: &Vector3<f32>,
+        u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        let sample = self.sample_area(u)?;
+        let pdf = area_to_solid_angle(
+            sample.pdf,
+            origin,
+            &sample.position,
+            &sample.normal,
+        );
+        pdf.is_finite().then_some(SurfaceSample { pdf, ..sample })
+    }
+
+    fn pdf_from(&self, origin: &Vector3<f32>, direction: &Vector3<f32>) -> f32 {
+        match self.hit(&Ray::new(*origin, *direction), 0.0, f32::INFINITY) {
+            Some(hit_info) => {
+                area_to_solid_angle(
+                    self.area().recip(),
+                    origin,
+                    &hit_info.position,
+                    &self.normal,
+                )
+            }
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::object::test_util::{
+        assert_sampling, assert_surface, diffuse, hit_towards,
+    };
+
+    fn disk() -> Disk {
+        let normal = Vector3::new(1.0, 2.0, 2.0) / 3.0;
+        Disk::new(Vector3::new(1.0, 0.0, -1.0), normal, 2.0, diffuse())
+    }
+
+    #[test]
+    fn disk_is_parametrized_by_its_tangents() {
+        let disk = disk();
+        for (x, y) in [(1.0, 0.5), (-0.5, 1.2), (0.3, -1.5)] {
+            let target = disk.center + x * disk.tangent + y * disk.bitangent;
+            let origin = target + disk.normal * 2.0 + disk.tangent;
+            assert_surface(&disk, origin, target, disk.normal);
+            let hit_info = hit_towards(&disk, origin, target);
+            assert!((hit_info.v - (x * x + y * y).sqrt() / 2.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn rays_past_the_rim_miss() {
+        let disk = disk();
+        let outside = disk.center + disk.tangent * 2.1;
+        let ray = Ray::new(outside + disk.normal, -disk.normal);
+        assert!(disk.hit(&ray, 0.0, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn sampling_densities_agree() {
+        let disk = disk();
+        assert_sampling(&disk, disk.center + disk.normal * 1.5);
+        assert_sampling(&disk, disk.center - disk.normal + disk.tangent * 3.0);
+    }
+}
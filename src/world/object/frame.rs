@@ -0,0 +1,100 @@
+use std::f32::consts::TAU;
+
+use na::Vector3;
+
+use crate::{
+    math::orthonormal_basis,
+    world::{aabb::Aabb, ray::Ray},
+};
+
+/// Orthonormal frame with its z axis along the axis of a shape of
+/// revolution, so the shape can be intersected in canonical position.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Frame {
+    origin: Vector3<f32>,
+    tangent: Vector3<f32>,
+    bitangent: Vector3<f32>,
+    axis: Vector3<f32>,
+}
+
+impl Frame {
+    /// `axis` must be normalized.
+    pub fn new(origin: Vector3<f32>, axis: Vector3<f32>) -> Self {
+        let (tangent, bitangent) = orthonormal_basis(&axis);
+        Self {
+            origin,
+            tangent,
+            bitangent,
+            axis,
+        }
+    }
+
+    #[inline(always)]
+    fn vector_to_local(&self, vector: &Vector3<f32>) -> Vector3<f32> {
+        Vector3::new(
+            vector.dot(&self.tangent),
+            vector.dot(&self.bitangent),
+            vector.dot(&self.axis),
+        )
+    }
+
+    /// `ray` in local coordinates. The frame is orthonormal, so parametric
+    /// distances stay the same.
+    #[inline(always)]
+    pub fn ray_to_local(&self, ray: &Ray) -> Ray {
+        Ray::with_time(
+            self.vector_to_local(&(ray.origin() - self.origin)),
+            self.vector_to_local(&ray.direction()),
+            ray.time(),
+        )
+    }
+
+    #[inline(always)]
+    pub fn vector_to_world(&self, vector: &Vector3<f32>) -> Vector3<f32> {
+        vector.x * self.tangent +
+            vector.y * self.bitangent +
+            vector.z * self.axis
+    }
+
+    /// Bounds of a disk of `radius` around the axis at `height` from the
+    /// origin.
+    pub fn disk_bounds(&self, height: f32, radius: f32) -> Aabb {
+        let center = self.origin + height * self.axis;
+        let extent = radius * self.axis.map(|a| (1.0 - a * a).max(0.0).sqrt());
+        Aabb::new(center - extent, center + extent)
+    }
+}
+
+/// Change of the local `point` with [`azimuth`].
+#[inline(always)]
+pub(super) fn azimuth_tangent(point: &Vector3<f32>) -> Vector3<f32> {
+    TAU * Vector3::new(-point.y, point.x, 0.0)
+}
+
+/// Unit vector from the z axis towards the local `point`, zero on the axis.
+#[inline(always)]
+pub(super) fn radial(point: &Vector3<f32>) -> Vector3<f32> {
+    Vector3::new(point.x, point.y, 0.0)
+        .try_normalize(0.0)
+        .unwrap_or_else(Vector3::zeros)
+}
+
+/// Angle of the local `point` around the z axis, as a texture coordinate in
+/// `[0, 1)`.
+#[inline(always)]
+pub(super) fn azimuth(point: &Vector3<f32>) -> f32 {
+    (fast_math::atan2(point.y, point.x) / TAU).rem_euclid(1.0)
+}
+
+/// Parametric distance at which the local `ray` crosses the plane
+/// `z = height` within `radius` of the axis.
+#[inline(always)]
+pub(super) fn cap_distance(ray: &Ray, height: f32, radius: f32) -> Option<f32> {
+    let direction = ray.direction();
+    if direction.z == 0.0 {
+        return None;
+    }
+    let t = (height - ray.origin().z) / direction.z;
+    let point = ray.at(t);
+    (point.x * point.x + point.y * point.y <= radius * radius).then_some(t)
+}
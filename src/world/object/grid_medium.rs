@@ -0,0 +1,173 @@
+use std::ops::ControlFlow;
+
+use na::{Vector2, Vector3};
+
+use super::{Hit, HitInfo, SampleSurface, SurfaceSample};
+use crate::{
+    math::random_f32,
+    sampler::Footprint,
+    world::{
+        aabb::{Aabb, Bounded},
+        material::{volume::Volume, Material},
+        ray::Ray,
+        voxel::{MajorantGrid, VoxelGrid},
+    },
+};
+
+/// Voxels along each axis of a macro cell of the majorant grid.
+const MACRO_CELL_SIZE: usize = 8;
+
+/// Transmittance below which ratio tracking plays Russian roulette.
+const ROULETTE_TRANSMITTANCE: f32 = 0.1;
+
+/// Heterogeneous participating medium whose density is interpolated from a
+/// voxel grid. The coefficients of `volume` are those at density one.
+///
+/// Hits are collisions found by delta tracking against the majorants of
+/// macro cells of the grid, and transmittance is estimated by ratio
+/// tracking, so both stay unbiased while empty cells are skipped. The normal
+/// of a hit faces back along the ray.
+#[derive(Debug)]
+pub struct GridMedium {
+    density: VoxelGrid,
+    majorants: MajorantGrid,
+    material: Material,
+}
+
+impl GridMedium {
+    pub fn new(density: VoxelGrid, volume: Volume) -> Self {
+        Self {
+            majorants: MajorantGrid::new(&density, MACRO_CELL_SIZE),
+            density,
+            material: Material::Volume(volume),
+        }
+    }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    #[inline(always)]
+    fn extinction(&self) -> f32 {
+        match &self.material {
+            Material::Volume(volume) => volume.extinction(),
+            _ => 0.0,
+        }
+    }
+
+    /// Walks the macro cells along the ray within `[t_min, t_max]`, drawing
+    /// tentative collisions with each cell's majorant, and calls `f` with
+    /// each one and the ratio of its density to the majorant until it
+    /// breaks.
+    fn track<T>(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        mut f: impl FnMut(f32, f32) -> ControlFlow<T>,
+    ) -> Option<T> {
+        let extinction = self.extinction() * ray.direction().magnitude();
+        if extinction <= 0.0 {
+            return None;
+        }
+
+        self.majorants
+            .traverse(ray, t_min, t_max, |start, end, majorant| {
+                if majorant <= 0.0 {
+                    return ControlFlow::Continue(());
+                }
+                let mut t = start;
+                loop {
+                    t -= (1.0 - random_f32()).ln() / (majorant * extinction);
+                    if t >= end {
+                        return ControlFlow::Continue(());
+                    }
+                    let density = self.density.sample(&ray.at(t));
+                    f(t, density / majorant)?;
+                }
+            })
+    }
+
+    /// Fraction of light passing through the medium along the ray within
+    /// `[t_min, t_max]`, estimated by ratio tracking.
+    pub fn transmittance(&self, ray: &Ray, t_min: f32, t_max: f32) -> f32 {
+        let mut transmittance = 1.0;
+        let absorbed = self.track(ray, t_min, t_max, |_, ratio| {
+            transmittance *= 1.0 - ratio;
+            if transmittance < ROULETTE_TRANSMITTANCE {
+                if random_f32() >= transmittance / ROULETTE_TRANSMITTANCE {
+                    return ControlFlow::Break(());
+                }
+                transmittance = ROULETTE_TRANSMITTANCE;
+            }
+            ControlFlow::Continue(())
+        });
+
+        match absorbed {
+            Some(()) => 0.0,
+            None => transmittance,
+        }
+    }
+}
+
+impl Hit for GridMedium {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        // Delta tracking: a tentative collision is real with the probability
+        // given by the density, and null otherwise.
+        let t = self.track(ray, t_min, t_max, |t, ratio| {
+            if random_f32() < ratio {
+                ControlFlow::Break(t)
+            } else {
+                ControlFlow::Continue(())
+            }
+        })?;
+
+        let position = ray.at(t);
+        Some(HitInfo {
+            t,
+            position,
+            local_position: position,
+            normal: -ray.direction().normalize(),
+            tangent: Vector3::zeros(),
+            bitangent: Vector3::zeros(),
+            u: 0.0,
+            v: 0.0,
+            footprint: Footprint::default(),
+            material: &self.material,
+        })
+    }
+}
+
+impl Bounded for GridMedium {
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(*self.density.bounds())
+    }
+}
+
+/// Media have no surface to emit from.
+impl SampleSurface for GridMedium {
+    fn area(&self) -> f32 {
+        0.0
+    }
+
+    fn sample_area(&self, _u: Vector2<f32>) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn sample_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn pdf_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _direction: &Vector3<f32>,
+    ) -> f32 {
+        0.0
+    }
+}
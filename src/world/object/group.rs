@@ -0,0 +1,226 @@
+use na::{Vector2, Vector3};
+
+use super::{Hit, HitInfo, Object, SampleSurface, SurfaceSample};
+use crate::world::{
+    aabb::{Aabb, Bounded},
+    bvh::Bvh,
+    material::Material,
+    ray::Ray,
+};
+
+/// Collection of objects with its own bounding volume hierarchy, meant to be
+/// shared between [`Transformed`](super::transformed::Transformed)
+/// instances. The world hierarchy then only spans the instances, and moving
+/// them only requires refitting it while the group's hierarchy stays as is.
+///
+/// Groups can't be sampled as lights and can't hold participating media.
+#[derive(Debug)]
+pub struct Group {
+    objects: Vec<Object>,
+    bvh: Bvh,
+    /// Indices of objects that have a bounding box, as indexed by `bvh`.
+    bounded: Vec<usize>,
+    /// Indices of objects that don't, and are always tested.
+    unbounded: Vec<usize>,
+}
+
+impl Group {
+    /// Panics if `objects` is empty or holds a participating medium.
+    pub fn new(objects: Vec<Object>) -> Self {
+        assert!(!objects.is_empty(), "group needs an object");
+        assert!(
+            objects.iter().all(|object| !object.is_medium()),
+            "groups can't hold participating media"
+        );
+
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        let mut bounds = Vec::new();
+        for (index, object) in objects.iter().enumerate() {
+            match object.bounding_box() {
+                Some(aabb) => {
+                    bounded.push(index);
+                    bounds.push(aabb);
+                }
+                None => unbounded.push(index),
+            }
+        }
+
+        Self {
+            bvh: Bvh::new(&bounds),
+            objects,
+            bounded,
+            unbounded,
+        }
+    }
+
+    #[inline(always)]
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    /// Groups have no material of their own, hits carry the material of the
+    /// object they land on. [`Object::material`] still has to return one for
+    /// every object, so this is the one of the first object. The world only
+    /// looks at it to find emitters to sample, which groups never are.
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        self.objects[0].material()
+    }
+}
+
+impl Hit for Group {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        let mut closest = None;
+        let mut t_max = t_max;
+        for &index in &self.unbounded {
+            if let Some(info) = self.objects[index].hit(ray, t_min, t_max) {
+                t_max = info.t;
+                closest = Some(info);
+            }
+        }
+
+        self.bvh
+            .traverse(ray, t_min, t_max, &mut 0, |i, t_max| {
+                self.objects[self.bounded[i]]
+                    .hit(ray, t_min, t_max)
+                    .map(|info| (info.t, info))
+            })
+            .or(closest)
+    }
+}
+
+impl Bounded for Group {
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.unbounded.is_empty().then(|| self.bvh.bounds())
+    }
+}
+
+/// Groups aren't sampled as a whole.
+impl SampleSurface for Group {
+    fn area(&self) -> f32 {
+        0.0
+    }
+
+    fn sample_area(&self, _u: Vector2<f32>) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn sample_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn pdf_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _direction: &Vector3<f32>,
+    ) -> f32 {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use na::Matrix4;
+
+    use super::*;
+    use crate::{
+        color::Color,
+        integrator::RAY_EPSILON,
+        sampler::Sampler2D,
+        world::{
+            material::emissive::Emissive,
+            object::{
+                sphere::Sphere, test_util::diffuse, transformed::Transformed,
+            },
+            World,
+        },
+    };
+
+    /// A diffuse ball at the origin and a glowing one next to it.
+    fn group() -> Arc<Object> {
+        let glowing = Material::Emissive(Emissive::new(
+            Sampler2D::Static(Color::gray(1.0)),
+            Sampler2D::Static(1.0),
+        ));
+        Arc::new(Object::Group(Group::new(vec![
+            Object::Sphere(Sphere::new(Vector3::zeros(), 1.0, diffuse())),
+            Object::Sphere(Sphere::new(Vector3::x() * 3.0, 1.0, glowing)),
+        ])))
+    }
+
+    fn instances(group: &Arc<Object>, offsets: &[Vector3<f32>]) -> World {
+        let mut world = World::new();
+        for offset in offsets {
+            world.add_object(Object::Transformed(Transformed::new(
+                Arc::clone(group),
+                Matrix4::new_translation(offset),
+            )));
+        }
+        world.build_bvh();
+        world
+    }
+
+    /// Asserts that the instance at `index` shows both balls of the group
+    /// around `offset`, each with its own material.
+    fn assert_instance(world: &World, index: usize, offset: Vector3<f32>) {
+        for (center, emissive) in
+            [(Vector3::zeros(), false), (Vector3::x() * 3.0, true)]
+        {
+            let origin = offset + center - Vector3::z() * 10.0;
+            let ray = Ray::new(origin, Vector3::z());
+            let (hit_index, hit_info) =
+                world.hit_object(&ray, RAY_EPSILON, f32::INFINITY).unwrap();
+            assert_eq!(hit_index, index);
+            let expected = offset + center - Vector3::z();
+            assert!((hit_info.position - expected).magnitude() < 1e-4);
+            assert_eq!(hit_info.material.is_emissive(), emissive);
+        }
+    }
+
+    #[test]
+    fn instances_hit_at_their_own_positions() {
+        let group = group();
+        let offsets = [
+            Vector3::zeros(),
+            Vector3::new(0.0, 10.0, 0.0),
+            Vector3::new(-10.0, -10.0, 5.0),
+        ];
+        let world = instances(&group, &offsets);
+        for (index, offset) in offsets.into_iter().enumerate() {
+            assert_instance(&world, index, offset);
+        }
+    }
+
+    #[test]
+    fn refit_leaves_the_group_hierarchy_alone() {
+        let group = group();
+        let Object::Group(shared) = group.as_ref() else {
+            unreachable!()
+        };
+        let hierarchy = format!("{:?}", shared.bvh);
+
+        let offsets = [Vector3::zeros(), Vector3::new(0.0, 10.0, 0.0)];
+        let mut world = instances(&group, &offsets);
+        let moved = Vector3::new(20.0, 0.0, 0.0);
+        let Object::Transformed(instance) = world.object_mut(1) else {
+            unreachable!()
+        };
+        instance.set_matrix(Matrix4::new_translation(&moved));
+        world.refit_bvh();
+
+        assert_instance(&world, 0, offsets[0]);
+        assert_instance(&world, 1, moved);
+        let Object::Transformed(instance) = world.object(1) else {
+            unreachable!()
+        };
+        assert!(Arc::ptr_eq(instance.object(), &group));
+        assert_eq!(format!("{:?}", shared.bvh), hierarchy);
+    }
+}
@@ -0,0 +1,429 @@
+use na::{Vector2, Vector3};
+
+use super::{Hit, HitInfo, SampleSurface, SurfaceSample};
+use crate::{
+    math::solve_quadratic,
+    sampler::{Footprint, Image2DSampler},
+    world::{
+        aabb::{Aabb, Bounded},
+        material::Material,
+        ray::Ray,
+    },
+};
+
+/// Terrain whose heights are the luminance of a grayscale image.
+///
+/// Pixels are height samples spread evenly over `extent` on the xz plane,
+/// with the left edge of the image at `origin.x` and its top edge at
+/// `origin.z`. Heights range from `origin.y` for black to
+/// `origin.y + height_scale` for white, interpolated bilinearly in between.
+/// Heights follow the stored pixel values for images read as
+/// [`ColorSpace::Raw`], while sRGB images are decoded first. Rays walk the
+/// grid cell by cell and skip cells whose height range they pass over or
+/// under. Normals are interpolated from the slopes at the samples, and `u`
+/// and `v` cover the extent so that the same image used as a texture lines
+/// up with the terrain. Heightfields aren't sampled as lights.
+///
+/// [`ColorSpace::Raw`]: crate::sampler::ColorSpace::Raw
+#[derive(Debug)]
+pub struct Heightfield {
+    origin: Vector3<f32>,
+    extent: Vector2<f32>,
+    /// Number of samples along x and z.
+    columns: usize,
+    rows: usize,
+    /// Size of a cell along x and z.
+    cell: Vector2<f32>,
+    /// Heights of the samples, row by row.
+    heights: Vec<f32>,
+    normals: Vec<Vector3<f32>>,
+    /// Lowest and highest height in each cell, row by row.
+    ranges: Vec<(f32, f32)>,
+    bounds: Aabb,
+    material: Material,
+}
+
+impl Heightfield {
+    /// Panics if the image has fewer than two pixels along either side.
+    pub fn new(
+        image: Image2DSampler,
+        origin: Vector3<f32>,
+        extent: Vector2<f32>,
+        height_scale: f32,
+        material: Material,
+    ) -> Self {
+        let (columns, rows) = (image.width() as usize, image.height() as usize);
+        assert!(
+            columns >= 2 && rows >= 2,
+            "a heightfield needs at least 2x2 samples"
+        );
+        let cell = extent.component_div(&Vector2::new(
+            (columns - 1) as f32,
+            (rows - 1) as f32,
+        ));
+
+        let heights: Vec<f32> = (0..rows)
+            .flat_map(|j| (0..columns).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let luminance = image.pixel(i as u32, j as u32).luminance();
+                origin.y + height_scale * luminance
+            })
+            .collect();
+        let height = |i: usize, j: usize| heights[j * columns + i];
+
+        // Slopes from the neighbouring samples, one-sided along the border.
+        let normals = (0..rows)
+            .flat_map(|j| (0..columns).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let (left, right) =
+                    (i.saturating_sub(1), (i + 1).min(columns - 1));
+                let (back, front) =
+                    (j.saturating_sub(1), (j + 1).min(rows - 1));
+                let slope_x = (height(right, j) - height(left, j)) /
+                    ((right - left) as f32 * cell.x);
+                let slope_z = (height(i, front) - height(i, back)) /
+                    ((front - back) as f32 * cell.y);
+                Vector3::new(-slope_x, 1.0, -slope_z).normalize()
+            })
+            .collect();
+
+        let ranges = (0..rows - 1)
+            .flat_map(|j| (0..columns - 1).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let corners = [
+                    height(i, j),
+                    height(i + 1, j),
+                    height(i, j + 1),
+                    height(i + 1, j + 1),
+                ];
+                corners.iter().fold(
+                    (f32::INFINITY, f32::NEG_INFINITY),
+                    |(low, high), &h| (low.min(h), high.max(h)),
+                )
+            })
+            .collect();
+
+        let (low, high) = heights
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), &h| {
+                (low.min(h), high.max(h))
+            });
+        let bounds = Aabb::new(
+            Vector3::new(origin.x, low, origin.z),
+            Vector3::new(origin.x + extent.x, high, origin.z + extent.y),
+        )
+        .padded();
+
+        Self {
+            origin,
+            extent,
+            columns,
+            rows,
+            cell,
+            heights,
+            normals,
+            ranges,
+            bounds,
+            material,
+        }
+    }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    #[inline(always)]
+    fn height(&self, i: usize, j: usize) -> f32 {
+        self.heights[j * self.columns + i]
+    }
+
+    #[inline(always)]
+    fn normal(&self, i: usize, j: usize) -> Vector3<f32> {
+        self.normals[j * self.columns + i]
+    }
+
+    /// Position of `point` on the xz plane in units of cells.
+    #[inline(always)]
+    fn to_grid(&self, point: &Vector3<f32>) -> Vector2<f32> {
+        (point.xz() - self.origin.xz()).component_div(&self.cell)
+    }
+
+    /// Intersects the bilinear patch of cell `(i, j)`, which the ray crosses
+    /// between `t0` and `t1`.
+    fn hit_cell(
+        &self,
+        ray: &Ray,
+        (i, j): (usize, usize),
+        t0: f32,
+        t1: f32,
+    ) -> Option<HitInfo<'_>> {
+        let (low, high) = self.ranges[j * (self.columns - 1) + i];
+        let (y0, y1) = (ray.at(t0).y, ray.at(t1).y);
+        if y0.max(y1) < low || high < y0.min(y1) {
+            return None;
+        }
+
+        // h = h00 + a x + b z + c x z within the cell, solved along the ray
+        // from where it enters the cell.
+        let h00 = self.height(i, j);
+        let a = self.height(i + 1, j) - h00;
+        let b = self.height(i, j + 1) - h00;
+        let c = self.height(i + 1, j + 1) - h00 - a - b;
+        let start = ray.at(t0);
+        let p = self.to_grid(&start) - Vector2::new(i as f32, j as f32);
+        let d = ray.direction().xz().component_div(&self.cell);
+        let (s0, s1) = solve_quadratic(
+            c * d.x * d.y,
+            a * d.x + b * d.y + c * (p.x * d.y + p.y * d.x) - ray.direction().y,
+            h00 + a * p.x + b * p.y + c * p.x * p.y - start.y,
+        )?;
+        let s = [s0, s1].into_iter().find(|s| (0.0..=t1 - t0).contains(s))?;
+
+        let t = t0 + s;
+        let position = ray.at(t);
+        let local = (p + s * d).map(|x| x.clamp(0.0, 1.0));
+        let normal = (1.0 - local.x) * (1.0 - local.y) * self.normal(i, j) +
+            local.x * (1.0 - local.y) * self.normal(i + 1, j) +
+            (1.0 - local.x) * local.y * self.normal(i, j + 1) +
+            local.x * local.y * self.normal(i + 1, j + 1);
+        let uv = (position.xz() - self.origin.xz()).component_div(&self.extent);
+        // Slopes of the patch, which u crosses left to right and v from the
+        // front row back.
+        let slope_x = (a + c * local.y) * (self.columns - 1) as f32;
+        let slope_z = (b + c * local.x) * (self.rows - 1) as f32;
+        Some(HitInfo {
+            t,
+            position,
+            local_position: position,
+            normal: normal.normalize(),
+            tangent: Vector3::new(self.extent.x, slope_x, 0.0),
+            bitangent: Vector3::new(0.0, -slope_z, -self.extent.y),
+            u: uv.x,
+            v: 1.0 - uv.y,
+            footprint: Footprint::default(),
+            material: &self.material,
+        })
+    }
+}
+
+impl Hit for Heightfield {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        let (t_enter, t_exit) = self.bounds.overlap(ray, t_min, t_max)?;
+
+        // 2D DDA over the cells, tracking for each axis the step direction,
+        // where the ray next crosses a cell border and the distance between
+        // those crossings.
+        let start = self.to_grid(&ray.at(t_enter));
+        let speed = ray.direction().xz().component_div(&self.cell);
+        let first_cell = |x: f32, samples: usize| {
+            (x.floor().max(0.0) as usize).min(samples - 2)
+        };
+        let axis = |x: f32, index: usize, speed: f32| {
+            if speed > 0.0 {
+                (1, t_enter + (index as f32 + 1.0 - x) / speed, 1.0 / speed)
+            } else if speed < 0.0 {
+                (-1, t_enter + (index as f32 - x) / speed, -1.0 / speed)
+            } else {
+                (0, f32::INFINITY, f32::INFINITY)
+            }
+        };
+        let mut i = first_cell(start.x, self.columns);
+        let mut j = first_cell(start.y, self.rows);
+        let (step_i, mut next_i, delta_i) = axis(start.x, i, speed.x);
+        let (step_j, mut next_j, delta_j) = axis(start.y, j, speed.y);
+
+        let mut t0 = t_enter;
+        loop {
+            let t1 = next_i.min(next_j).min(t_exit);
+            if let Some(hit_info) = self.hit_cell(ray, (i, j), t0, t1) {
+                return Some(hit_info);
+            }
+            if t1 >= t_exit {
+                return None;
+            }
+            if next_i < next_j {
+                i = i.checked_add_signed(step_i)?;
+                next_i += delta_i;
+            } else {
+                j = j.checked_add_signed(step_j)?;
+                next_j += delta_j;
+            }
+            if i >= self.columns - 1 || j >= self.rows - 1 {
+                return None;
+            }
+            t0 = t1;
+        }
+    }
+}
+
+impl Bounded for Heightfield {
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
+
+/// Heightfields aren't sampled, see [`Heightfield`].
+impl SampleSurface for Heightfield {
+    fn area(&self) -> f32 {
+        0.0
+    }
+
+    fn sample_area(&self, _u: Vector2<f32>) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn sample_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn pdf_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _direction: &Vector3<f32>,
+    ) -> f32 {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgb, Rgb32FImage};
+
+    use super::*;
+    use crate::{sampler::ColorSpace, world::object::test_util::diffuse};
+
+    /// Steps per unit of ray length for the reference march.
+    const MARCH_STEPS: f32 = 2_000.0;
+
+    fn heightfield(rng: &mut fastrand::Rng) -> Heightfield {
+        let image = Rgb32FImage::from_fn(7, 5, |_, _| {
+            let height = rng.f32();
+            Rgb([height; 3])
+        });
+        Heightfield::new(
+            Image2DSampler::from_image(
+                DynamicImage::ImageRgb32F(image),
+                ColorSpace::Raw,
+            ),
+            Vector3::new(-1.5, 0.0, -1.0),
+            Vector2::new(3.0, 2.0),
+            1.0,
+            diffuse(),
+        )
+    }
+
+    /// Bilinear height below `point`, if it is over the heightfield.
+    fn height_at(
+        heightfield: &Heightfield,
+        point: &Vector3<f32>,
+    ) -> Option<f32> {
+        let grid = heightfield.to_grid(point);
+        let last = Vector2::new(heightfield.columns, heightfield.rows)
+            .map(|n| (n - 1) as f32);
+        if grid.x < 0.0 || grid.y < 0.0 || grid.x > last.x || grid.y > last.y {
+            return None;
+        }
+        let i = (grid.x as usize).min(heightfield.columns - 2);
+        let j = (grid.y as usize).min(heightfield.rows - 2);
+        let (x, z) = (grid.x - i as f32, grid.y - j as f32);
+        Some(
+            (1.0 - x) * (1.0 - z) * heightfield.height(i, j) +
+                x * (1.0 - z) * heightfield.height(i + 1, j) +
+                (1.0 - x) * z * heightfield.height(i, j + 1) +
+                x * z * heightfield.height(i + 1, j + 1),
+        )
+    }
+
+    /// First crossing of the surface within `[t_min, t_max]` found by steps
+    /// of `1 / density` along the ray, refined by bisection.
+    fn march(
+        heightfield: &Heightfield,
+        ray: &Ray,
+        (t_min, t_max): (f32, f32),
+        density: f32,
+    ) -> Option<f32> {
+        let above = |t: f32| {
+            let point = ray.at(t);
+            height_at(heightfield, &point).map(|height| point.y > height)
+        };
+        let length = t_max - t_min;
+        let steps = (length * ray.direction().magnitude() * density) as u32;
+        let step = length / steps as f32;
+        let mut previous = above(t_min);
+        for k in 1..=steps {
+            let (mut low, mut high) =
+                (t_min + (k - 1) as f32 * step, t_min + k as f32 * step);
+            let current = above(high);
+            if let (Some(start), Some(end)) = (previous, current) {
+                if start != end {
+                    for _ in 0..20 {
+                        let mid = 0.5 * (low + high);
+                        if above(mid) == Some(start) {
+                            low = mid;
+                        } else {
+                            high = mid;
+                        }
+                    }
+                    return Some(0.5 * (low + high));
+                }
+            }
+            previous = current;
+        }
+        None
+    }
+
+    #[test]
+    fn traversal_matches_marching() {
+        let mut rng = fastrand::Rng::with_seed(42);
+        let heightfield = heightfield(&mut rng);
+        let mut hits = 0;
+        for _ in 0..300 {
+            // From above or beside the terrain, slanted downwards.
+            let origin = Vector3::new(
+                rng.f32() * 6.0 - 3.0,
+                1.0 + rng.f32(),
+                rng.f32() * 4.0 - 2.0,
+            );
+            let target = Vector3::new(
+                rng.f32() * 3.0 - 1.5,
+                rng.f32(),
+                rng.f32() * 2.0 - 1.0,
+            );
+            let ray = Ray::new(origin, target - origin);
+            let t_max = 2.0;
+
+            let found = heightfield.hit(&ray, 0.0, t_max).map(|hit| hit.t);
+            let mut expected =
+                march(&heightfield, &ray, (0.0, t_max), MARCH_STEPS);
+            if let (Some(t), None) = (found, expected) {
+                // Grazing rays can dip below the surface for less than a
+                // step, look closer.
+                let window = 10.0 / MARCH_STEPS;
+                expected = march(
+                    &heightfield,
+                    &ray,
+                    (t - window, t + window),
+                    100.0 * MARCH_STEPS,
+                );
+            }
+            match (found, expected) {
+                (Some(found), Some(expected)) => {
+                    assert!(
+                        (found - expected).abs() < 1e-3,
+                        "{ray:?}: {found} != {expected}"
+                    );
+                    hits += 1;
+                }
+                (None, None) => {}
+                _ => panic!("{ray:?}: {found:?} != {expected:?}"),
+            }
+        }
+        // Most rays aim at the terrain.
+        assert!(hits > 150, "{hits}");
+    }
+}
@@ -0,0 +1,213 @@
+use std::ops::ControlFlow;
+
+use na::{Vector2, Vector3};
+
+use super::{Hit, HitInfo, Object, SampleSurface, SurfaceSample};
+use crate::{
+    math::random_f32,
+    sampler::Footprint,
+    world::{
+        aabb::{Aabb, Bounded},
+        material::{volume::Volume, Material},
+        ray::Ray,
+    },
+};
+
+/// Parameter offset used to step past the boundary when looking for the
+/// next crossing.
+const BOUNDARY_EPSILON: f32 = 0.0001;
+
+/// Homogeneous participating medium filling a closed `boundary` object.
+///
+/// Hits are free-flight distances sampled from the extinction of `volume`
+/// for every segment of the ray inside the boundary, so rays pass through
+/// the medium with the probability given by its transmittance and otherwise
+/// scatter inside it. The normal of a hit faces back along the ray.
+#[derive(Debug)]
+pub struct ConstantMedium {
+    boundary: Box<Object>,
+    material: Material,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Object, volume: Volume) -> Self {
+        Self {
+            boundary: Box::new(boundary),
+            material: Material::Volume(volume),
+        }
+    }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    #[inline(always)]
+    fn extinction(&self) -> f32 {
+        match &self.material {
+            Material::Volume(volume) => volume.extinction(),
+            _ => 0.0,
+        }
+    }
+
+    /// Calls `f` with the parametric range of every segment of the ray
+    /// within `[t_min, t_max]` that lies inside the boundary, in order, until
+    /// it breaks. A non-convex boundary can produce several.
+    fn for_each_segment<T>(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        mut f: impl FnMut(f32, f32) -> ControlFlow<T>,
+    ) -> Option<T> {
+        let mut t = f32::NEG_INFINITY;
+        loop {
+            let entry = self.boundary.hit(ray, t, f32::INFINITY)?;
+            if entry.t >= t_max {
+                return None;
+            }
+            let exit = self.boundary.hit(
+                ray,
+                entry.t + BOUNDARY_EPSILON,
+                f32::INFINITY,
+            )?;
+
+            let start = entry.t.max(t_min);
+            let end = exit.t.min(t_max);
+            if start < end {
+                if let ControlFlow::Break(result) = f(start, end) {
+                    return Some(result);
+                }
+            }
+
+            // Also stops on degenerate rays, whose hits have NaN distances.
+            let next = exit.t + BOUNDARY_EPSILON;
+            if next.is_nan() || next <= t {
+                return None;
+            }
+            t = next;
+        }
+    }
+
+    /// Fraction of light passing through the medium along the ray within
+    /// `[t_min, t_max]`.
+    pub fn transmittance(&self, ray: &Ray, t_min: f32, t_max: f32) -> f32 {
+        let mut inside = 0.0;
+        self.for_each_segment(ray, t_min, t_max, |start, end| {
+            inside += end - start;
+            ControlFlow::<()>::Continue(())
+        });
+        (-self.extinction() * inside * ray.direction().magnitude()).exp()
+    }
+}
+
+impl Hit for ConstantMedium {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        let extinction = self.extinction();
+        if extinction <= 0.0 {
+            return None;
+        }
+        let length = ray.direction().magnitude();
+
+        // The sampled distance is memoryless, so every segment can be
+        // sampled on its own.
+        let t = self.for_each_segment(ray, t_min, t_max, |start, end| {
+            let distance = -(1.0 - random_f32()).ln() / extinction;
+            if distance < (end - start) * length {
+                ControlFlow::Break(start + distance / length)
+            } else {
+                ControlFlow::Continue(())
+            }
+        })?;
+
+        let position = ray.at(t);
+        Some(HitInfo {
+            t,
+            position,
+            local_position: position,
+            normal: -ray.direction() / length,
+            tangent: Vector3::zeros(),
+            bitangent: Vector3::zeros(),
+            u: 0.0,
+            v: 0.0,
+            footprint: Footprint::default(),
+            material: &self.material,
+        })
+    }
+}
+
+impl Bounded for ConstantMedium {
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+/// Media have no surface to emit from.
+impl SampleSurface for ConstantMedium {
+    fn area(&self) -> f32 {
+        0.0
+    }
+
+    fn sample_area(&self, _u: Vector2<f32>) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn sample_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn pdf_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _direction: &Vector3<f32>,
+    ) -> f32 {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::Color,
+        world::{
+            material::volume::PhaseFunction,
+            object::{sphere::Sphere, test_util::diffuse},
+        },
+    };
+
+    #[test]
+    fn transmittance_decays_with_the_distance_inside() {
+        let extinction = 0.7;
+        let medium = ConstantMedium::new(
+            Object::Sphere(Sphere::new(Vector3::zeros(), 2.0, diffuse())),
+            Volume::new(0.3, 0.4, Color::gray(1.0), PhaseFunction::Isotropic),
+        );
+
+        for offset in [0.0, 1.0, 1.9] {
+            // Directions of any length, parametrized accordingly.
+            for length in [1.0, 3.0] {
+                let ray = Ray::new(
+                    Vector3::new(offset, 0.0, -5.0),
+                    Vector3::z() * length,
+                );
+                let chord = 2.0 * (4.0 - offset * offset).sqrt();
+                let expected = (-extinction * chord).exp();
+                let found = medium.transmittance(&ray, 0.0, 10.0 / length);
+                assert!((found - expected).abs() < 1e-4, "{found}");
+
+                // Stopping at the center only crosses half of the medium.
+                let found = medium.transmittance(&ray, 0.0, 5.0 / length);
+                let expected = (-extinction * chord / 2.0).exp();
+                assert!((found - expected).abs() < 1e-4, "{found}");
+            }
+        }
+
+        let ray = Ray::new(Vector3::new(2.5, 0.0, -5.0), Vector3::z());
+        assert_eq!(medium.transmittance(&ray, 0.0, 10.0), 1.0);
+    }
+}
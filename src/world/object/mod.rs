@@ -1,34 +1,599 @@
+pub mod capsule;
+pub mod cone;
+pub mod csg;
+pub mod cuboid;
+pub mod cylinder;
+pub mod disk;
+mod frame;
+pub mod grid_medium;
+pub mod group;
+pub mod heightfield;
+pub mod medium;
+pub mod moving;
+pub mod plane;
+pub mod quad;
+pub mod sdf;
 pub mod sphere;
+pub mod torus;
+pub mod transformed;
 
-use na::Vector3;
+use na::{Vector2, Vector3};
 
-use self::sphere::Sphere;
-use super::{material::Material, ray::Ray};
+use self::{
+    capsule::Capsule, cone::Cone, csg::Csg, cuboid::Cuboid, cylinder::Cylinder,
+    disk::Disk, grid_medium::GridMedium, group::Group,
+    heightfield::Heightfield, medium::ConstantMedium, moving::Moving,
+    plane::Plane, quad::Quad, sdf::SdfObject, sphere::Sphere, torus::Torus,
+    transformed::Transformed,
+};
+use super::{
+    aabb::{Aabb, Bounded},
+    material::Material,
+    ray::Ray,
+};
+use crate::sampler::Footprint;
 
 #[derive(Debug)]
 pub enum Object {
     Sphere(Sphere),
+    Plane(Plane),
+    Quad(Quad),
+    Disk(Disk),
+    Cuboid(Cuboid),
+    Cylinder(Cylinder),
+    Cone(Cone),
+    Capsule(Capsule),
+    Torus(Torus),
+    Sdf(SdfObject),
+    Heightfield(Heightfield),
+    Medium(ConstantMedium),
+    GridMedium(GridMedium),
+    Moving(Moving),
+    Transformed(Transformed),
+    Group(Group),
+    Csg(Csg),
 }
 
 pub trait Hit {
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo>;
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>>;
+
+    /// All parametric intervals in which the whole line through `ray` lies
+    /// inside the object, sorted and disjoint, with the hits at both ends.
+    /// The normals at both ends point out of the object.
+    ///
+    /// Only closed objects report intervals, see [`Object::is_closed`].
+    fn intervals(&self, _ray: &Ray) -> Vec<Interval<'_>> {
+        Vec::new()
+    }
 }
 
-#[derive(Debug)]
+/// Surfaces that can be sampled as area lights.
+pub trait SampleSurface {
+    fn area(&self) -> f32;
+
+    /// Samples a point uniformly by area. The returned density is with
+    /// respect to surface area.
+    fn sample_area(&self, u: Vector2<f32>) -> Option<SurfaceSample>;
+
+    /// Samples a point on the surface that is likely visible from `origin`.
+    /// The returned density is with respect to solid angle at `origin`.
+    fn sample_from(
+        &self,
+        origin: &Vector3<f32>,
+        u: Vector2<f32>,
+    ) -> Option<SurfaceSample>;
+
+    /// Solid angle density with which `sample_from` picks the normalized
+    /// `direction` from `origin`.
+    fn pdf_from(&self, origin: &Vector3<f32>, direction: &Vector3<f32>) -> f32;
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct HitInfo<'a> {
     pub t: f32,
     pub position: Vector3<f32>,
+    /// Position in the space the object was modelled in, before wrappers
+    /// such as [`Transformed`] and [`Moving`] placed it in the world.
+    pub local_position: Vector3<f32>,
     pub normal: Vector3<f32>,
+    /// Change of `position` with `u` and `v`, spanning the tangent plane that
+    /// normal and bump maps are oriented by. Zero where the texture
+    /// coordinates don't vary, as inside media.
+    pub tangent: Vector3<f32>,
+    pub bitangent: Vector3<f32>,
     pub u: f32,
     pub v: f32,
+    /// Area around `(u, v)` that textures are filtered over. Objects leave
+    /// it empty, [`World`](crate::world::World) fills it in for rays with
+    /// differentials.
+    pub footprint: Footprint,
     pub material: &'a Material,
 }
 
+/// Part of a ray inside a closed object, see [`Hit::intervals`].
+#[derive(Debug, Clone, Copy)]
+pub struct Interval<'a> {
+    pub enter: HitInfo<'a>,
+    pub exit: HitInfo<'a>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SurfaceSample {
+    pub position: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub u: f32,
+    pub v: f32,
+    pub pdf: f32,
+}
+
+/// Interval of a convex object, which a line enters and leaves at most once.
+/// The entry is the first hit along the whole line, and the exit the first
+/// one along the reversed line.
+pub(super) fn convex_intervals<'a>(
+    object: &'a impl Hit,
+    ray: &Ray,
+) -> Vec<Interval<'a>> {
+    let reversed = Ray::with_time(ray.origin(), -ray.direction(), ray.time());
+    let hits = (
+        object.hit(ray, f32::NEG_INFINITY, f32::INFINITY),
+        object.hit(&reversed, f32::NEG_INFINITY, f32::INFINITY),
+    );
+    match hits {
+        (Some(enter), Some(exit)) => {
+            vec![Interval {
+                enter,
+                exit: HitInfo { t: -exit.t, ..exit },
+            }]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Converts a density `pdf` with respect to area at `position`, where the
+/// surface faces along `normal`, into one with respect to solid angle at
+/// `origin`.
+#[inline(always)]
+pub(super) fn area_to_solid_angle(
+    pdf: f32,
+    origin: &Vector3<f32>,
+    position: &Vector3<f32>,
+    normal: &Vector3<f32>,
+) -> f32 {
+    let to_point = position - origin;
+    let distance_sq = to_point.magnitude_squared();
+    let cos = normal.dot(&to_point).abs() / distance_sq.sqrt();
+    pdf * distance_sq / cos
+}
+
+impl Object {
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        match self {
+            Object::Sphere(sphere) => sphere.material(),
+            Object::Plane(plane) => plane.material(),
+            Object::Quad(quad) => quad.material(),
+            Object::Disk(disk) => disk.material(),
+            Object::Cuboid(cuboid) => cuboid.material(),
+            Object::Cylinder(cylinder) => cylinder.material(),
+            Object::Cone(cone) => cone.material(),
+            Object::Capsule(capsule) => capsule.material(),
+            Object::Torus(torus) => torus.material(),
+            Object::Sdf(sdf) => sdf.material(),
+            Object::Heightfield(heightfield) => heightfield.material(),
+            Object::Medium(medium) => medium.material(),
+            Object::GridMedium(medium) => medium.material(),
+            Object::Moving(moving) => moving.material(),
+            Object::Transformed(transformed) => transformed.material(),
+            Object::Group(group) => group.material(),
+            Object::Csg(csg) => csg.material(),
+        }
+    }
+
+    /// Whether the object is a participating medium rather than a surface.
+    #[inline(always)]
+    pub fn is_medium(&self) -> bool {
+        match self {
+            Object::Medium(_) | Object::GridMedium(_) => true,
+            Object::Moving(moving) => moving.is_medium(),
+            Object::Transformed(transformed) => transformed.is_medium(),
+            Object::Sphere(_) |
+            Object::Plane(_) |
+            Object::Quad(_) |
+            Object::Disk(_) |
+            Object::Cuboid(_) |
+            Object::Cylinder(_) |
+            Object::Cone(_) |
+            Object::Capsule(_) |
+            Object::Torus(_) |
+            Object::Sdf(_) |
+            Object::Heightfield(_) |
+            Object::Group(_) |
+            Object::Csg(_) => false,
+        }
+    }
+
+    /// Whether the object encloses a volume, so that it reports its
+    /// [`Hit::intervals`] and can be combined by [`Csg`].
+    #[inline(always)]
+    pub fn is_closed(&self) -> bool {
+        match self {
+            Object::Sphere(_) |
+            Object::Cuboid(_) |
+            Object::Cylinder(_) |
+            Object::Cone(_) |
+            Object::Capsule(_) |
+            Object::Torus(_) |
+            Object::Csg(_) => true,
+            Object::Plane(_) |
+            Object::Quad(_) |
+            Object::Disk(_) |
+            Object::Sdf(_) |
+            Object::Heightfield(_) |
+            Object::Medium(_) |
+            Object::GridMedium(_) |
+            Object::Group(_) => false,
+            Object::Moving(moving) => moving.is_closed(),
+            Object::Transformed(transformed) => transformed.is_closed(),
+        }
+    }
+
+    /// Whether the surface of the object can be sampled, so that it can
+    /// serve as an area light when emissive.
+    #[inline(always)]
+    pub fn is_sampleable(&self) -> bool {
+        match self {
+            Object::Sphere(_) | Object::Quad(_) | Object::Disk(_) => true,
+            Object::Plane(_) |
+            Object::Cuboid(_) |
+            Object::Cylinder(_) |
+            Object::Cone(_) |
+            Object::Capsule(_) |
+            Object::Torus(_) |
+            Object::Sdf(_) |
+            Object::Heightfield(_) |
+            Object::Medium(_) |
+            Object::GridMedium(_) |
+            Object::Moving(_) |
+            Object::Group(_) |
+            Object::Csg(_) => false,
+            Object::Transformed(transformed) => transformed.is_sampleable(),
+        }
+    }
+
+    /// Fraction of light passing through a medium along the ray within
+    /// `[t_min, t_max]`. Surfaces are opaque when hit and transmit everything
+    /// otherwise, so this is one for them.
+    #[inline(always)]
+    pub fn transmittance(&self, ray: &Ray, t_min: f32, t_max: f32) -> f32 {
+        match self {
+            Object::Sphere(_) |
+            Object::Plane(_) |
+            Object::Quad(_) |
+            Object::Disk(_) |
+            Object::Cuboid(_) |
+            Object::Cylinder(_) |
+            Object::Cone(_) |
+            Object::Capsule(_) |
+            Object::Torus(_) |
+            Object::Sdf(_) |
+            Object::Heightfield(_) |
+            Object::Group(_) |
+            Object::Csg(_) => 1.0,
+            Object::Medium(medium) => medium.transmittance(ray, t_min, t_max),
+            Object::GridMedium(medium) => {
+                medium.transmittance(ray, t_min, t_max)
+            }
+            Object::Moving(moving) => moving.transmittance(ray, t_min, t_max),
+            Object::Transformed(transformed) => {
+                transformed.transmittance(ray, t_min, t_max)
+            }
+        }
+    }
+}
+
 impl Hit for Object {
     #[inline(always)]
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
         match self {
             Object::Sphere(sphere) => sphere.hit(ray, t_min, t_max),
+            Object::Plane(plane) => plane.hit(ray, t_min, t_max),
+            Object::Quad(quad) => quad.hit(ray, t_min, t_max),
+            Object::Disk(disk) => disk.hit(ray, t_min, t_max),
+            Object::Cuboid(cuboid) => cuboid.hit(ray, t_min, t_max),
+            Object::Cylinder(cylinder) => cylinder.hit(ray, t_min, t_max),
+            Object::Cone(cone) => cone.hit(ray, t_min, t_max),
+            Object::Capsule(capsule) => capsule.hit(ray, t_min, t_max),
+            Object::Torus(torus) => torus.hit(ray, t_min, t_max),
+            Object::Sdf(sdf) => sdf.hit(ray, t_min, t_max),
+            Object::Heightfield(heightfield) => {
+                heightfield.hit(ray, t_min, t_max)
+            }
+            Object::Medium(medium) => medium.hit(ray, t_min, t_max),
+            Object::GridMedium(medium) => medium.hit(ray, t_min, t_max),
+            Object::Moving(moving) => moving.hit(ray, t_min, t_max),
+            Object::Transformed(transformed) => {
+                transformed.hit(ray, t_min, t_max)
+            }
+            Object::Group(group) => group.hit(ray, t_min, t_max),
+            Object::Csg(csg) => csg.hit(ray, t_min, t_max),
         }
     }
+
+    #[inline(always)]
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        match self {
+            Object::Sphere(sphere) => sphere.intervals(ray),
+            Object::Cuboid(cuboid) => cuboid.intervals(ray),
+            Object::Cylinder(cylinder) => cylinder.intervals(ray),
+            Object::Cone(cone) => cone.intervals(ray),
+            Object::Capsule(capsule) => capsule.intervals(ray),
+            Object::Torus(torus) => torus.intervals(ray),
+            Object::Moving(moving) => moving.intervals(ray),
+            Object::Transformed(transformed) => transformed.intervals(ray),
+            Object::Csg(csg) => csg.intervals(ray),
+            Object::Plane(_) |
+            Object::Quad(_) |
+            Object::Disk(_) |
+            Object::Sdf(_) |
+            Object::Heightfield(_) |
+            Object::Medium(_) |
+            Object::GridMedium(_) |
+            Object::Group(_) => Vec::new(),
+        }
+    }
+}
+
+impl Bounded for Object {
+    #[inline(always)]
+    fn bounding_box(&self) -> Option<Aabb> {
+        match self {
+            Object::Sphere(sphere) => sphere.bounding_box(),
+            Object::Plane(plane) => plane.bounding_box(),
+            Object::Quad(quad) => quad.bounding_box(),
+            Object::Disk(disk) => disk.bounding_box(),
+            Object::Cuboid(cuboid) => cuboid.bounding_box(),
+            Object::Cylinder(cylinder) => cylinder.bounding_box(),
+            Object::Cone(cone) => cone.bounding_box(),
+            Object::Capsule(capsule) => capsule.bounding_box(),
+            Object::Torus(torus) => torus.bounding_box(),
+            Object::Sdf(sdf) => sdf.bounding_box(),
+            Object::Heightfield(heightfield) => heightfield.bounding_box(),
+            Object::Medium(medium) => medium.bounding_box(),
+            Object::GridMedium(medium) => medium.bounding_box(),
+            Object::Moving(moving) => moving.bounding_box(),
+            Object::Transformed(transformed) => transformed.bounding_box(),
+            Object::Group(group) => group.bounding_box(),
+            Object::Csg(csg) => csg.bounding_box(),
+        }
+    }
+}
+
+impl SampleSurface for Object {
+    #[inline(always)]
+    fn area(&self) -> f32 {
+        match self {
+            Object::Sphere(sphere) => sphere.area(),
+            Object::Plane(plane) => plane.area(),
+            Object::Quad(quad) => quad.area(),
+            Object::Disk(disk) => disk.area(),
+            Object::Cuboid(cuboid) => cuboid.area(),
+            Object::Cylinder(cylinder) => cylinder.area(),
+            Object::Cone(cone) => cone.area(),
+            Object::Capsule(capsule) => capsule.area(),
+            Object::Torus(torus) => torus.area(),
+            Object::Sdf(sdf) => sdf.area(),
+            Object::Heightfield(heightfield) => heightfield.area(),
+            Object::Medium(medium) => medium.area(),
+            Object::GridMedium(medium) => medium.area(),
+            Object::Moving(moving) => moving.area(),
+            Object::Transformed(transformed) => transformed.area(),
+            Object::Group(group) => group.area(),
+            Object::Csg(csg) => csg.area(),
+        }
+    }
+
+    #[inline(always)]
+    fn sample_area(&self, u: Vector2<f32>) -> Option<SurfaceSample> {
+        match self {
+            Object::Sphere(sphere) => sphere.sample_area(u),
+            Object::Plane(plane) => plane.sample_area(u),
+            Object::Quad(quad) => quad.sample_area(u),
+            Object::Disk(disk) => disk.sample_area(u),
+            Object::Cuboid(cuboid) => cuboid.sample_area(u),
+            Object::Cylinder(cylinder) => cylinder.sample_area(u),
+            Object::Cone(cone) => cone.sample_area(u),
+            Object::Capsule(capsule) => capsule.sample_area(u),
+            Object::Torus(torus) => torus.sample_area(u),
+            Object::Sdf(sdf) => sdf.sample_area(u),
+            Object::Heightfield(heightfield) => heightfield.sample_area(u),
+            Object::Medium(medium) => medium.sample_area(u),
+            Object::GridMedium(medium) => medium.sample_area(u),
+            Object::Moving(moving) => moving.sample_area(u),
+            Object::Transformed(transformed) => transformed.sample_area(u),
+            Object::Group(group) => group.sample_area(u),
+            Object::Csg(csg) => csg.sample_area(u),
+        }
+    }
+
+    #[inline(always)]
+    fn sample_from(
+        &self,
+        origin: &Vector3<f32>,
+        u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        match self {
+            Object::Sphere(sphere) => sphere.sample_from(origin, u),
+            Object::Plane(plane) => plane.sample_from(origin, u),
+            Object::Quad(quad) => quad.sample_from(origin, u),
+            Object::Disk(disk) => disk.sample_from(origin, u),
+            Object::Cuboid(cuboid) => cuboid.sample_from(origin, u),
+            Object::Cylinder(cylinder) => cylinder.sample_from(origin, u),
+            Object::Cone(cone) => cone.sample_from(origin, u),
+            Object::Capsule(capsule) => capsule.sample_from(origin, u),
+            Object::Torus(torus) => torus.sample_from(origin, u),
+            Object::Sdf(sdf) => sdf.sample_from(origin, u),
+            Object::Heightfield(heightfield) => {
+                heightfield.sample_from(origin, u)
+            }
+            Object::Medium(medium) => medium.sample_from(origin, u),
+            Object::GridMedium(medium) => medium.sample_from(origin, u),
+            Object::Moving(moving) => moving.sample_from(origin, u),
+            Object::Transformed(transformed) => {
+                transformed.sample_from(origin, u)
+            }
+            Object::Group(group) => group.sample_from(origin, u),
+            Object::Csg(csg) => csg.sample_from(origin, u),
+        }
+    }
+
+    #[inline(always)]
+    fn pdf_from(&self, origin: &Vector3<f32>, direction: &Vector3<f32>) -> f32 {
+        match self {
+            Object::Sphere(sphere) => sphere.pdf_from(origin, direction),
+            Object::Plane(plane) => plane.pdf_from(origin, direction),
+            Object::Quad(quad) => quad.pdf_from(origin, direction),
+            Object::Disk(disk) => disk.pdf_from(origin, direction),
+            Object::Cuboid(cuboid) => cuboid.pdf_from(origin, direction),
+            Object::Cylinder(cylinder) => cylinder.pdf_from(origin, direction),
+            Object::Cone(cone) => cone.pdf_from(origin, direction),
+            Object::Capsule(capsule) => capsule.pdf_from(origin, direction),
+            Object::Torus(torus) => torus.pdf_from(origin, direction),
+            Object::Sdf(sdf) => sdf.pdf_from(origin, direction),
+            Object::Heightfield(heightfield) => {
+                heightfield.pdf_from(origin, direction)
+            }
+            Object::Medium(medium) => medium.pdf_from(origin, direction),
+            Object::GridMedium(medium) => medium.pdf_from(origin, direction),
+            Object::Moving(moving) => moving.pdf_from(origin, direction),
+            Object::Transformed(transformed) => {
+                transformed.pdf_from(origin, direction)
+            }
+            Object::Group(group) => group.pdf_from(origin, direction),
+            Object::Csg(csg) => csg.pdf_from(origin, direction),
+        }
+    }
+}
+
+/// Checks shared by the tests of the individual objects.
+#[cfg(test)]
+mod test_util {
+    use std::f32::consts::PI;
+
+    use na::{Vector2, Vector3};
+
+    use super::{Hit, HitInfo, SampleSurface};
+    use crate::{
+        color::Color,
+        sampler::Sampler2D,
+        sampling::uniform_sphere,
+        world::{
+            material::{lambertian::Lambertian, Material},
+            ray::Ray,
+        },
+    };
+
+    /// Step along the surface for finite differences.
+    const STEP: f32 = 1e-3;
+
+    pub fn diffuse() -> Material {
+        Material::Lambertian(Lambertian::new(Sampler2D::Static(Color::gray(
+            0.5,
+        ))))
+    }
+
+    /// Hit of the ray from `origin` towards `target`, asserting that it
+    /// lands within `1e-3` of `target`.
+    pub fn hit_towards<'a>(
+        object: &'a impl Hit,
+        origin: Vector3<f32>,
+        target: Vector3<f32>,
+    ) -> HitInfo<'a> {
+        let ray = Ray::new(origin, target - origin);
+        let hit_info = object
+            .hit(&ray, 0.0, f32::INFINITY)
+            .unwrap_or_else(|| panic!("ray towards {target:?} missed"));
+        assert!(
+            (hit_info.position - target).magnitude() < 1e-3,
+            "hit {:?} instead of {target:?}",
+            hit_info.position
+        );
+        hit_info
+    }
+
+    /// Asserts that the hit at `target` has the expected unit normal, that
+    /// its tangent and bitangent span the surface in a right-handed frame
+    /// around it, and that stepping along them changes only `u` and only `v`
+    /// respectively, by the step over their lengths.
+    pub fn assert_surface(
+        object: &impl Hit,
+        origin: Vector3<f32>,
+        target: Vector3<f32>,
+        normal: Vector3<f32>,
+    ) {
+        let hit_info = hit_towards(object, origin, target);
+        assert!(
+            (hit_info.normal - normal).magnitude() < 1e-3,
+            "normal {:?} instead of {normal:?} at {target:?}",
+            hit_info.normal
+        );
+        assert!(hit_info.tangent.dot(&normal).abs() < 1e-3);
+        assert!(hit_info.bitangent.dot(&normal).abs() < 1e-3);
+        assert!(
+            hit_info.tangent.cross(&hit_info.bitangent).dot(&normal) > 0.0,
+            "left-handed tangent frame at {target:?}"
+        );
+
+        for (direction, expected) in [
+            (hit_info.tangent, Vector2::x()),
+            (hit_info.bitangent, Vector2::y()),
+        ] {
+            let length = direction.magnitude();
+            let stepped = target + STEP * direction / length;
+            // Aim at the stepped point, then measure the texture coordinates
+            // where the ray actually meets the curved surface.
+            let ray = Ray::new(origin, stepped - origin);
+            let moved = object.hit(&ray, 0.0, f32::INFINITY).unwrap();
+            let found =
+                Vector2::new(moved.u - hit_info.u, moved.v - hit_info.v) *
+                    length /
+                    STEP;
+            assert!(
+                (found - expected).magnitude() < 0.05,
+                "texture coordinates moved by {found:?} instead of \
+                 {expected:?} at {target:?}"
+            );
+        }
+    }
+
+    /// Asserts that `sample_from` and `pdf_from` agree on the density of
+    /// the directions from `origin` towards the object, and that the density
+    /// integrates to one over all directions.
+    pub fn assert_sampling(object: &impl SampleSurface, origin: Vector3<f32>) {
+        let mut rng = fastrand::Rng::with_seed(1);
+        for _ in 0..100 {
+            let u = Vector2::new(rng.f32(), rng.f32());
+            let sample = object.sample_from(&origin, u).unwrap();
+            let direction = (sample.position - origin).normalize();
+            let pdf = object.pdf_from(&origin, &direction);
+            assert!(
+                (pdf - sample.pdf).abs() < 1e-3 * sample.pdf,
+                "density {pdf} instead of {} towards {:?}",
+                sample.pdf,
+                sample.position
+            );
+        }
+
+        let directions = 100_000;
+        let sum: f32 = (0..directions)
+            .map(|_| {
+                let u = Vector2::new(rng.f32(), rng.f32());
+                object.pdf_from(&origin, &uniform_sphere(u))
+            })
+            .sum();
+        let integral = sum * 4.0 * PI / directions as f32;
+        assert!(
+            (integral - 1.0).abs() < 0.05,
+            "density integrates to {integral}"
+        );
+    }
 }
@@ -0,0 +1,232 @@
+use na::{Matrix4, UnitQuaternion, Vector2, Vector3};
+
+use super::{
+    transformed::{
+        hit_transformed, intervals_transformed, to_object, transform_bounds,
+    },
+    Hit, HitInfo, Interval, Object, SampleSurface, SurfaceSample,
+};
+use crate::world::{
+    aabb::{Aabb, Bounded},
+    material::Material,
+    ray::Ray,
+};
+
+/// Placement of an object at one instant: scaled, then rotated, then
+/// translated.
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe {
+    pub time: f32,
+    pub translation: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Keyframe {
+    pub fn new(
+        time: f32,
+        translation: Vector3<f32>,
+        rotation: UnitQuaternion<f32>,
+        scale: Vector3<f32>,
+    ) -> Self {
+        Self {
+            time,
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    /// Interpolates towards `other` at `time`, which must lie between the
+    /// times of both keyframes.
+    fn interpolate(&self, other: &Keyframe, time: f32) -> Keyframe {
+        let span = other.time - self.time;
+        let s = if span > 0.0 {
+            (time - self.time) / span
+        } else {
+            0.0
+        };
+        Keyframe {
+            time,
+            translation: self.translation.lerp(&other.translation, s),
+            rotation: self.rotation.slerp(&other.rotation, s),
+            scale: self.scale.lerp(&other.scale, s),
+        }
+    }
+
+    fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::new_translation(&self.translation) *
+            self.rotation.to_homogeneous() *
+            Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+
+    fn inverse(&self) -> Matrix4<f32> {
+        Matrix4::new_nonuniform_scaling(&self.scale.map(f32::recip)) *
+            self.rotation.inverse().to_homogeneous() *
+            Matrix4::new_translation(&-self.translation)
+    }
+}
+
+/// How a [`Moving`] object is placed over time.
+#[derive(Debug)]
+pub enum Motion {
+    /// Translation by `from` at time zero moving linearly to `to` at time
+    /// one, and resting outside that interval.
+    Linear {
+        from: Vector3<f32>,
+        to: Vector3<f32>,
+    },
+    /// Placement interpolated between keyframes sorted by time, and held
+    /// before the first and after the last.
+    Keyframes(Vec<Keyframe>),
+}
+
+/// Object placed according to its [`Motion`] at the time of each ray.
+///
+/// Moving emitters aren't sampled as lights, they are only found by rays
+/// hitting them.
+#[derive(Debug)]
+pub struct Moving {
+    object: Box<Object>,
+    motion: Motion,
+}
+
+impl Moving {
+    /// Panics if `motion` has no keyframes.
+    pub fn new(object: Object, mut motion: Motion) -> Self {
+        if let Motion::Keyframes(keyframes) = &mut motion {
+            assert!(!keyframes.is_empty(), "motion needs a keyframe");
+            keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        }
+        Self {
+            object: Box::new(object),
+            motion,
+        }
+    }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        self.object.material()
+    }
+
+    #[inline(always)]
+    pub fn is_medium(&self) -> bool {
+        self.object.is_medium()
+    }
+
+    #[inline(always)]
+    pub fn is_closed(&self) -> bool {
+        self.object.is_closed()
+    }
+
+    /// Object to world matrix and its inverse at `time`.
+    fn transform(&self, time: f32) -> (Matrix4<f32>, Matrix4<f32>) {
+        match &self.motion {
+            Motion::Linear { from, to } => {
+                let offset = from.lerp(to, time.clamp(0.0, 1.0));
+                (
+                    Matrix4::new_translation(&offset),
+                    Matrix4::new_translation(&-offset),
+                )
+            }
+            Motion::Keyframes(keyframes) => {
+                let next = keyframes.partition_point(|k| k.time <= time);
+                let keyframe = match next {
+                    0 => keyframes[0],
+                    n if n == keyframes.len() => keyframes[n - 1],
+                    n => keyframes[n - 1].interpolate(&keyframes[n], time),
+                };
+                (keyframe.matrix(), keyframe.inverse())
+            }
+        }
+    }
+
+    pub fn transmittance(&self, ray: &Ray, t_min: f32, t_max: f32) -> f32 {
+        let (_, inverse) = self.transform(ray.time());
+        self.object
+            .transmittance(&to_object(ray, &inverse), t_min, t_max)
+    }
+}
+
+impl Hit for Moving {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        let (matrix, inverse) = self.transform(ray.time());
+        hit_transformed(&self.object, &matrix, &inverse, ray, t_min, t_max)
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        let (matrix, inverse) = self.transform(ray.time());
+        intervals_transformed(&self.object, &matrix, &inverse, ray)
+    }
+}
+
+impl Bounded for Moving {
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bounds = self.object.bounding_box()?;
+        let place = |matrix: &Matrix4<f32>| transform_bounds(&bounds, matrix);
+
+        match &self.motion {
+            Motion::Linear { from, to } => {
+                Some(
+                    place(&Matrix4::new_translation(from))
+                        .union(&place(&Matrix4::new_translation(to))),
+                )
+            }
+            Motion::Keyframes(keyframes) => {
+                let mut aabb = place(&keyframes[0].matrix());
+                for pair in keyframes.windows(2) {
+                    let (a, b) = (&pair[0], &pair[1]);
+                    aabb = aabb.union(&place(&b.matrix()));
+                    if a.rotation.angle_to(&b.rotation) == 0.0 {
+                        // Corners then move linearly, so the keyframes bound
+                        // everything in between.
+                        continue;
+                    }
+                    // A rotating box stays within the sphere through its
+                    // farthest corner around the translation.
+                    let scale = a.scale.abs().sup(&b.scale.abs());
+                    let radius = bounds
+                        .corners()
+                        .iter()
+                        .map(|corner| corner.component_mul(&scale).magnitude())
+                        .fold(0.0, f32::max);
+                    let reach = Vector3::repeat(radius);
+                    for translation in [a.translation, b.translation] {
+                        aabb = aabb.union(&Aabb::new(
+                            translation - reach,
+                            translation + reach,
+                        ));
+                    }
+                }
+                Some(aabb)
+            }
+        }
+    }
+}
+
+/// Moving objects have no fixed surface to sample.
+impl SampleSurface for Moving {
+    fn area(&self) -> f32 {
+        0.0
+    }
+
+    fn sample_area(&self, _u: Vector2<f32>) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn sample_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn pdf_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _direction: &Vector3<f32>,
+    ) -> f32 {
+        0.0
+    }
+}
@@ -0,0 +1,169 @@
+use na::{Vector2, Vector3};
+
+use super::{Hit, HitInfo, SampleSurface, SurfaceSample};
+use crate::{
+    math::orthonormal_basis,
+    sampler::Footprint,
+    world::{
+        aabb::{Aabb, Bounded},
+        material::Material,
+        ray::Ray,
+    },
+};
+
+/// Infinite plane through `point`. Texture coordinates repeat every unit
+/// along two directions in the plane.
+#[derive(Debug)]
+pub struct Plane {
+    point: Vector3<f32>,
+    normal: Vector3<f32>,
+    tangent: Vector3<f32>,
+    bitangent: Vector3<f32>,
+    material: Material,
+}
+
+impl Plane {
+    pub fn new(
+        point: Vector3<f32>,
+        normal: Vector3<f32>,
+        material: Material,
+    ) -> Self {
+        let normal = normal.normalize();
+        let (tangent, bitangent) = orthonormal_basis(&normal);
+        Self {
+            point,
+            normal,
+            tangent,
+            bitangent,
+            material,
+        }
+    }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+impl Hit for Plane {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        let t = plane_distance(ray, &self.point, &self.normal)?;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        let position = ray.at(t);
+        let offset = position - self.point;
+        Some(HitInfo {
+            t,
+            position,
+            local_position: position,
+            normal: self.normal,
+            tangent: self.tangent,
+            bitangent: self.bitangent,
+            u: offset.dot(&self.tangent).rem_euclid(1.0),
+            v: offset.dot(&self.bitangent).rem_euclid(1.0),
+            footprint: Footprint::default(),
+            material: &self.material,
+        })
+    }
+}
+
+/// Parametric distance along `ray` to the plane through `point` with
+/// `normal`, or `None` if the ray runs parallel to it.
+#[inline(always)]
+pub(super) fn plane_distance(
+    ray: &Ray,
+    point: &Vector3<f32>,
+    normal: &Vector3<f32>,
+) -> Option<f32> {
+    let denom = normal.dot(&ray.direction());
+    if denom.abs() < 1e-9 {
+        return None;
+    }
+    Some(normal.dot(&(point - ray.origin())) / denom)
+}
+
+impl Bounded for Plane {
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+/// Infinite planes can't be sampled uniformly.
+impl SampleSurface for Plane {
+    fn area(&self) -> f32 {
+        0.0
+    }
+
+    fn sample_area(&self, _u: Vector2<f32>) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn sample_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn pdf_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _direction: &Vector3<f32>,
+    ) -> f32 {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::object::test_util::{
+        assert_surface, diffuse, hit_towards,
+    };
+
+    fn plane() -> Plane {
+        Plane::new(Vector3::new(0.0, -1.0, 0.0), Vector3::y() * 3.0, diffuse())
+    }
+
+    #[test]
+    fn plane_is_parametrized_by_its_tangents() {
+        let plane = plane();
+        for target in
+            [Vector3::new(0.3, -1.0, 0.4), Vector3::new(-20.0, -1.0, 7.5)]
+        {
+            let origin = target + Vector3::new(0.5, 2.0, -0.5);
+            assert_surface(&plane, origin, target, Vector3::y());
+        }
+    }
+
+    #[test]
+    fn texture_coordinates_repeat_every_unit() {
+        let plane = plane();
+        let target = Vector3::new(0.3, -1.0, 0.4);
+        let first = hit_towards(&plane, target + Vector3::y(), target);
+        let next = target + plane.tangent * 2.0 + plane.bitangent * 3.0;
+        let repeated = hit_towards(&plane, next + Vector3::y(), next);
+        assert!((first.u - repeated.u).abs() < 1e-4);
+        assert!((first.v - repeated.v).abs() < 1e-4);
+    }
+
+    #[test]
+    fn parallel_rays_miss() {
+        let plane = plane();
+        let ray = Ray::new(Vector3::zeros(), Vector3::x());
+        assert!(plane.hit(&ray, 0.0, f32::INFINITY).is_none());
+        let ray = Ray::new(Vector3::zeros(), Vector3::y());
+        assert!(plane.hit(&ray, 0.0, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn planes_are_not_sampled() {
+        let plane = plane();
+        let origin = Vector3::zeros();
+        assert!(plane.sample_from(&origin, Vector2::repeat(0.5)).is_none());
+        assert_eq!(plane.pdf_from(&origin, &-Vector3::y()), 0.0);
+    }
+}
@@ -0,0 +1,238 @@
+use na::{Vector2, Vector3};
+
+use super::{
+    area_to_solid_angle, plane::plane_distance, Hit, HitInfo, SampleSurface,
+    SurfaceSample,
+};
+use crate::{
+    sampler::Footprint,
+    world::{
+        aabb::{Aabb, Bounded},
+        material::Material,
+        ray::Ray,
+    },
+};
+
+/// Parallelogram spanned by `edge_u` and `edge_v` from `corner`. The normal
+/// is `edge_u × edge_v` and texture coordinates run along the edges.
+#[derive(Debug)]
+pub struct Quad {
+    corner: Vector3<f32>,
+    edge_u: Vector3<f32>,
+    edge_v: Vector3<f32>,
+    normal: Vector3<f32>,
+    /// `edge_u × edge_v` divided by its squared length, which maps offsets
+    /// in the plane to texture coordinates.
+    w: Vector3<f32>,
+    area: f32,
+    material: Material,
+}
+
+impl Quad {
+    pub fn new(
+        corner: Vector3<f32>,
+        edge_u: Vector3<f32>,
+        edge_v: Vector3<f32>,
+        material: Material,
+    ) -> Self {
+        let n = edge_u.cross(&edge_v);
+        let area = n.magnitude();
+        Self {
+            corner,
+            edge_u,
+            edge_v,
+            normal: n / area,
+            w: n / (area * area),
+            area,
+            material,
+        }
+    }
+
+    /// Axis-aligned rectangle between the corners `min` and `max`, which
+    /// must agree in exactly one coordinate. The normal points along that
+    /// axis.
+    pub fn axis_aligned(
+        min: Vector3<f32>,
+        max: Vector3<f32>,
+        material: Material,
+    ) -> Self {
+        let extent = max - min;
+        let flat: Vec<_> = (0..3).filter(|&axis| extent[axis] == 0.0).collect();
+        assert!(
+            flat.len() == 1,
+            "axis-aligned rectangle from {min:?} to {max:?} needs exactly \
+             one flat axis"
+        );
+
+        // Edges along the next two axes in cyclic order, so their cross
+        // product points along the flat axis.
+        let axis = flat[0];
+        let edge = |offset: usize| {
+            let along = (axis + offset) % 3;
+            let mut edge = Vector3::zeros();
+            edge[along] = extent[along];
+            edge
+        };
+        Self::new(min, edge(1), edge(2), material)
+    }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    #[inline(always)]
+    fn point(&self, u: f32, v: f32) -> Vector3<f32> {
+        self.corner + u * self.edge_u + v * self.edge_v
+    }
+}
+
+impl Hit for Quad {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        let t = plane_distance(ray, &self.corner, &self.normal)?;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        let position = ray.at(t);
+        let offset = position - self.corner;
+        let u = self.w.dot(&offset.cross(&self.edge_v));
+        let v = self.w.dot(&self.edge_u.cross(&offset));
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
+        }
+
+        Some(HitInfo {
+            t,
+            position,
+            local_position: position,
+            normal: self.normal,
+            tangent: self.edge_u,
+            bitangent: self.edge_v,
+            u,
+            v,
+            footprint: Footprint::default(),
+            material: &self.material,
+        })
+    }
+}
+
+impl Bounded for Quad {
+    fn bounding_box(&self) -> Option<Aabb> {
+        let aabb = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)]
+            .iter()
+            .fold(Aabb::empty(), |aabb, &(u, v)| {
+                aabb.include(&self.point(u, v))
+            });
+        Some(aabb.padded())
+    }
+}
+
+impl SampleSurface for Quad {
+    fn area(&self) -> f32 {
+        self.area
+    }
+
+    fn sample_area(&self, u: Vector2<f32>) -> Option<SurfaceSample> {
+        Some(SurfaceSample {
+            position: self.point(u.x, u.y),
+            normal: self.normal,
+            u: u.x,
+            v: u.y,
+            pdf: self.area.recip(),
+        })
+    }
+
+    fn sample_from(
+        &self,
+        origin: &Vector3<f32>,
+        u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        let sample = self.sample_area(u)?;
+        let pdf = area_to_solid_angle(
+            sample.pdf,
+            origin,
+            &sample.position,
+            &sample.normal,
+        );
+        pdf.is_finite().then_some(SurfaceSample { pdf, ..sample })
+    }
+
+    fn pdf_from(&self, origin: &Vector3<f32>, direction: &Vector3<f32>) -> f32 {
+        match self.hit(&Ray::new(*origin, *direction), 0.0, f32::INFINITY) {
+            Some(hit_info) => {
+                area_to_solid_angle(
+                    self.area.recip(),
+                    origin,
+                    &hit_info.position,
+                    &self.normal,
+                )
+            }
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::object::test_util::{
+        assert_sampling, assert_surface, diffuse, hit_towards,
+    };
+
+    /// A parallelogram, so that the edges aren't orthogonal.
+    fn quad() -> Quad {
+        Quad::new(
+            Vector3::new(-1.0, 0.0, -1.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 2.0),
+            diffuse(),
+        )
+    }
+
+    #[test]
+    fn quad_is_parametrized_by_its_edges() {
+        let quad = quad();
+        let normal = -Vector3::y();
+        for (u, v) in [(0.25, 0.5), (0.9, 0.1), (0.5, 0.95)] {
+            let target = quad.point(u, v);
+            let origin = target + normal * 2.0 + Vector3::x() * 0.5;
+            assert_surface(&quad, origin, target, normal);
+            let hit_info = hit_towards(&quad, origin, target);
+            assert!((hit_info.u - u).abs() < 1e-5);
+            assert!((hit_info.v - v).abs() < 1e-5);
+            // The normal doesn't depend on the side the ray comes from.
+            let behind = hit_towards(&quad, target - normal, target);
+            assert_eq!(behind.normal, normal);
+        }
+    }
+
+    #[test]
+    fn rays_beside_the_quad_miss() {
+        let quad = quad();
+        for (u, v) in [(-0.1, 0.5), (1.1, 0.5), (0.5, -0.1), (0.5, 1.1)] {
+            let target = quad.point(u, v);
+            let ray = Ray::new(target + Vector3::y(), -Vector3::y());
+            assert!(quad.hit(&ray, 0.0, f32::INFINITY).is_none());
+        }
+    }
+
+    #[test]
+    fn axis_aligned_rectangles_face_along_the_flat_axis() {
+        let quad = Quad::axis_aligned(
+            Vector3::new(0.0, 1.0, 2.0),
+            Vector3::new(0.0, 3.0, 5.0),
+            diffuse(),
+        );
+        let target = Vector3::new(0.0, 2.0, 3.0);
+        assert_surface(&quad, target + Vector3::x(), target, Vector3::x());
+        assert!((quad.area() - 6.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn sampling_densities_agree() {
+        let quad = quad();
+        assert_sampling(&quad, Vector3::new(0.0, -1.5, 0.0));
+        assert_sampling(&quad, Vector3::new(3.0, 1.0, 2.0));
+    }
+}
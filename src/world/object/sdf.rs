@@ -0,0 +1,449 @@
+use na::{Vector2, Vector3};
+
+use super::{Hit, HitInfo, SampleSurface, SurfaceSample};
+use crate::{
+    sampler::Footprint,
+    world::{
+        aabb::{Aabb, Bounded},
+        material::Material,
+        ray::Ray,
+    },
+};
+
+/// Maximum number of sphere tracing steps along a ray.
+const MAX_STEPS: usize = 512;
+/// Distance below which sphere tracing counts as a hit.
+const HIT_DISTANCE: f32 = 1e-4;
+/// Offset of the samples used to estimate the gradient.
+const GRADIENT_OFFSET: f32 = 1e-4;
+
+/// Tree of signed distance functions, negative inside the surface.
+///
+/// Build trees with the constructors and combinators, e.g.
+/// `Sdf::sphere(1.0).smooth_union(Sdf::torus(1.2, 0.2), 0.3).round(0.05)`.
+/// Primitives are centered at the origin and placed with
+/// [`Sdf::translate`].
+#[derive(Debug, Clone)]
+pub enum Sdf {
+    Sphere {
+        radius: f32,
+    },
+    Box {
+        half_extent: Vector3<f32>,
+    },
+    /// Torus around the y axis.
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+    },
+    Capsule {
+        start: Vector3<f32>,
+        end: Vector3<f32>,
+        radius: f32,
+    },
+    /// Distance estimate of the Mandelbulb fractal, which fits in a sphere
+    /// of radius 1.2 for the usual power of 8.
+    Mandelbulb {
+        power: f32,
+        iterations: usize,
+    },
+    Translate {
+        offset: Vector3<f32>,
+        sdf: Box<Sdf>,
+    },
+    Scale {
+        factor: f32,
+        sdf: Box<Sdf>,
+    },
+    Union(Box<Sdf>, Box<Sdf>),
+    Intersection(Box<Sdf>, Box<Sdf>),
+    /// Inside the first but not the second.
+    Difference(Box<Sdf>, Box<Sdf>),
+    /// Union blending both surfaces within `smoothness` of each other.
+    SmoothUnion {
+        a: Box<Sdf>,
+        b: Box<Sdf>,
+        smoothness: f32,
+    },
+    /// Rotation around the y axis by `rate` radians per unit of height.
+    /// Twisting stretches distances, see [`SdfObject::with_step_scale`].
+    Twist {
+        rate: f32,
+        sdf: Box<Sdf>,
+    },
+    /// Infinite repetition with the given period along each axis, where a
+    /// period of zero doesn't repeat. The repeated shape has to fit into a
+    /// single cell.
+    Repeat {
+        period: Vector3<f32>,
+        sdf: Box<Sdf>,
+    },
+    /// Surface moved outwards by `radius`, which rounds edges.
+    Round {
+        radius: f32,
+        sdf: Box<Sdf>,
+    },
+}
+
+impl Sdf {
+    pub fn sphere(radius: f32) -> Self {
+        Sdf::Sphere { radius }
+    }
+
+    pub fn cuboid(half_extent: Vector3<f32>) -> Self {
+        Sdf::Box { half_extent }
+    }
+
+    pub fn torus(major_radius: f32, minor_radius: f32) -> Self {
+        Sdf::Torus {
+            major_radius,
+            minor_radius,
+        }
+    }
+
+    pub fn capsule(
+        start: Vector3<f32>,
+        end: Vector3<f32>,
+        radius: f32,
+    ) -> Self {
+        Sdf::Capsule { start, end, radius }
+    }
+
+    pub fn mandelbulb(power: f32, iterations: usize) -> Self {
+        Sdf::Mandelbulb { power, iterations }
+    }
+
+    pub fn translate(self, offset: Vector3<f32>) -> Self {
+        Sdf::Translate {
+            offset,
+            sdf: Box::new(self),
+        }
+    }
+
+    pub fn scale(self, factor: f32) -> Self {
+        Sdf::Scale {
+            factor,
+            sdf: Box::new(self),
+        }
+    }
+
+    pub fn union(self, other: Sdf) -> Self {
+        Sdf::Union(Box::new(self), Box::new(other))
+    }
+
+    pub fn intersection(self, other: Sdf) -> Self {
+        Sdf::Intersection(Box::new(self), Box::new(other))
+    }
+
+    pub fn difference(self, other: Sdf) -> Self {
+        Sdf::Difference(Box::new(self), Box::new(other))
+    }
+
+    pub fn smooth_union(self, other: Sdf, smoothness: f32) -> Self {
+        Sdf::SmoothUnion {
+            a: Box::new(self),
+            b: Box::new(other),
+            smoothness,
+        }
+    }
+
+    pub fn twist(self, rate: f32) -> Self {
+        Sdf::Twist {
+            rate,
+            sdf: Box::new(self),
+        }
+    }
+
+    pub fn repeat(self, period: Vector3<f32>) -> Self {
+        Sdf::Repeat {
+            period,
+            sdf: Box::new(self),
+        }
+    }
+
+    pub fn round(self, radius: f32) -> Self {
+        Sdf::Round {
+            radius,
+            sdf: Box::new(self),
+        }
+    }
+
+    /// Signed distance from `p` to the surface, or a lower bound of it.
+    pub fn distance(&self, p: &Vector3<f32>) -> f32 {
+        match self {
+            Sdf::Sphere { radius } => p.magnitude() - radius,
+            Sdf::Box { half_extent } => {
+                let q = p.abs() - half_extent;
+                q.sup(&Vector3::zeros()).magnitude() + q.max().min(0.0)
+            }
+            Sdf::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let ring = p.xz().magnitude() - major_radius;
+                Vector2::new(ring, p.y).magnitude() - minor_radius
+            }
+            Sdf::Capsule { start, end, radius } => {
+                let axis = end - start;
+                let offset = p - start;
+                let h = (offset.dot(&axis) / axis.magnitude_squared())
+                    .clamp(0.0, 1.0);
+                (offset - h * axis).magnitude() - radius
+            }
+            Sdf::Mandelbulb { power, iterations } => {
+                mandelbulb(p, *power, *iterations)
+            }
+            Sdf::Translate { offset, sdf } => sdf.distance(&(p - offset)),
+            Sdf::Scale { factor, sdf } => factor * sdf.distance(&(p / *factor)),
+            Sdf::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Sdf::Intersection(a, b) => a.distance(p).max(b.distance(p)),
+            Sdf::Difference(a, b) => a.distance(p).max(-b.distance(p)),
+            Sdf::SmoothUnion { a, b, smoothness } => {
+                // Polynomial smooth minimum, which is a plain minimum for
+                // no smoothness.
+                let (da, db) = (a.distance(p), b.distance(p));
+                if *smoothness <= 0.0 {
+                    return da.min(db);
+                }
+                let h = (0.5 + 0.5 * (db - da) / smoothness).clamp(0.0, 1.0);
+                db + (da - db) * h - smoothness * h * (1.0 - h)
+            }
+            Sdf::Twist { rate, sdf } => {
+                let (sin, cos) = (rate * p.y).sin_cos();
+                let twisted = Vector3::new(
+                    cos * p.x - sin * p.z,
+                    p.y,
+                    sin * p.x + cos * p.z,
+                );
+                sdf.distance(&twisted)
+            }
+            Sdf::Repeat { period, sdf } => {
+                let cell = p.zip_map(period, |x, period| {
+                    if period > 0.0 {
+                        x - period * (x / period).round()
+                    } else {
+                        x
+                    }
+                });
+                sdf.distance(&cell)
+            }
+            Sdf::Round { radius, sdf } => sdf.distance(p) - radius,
+        }
+    }
+}
+
+/// Distance estimate of the Mandelbulb from the derivative of its orbit. At
+/// least one iteration is run, without any the estimate is zero everywhere.
+fn mandelbulb(p: &Vector3<f32>, power: f32, iterations: usize) -> f32 {
+    let mut z = *p;
+    let mut derivative = 1.0;
+    let mut r = 0.0;
+    for _ in 0..iterations.max(1) {
+        r = z.magnitude();
+        if r > 2.0 {
+            break;
+        }
+        let theta = (z.z / r).clamp(-1.0, 1.0).acos() * power;
+        let phi = z.y.atan2(z.x) * power;
+        derivative = power * r.powf(power - 1.0) * derivative + 1.0;
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        z = r.powf(power) *
+            Vector3::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta) +
+            p;
+    }
+    if r == 0.0 {
+        return 0.0;
+    }
+    0.5 * r.ln() * r / derivative
+}
+
+/// Surface of a signed distance function within `bounds`, intersected by
+/// sphere tracing.
+///
+/// Normals are the gradient of the distance, and texture coordinates are a
+/// triplanar projection: the position projected along the axis closest to
+/// the normal, repeating every unit. SDF objects aren't sampled as lights.
+#[derive(Debug)]
+pub struct SdfObject {
+    sdf: Sdf,
+    bounds: Aabb,
+    step_scale: f32,
+    material: Material,
+}
+
+impl SdfObject {
+    /// Traces `sdf` only inside `bounds`, which must enclose the surface.
+    pub fn new(sdf: Sdf, bounds: Aabb, material: Material) -> Self {
+        Self {
+            sdf,
+            bounds,
+            step_scale: 1.0,
+            material,
+        }
+    }
+
+    /// Shortens every tracing step by `scale`, for trees that overestimate
+    /// distances, such as twists, which would otherwise step through the
+    /// surface.
+    pub fn with_step_scale(mut self, scale: f32) -> Self {
+        self.step_scale = scale;
+        self
+    }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// Gradient of the distance, estimated from four samples around `p`.
+    fn normal(&self, p: &Vector3<f32>) -> Vector3<f32> {
+        let h = GRADIENT_OFFSET;
+        [
+            Vector3::new(1.0, -1.0, -1.0),
+            Vector3::new(-1.0, -1.0, 1.0),
+            Vector3::new(-1.0, 1.0, -1.0),
+            Vector3::new(1.0, 1.0, 1.0),
+        ]
+        .iter()
+        .map(|k| k * self.sdf.distance(&(p + h * k)))
+        .sum::<Vector3<f32>>()
+        .normalize()
+    }
+}
+
+impl Hit for SdfObject {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        let (start, end) = self.bounds.overlap(ray, t_min, t_max)?;
+        if !end.is_finite() {
+            return None;
+        }
+
+        // Distances are measured in space, steps in ray parameters.
+        let to_parameter = ray.direction().magnitude().recip();
+        let mut t = start;
+        // Rays leaving a surface start within reach of it, so a hit only
+        // counts once the march has been clear of the surface or stepped
+        // through it. Until then, steps are long enough to get away.
+        let mut cleared = false;
+        let mut previous_inside = None;
+        for _ in 0..MAX_STEPS {
+            let position = ray.at(t);
+            let signed = self.sdf.distance(&position);
+            let inside = signed < 0.0;
+            // Stepping by the unsigned distance works from either side of
+            // the surface.
+            let distance = signed.abs();
+            let crossed = previous_inside.is_some_and(|was| was != inside);
+            cleared |= distance >= HIT_DISTANCE;
+            if crossed || (cleared && distance < HIT_DISTANCE) {
+                let normal = self.normal(&position);
+                let axis = normal.abs().imax();
+                return Some(HitInfo {
+                    t,
+                    position,
+                    local_position: position,
+                    normal,
+                    tangent: Vector3::ith((axis + 1) % 3, 1.0),
+                    bitangent: Vector3::ith((axis + 2) % 3, 1.0),
+                    u: position[(axis + 1) % 3].rem_euclid(1.0),
+                    v: position[(axis + 2) % 3].rem_euclid(1.0),
+                    footprint: Footprint::default(),
+                    material: &self.material,
+                });
+            }
+            previous_inside = Some(inside);
+            t += (distance * self.step_scale).max(HIT_DISTANCE) * to_parameter;
+            if t > end {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+impl Bounded for SdfObject {
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
+
+/// SDF objects aren't sampled, see [`SdfObject`].
+impl SampleSurface for SdfObject {
+    fn area(&self) -> f32 {
+        0.0
+    }
+
+    fn sample_area(&self, _u: Vector2<f32>) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn sample_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn pdf_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _direction: &Vector3<f32>,
+    ) -> f32 {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{integrator::RAY_EPSILON, world::object::test_util::diffuse};
+
+    fn traced(sdf: Sdf) -> SdfObject {
+        let bounds = Aabb::new(Vector3::repeat(-2.0), Vector3::repeat(2.0));
+        SdfObject::new(sdf, bounds, diffuse())
+    }
+
+    #[test]
+    fn sphere_is_hit_from_outside() {
+        let sphere = traced(Sdf::sphere(1.0));
+        let ray = Ray::new(Vector3::new(0.6, 0.0, -5.0), Vector3::z());
+        let hit_info = sphere.hit(&ray, 0.0, f32::INFINITY).unwrap();
+        assert!((hit_info.t - 4.2).abs() < 1e-3, "{}", hit_info.t);
+        let normal = Vector3::new(0.6, 0.0, -0.8);
+        assert!((hit_info.normal - normal).magnitude() < 1e-3);
+
+        let beside = Ray::new(Vector3::new(1.1, 0.0, -5.0), Vector3::z());
+        assert!(sphere.hit(&beside, 0.0, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn grazing_rays_leave_the_surface() {
+        let sphere = traced(Sdf::sphere(1.0));
+        // Launched from the top almost along the surface, as a reflection
+        // off it would be.
+        let top = Vector3::y();
+        for rise in [1e-3, 1e-2, 0.1] {
+            let ray = Ray::new(top, Vector3::new(1.0, rise, 0.0));
+            let hit = sphere.hit(&ray, RAY_EPSILON, f32::INFINITY);
+            assert!(hit.is_none(), "{rise}: {:?}", hit.map(|hit| hit.t));
+        }
+
+        // Other parts of the surface are still found after leaving.
+        let bowl = traced(Sdf::sphere(1.5).difference(Sdf::sphere(1.0)));
+        let ray = Ray::new(top, Vector3::new(1.0, 0.01, 0.0));
+        let hit_info = bowl.hit(&ray, RAY_EPSILON, f32::INFINITY).unwrap();
+        assert!((hit_info.position.magnitude() - 1.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn degenerate_parameters_stay_finite() {
+        let p = Vector3::new(0.3, 0.2, 0.1);
+        let blend = Sdf::sphere(1.0).smooth_union(Sdf::sphere(1.0), 0.0);
+        assert_eq!(blend.distance(&p), p.magnitude() - 1.0);
+        for point in [p, Vector3::repeat(1.5)] {
+            assert!(Sdf::mandelbulb(8.0, 0).distance(&point).is_finite());
+        }
+    }
+}
@@ -1,9 +1,19 @@
 use std::f32::consts::{PI, TAU};
 
-use na::Vector3;
+use na::{Vector2, Vector3};
 
-use super::{Hit, HitInfo};
-use crate::world::{material::Material, ray::Ray};
+use super::{
+    convex_intervals, Hit, HitInfo, Interval, SampleSurface, SurfaceSample,
+};
+use crate::{
+    sampler::Footprint,
+    sampling::{uniform_cone, uniform_cone_pdf, uniform_sphere},
+    world::{
+        aabb::{Aabb, Bounded},
+        material::Material,
+        ray::Ray,
+    },
+};
 
 #[derive(Debug)]
 pub struct Sphere {
@@ -20,10 +30,81 @@ impl Sphere {
             material,
         }
     }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// Texture coordinates of the point with outward `normal`.
+    #[inline(always)]
+    fn uv(normal: &Vector3<f32>) -> (f32, f32) {
+        let th = (-normal.y).clamp(-1.0, 1.0).acos();
+        let phi = fast_math::atan2(-normal.z, normal.x) + PI;
+        (phi / TAU, th / PI)
+    }
+
+    /// Change of the point with `normal` with the texture coordinates of
+    /// [`Sphere::uv`]. `u` doesn't change the point at the poles.
+    #[inline(always)]
+    fn tangents(&self, normal: &Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+        let tangent =
+            TAU * self.radius * Vector3::new(normal.z, 0.0, -normal.x);
+        let ring = normal.xz().magnitude();
+        let bitangent = if ring > 0.0 {
+            PI * self.radius *
+                Vector3::new(
+                    -normal.y * normal.x / ring,
+                    ring,
+                    -normal.y * normal.z / ring,
+                )
+        } else {
+            Vector3::zeros()
+        };
+        (tangent, bitangent)
+    }
+
+    #[inline(always)]
+    fn surface_sample(&self, normal: Vector3<f32>, pdf: f32) -> SurfaceSample {
+        let (u, v) = Self::uv(&normal);
+        SurfaceSample {
+            position: self.center + self.radius * normal,
+            normal,
+            u,
+            v,
+            pdf,
+        }
+    }
+
+    /// Cosine of the half-angle of the cone the sphere subtends from
+    /// `origin`, or `None` if `origin` is inside the sphere.
+    #[inline(always)]
+    fn cone_cos_theta_max(&self, origin: &Vector3<f32>) -> Option<f32> {
+        let dist_sq = (self.center - origin).magnitude_squared();
+        let radius_sq = self.radius * self.radius;
+        if dist_sq <= radius_sq {
+            return None;
+        }
+        Some((1.0 - radius_sq / dist_sq).max(0.0).sqrt())
+    }
+
+    /// Converts an area density at `position` into a solid angle density at
+    /// `origin`.
+    #[inline(always)]
+    fn area_to_solid_angle(
+        &self,
+        origin: &Vector3<f32>,
+        position: &Vector3<f32>,
+    ) -> f32 {
+        let to_point = position - origin;
+        let normal = (position - self.center) / self.radius;
+        let cos = normal.dot(&to_point).abs() / to_point.magnitude();
+        to_point.magnitude_squared() / (cos * self.area())
+    }
 }
 
 impl Hit for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
         let oc = ray.origin() - self.center;
         let a = ray.direction().magnitude_squared();
         let half_b = oc.dot(&ray.direction());
@@ -46,20 +127,86 @@ impl Hit for Sphere {
 
         let point = ray.at(t);
         let normal = (point - self.center) / self.radius;
-
-        let th = (-point.y).acos();
-        let phi = fast_math::atan2(-point.z, point.x) + PI;
-
-        let u = phi / TAU;
-        let v = th / PI;
+        let (u, v) = Self::uv(&normal);
+        let (tangent, bitangent) = self.tangents(&normal);
 
         Some(HitInfo {
             t,
             position: point,
+            local_position: point,
             normal,
+            tangent,
+            bitangent,
             u,
             v,
+            footprint: Footprint::default(),
             material: &self.material,
         })
     }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        convex_intervals(self, ray)
+    }
+}
+
+impl Bounded for Sphere {
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vector3::repeat(self.radius.abs());
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+impl SampleSurface for Sphere {
+    fn area(&self) -> f32 {
+        4.0 * PI * self.radius * self.radius
+    }
+
+    fn sample_area(&self, u: Vector2<f32>) -> Option<SurfaceSample> {
+        Some(self.surface_sample(uniform_sphere(u), self.area().recip()))
+    }
+
+    fn sample_from(
+        &self,
+        origin: &Vector3<f32>,
+        u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        let Some(cos_theta_max) = self.cone_cos_theta_max(origin) else {
+            // Inside the sphere every point is visible, sample by area.
+            let sample = self.surface_sample(uniform_sphere(u), 1.0);
+            let pdf = self.area_to_solid_angle(origin, &sample.position);
+            return pdf.is_finite().then_some(SurfaceSample { pdf, ..sample });
+        };
+
+        let to_center = self.center - origin;
+        let axis = to_center.normalize();
+        let direction = uniform_cone(&axis, cos_theta_max, u);
+
+        // Closest intersection with the sphere, clamped to the tangent point
+        // for directions that graze the silhouette.
+        let b = direction.dot(&to_center);
+        let discriminant =
+            self.radius * self.radius - (to_center.magnitude_squared() - b * b);
+        let t = b - discriminant.max(0.0).sqrt();
+        let normal = (origin + direction * t - self.center) / self.radius;
+
+        Some(self.surface_sample(normal, uniform_cone_pdf(cos_theta_max)))
+    }
+
+    fn pdf_from(&self, origin: &Vector3<f32>, direction: &Vector3<f32>) -> f32 {
+        let Some(cos_theta_max) = self.cone_cos_theta_max(origin) else {
+            let ray = Ray::new(*origin, *direction);
+            return match self.hit(&ray, 0.0, f32::INFINITY) {
+                Some(hit_info) => {
+                    self.area_to_solid_angle(origin, &hit_info.position)
+                }
+                None => 0.0,
+            };
+        };
+
+        let axis = (self.center - origin).normalize();
+        if direction.dot(&axis) < cos_theta_max {
+            return 0.0;
+        }
+        uniform_cone_pdf(cos_theta_max)
+    }
 }
@@ -0,0 +1,233 @@
+use std::f32::consts::TAU;
+
+use na::{Vector2, Vector3};
+
+use super::{
+    frame::{azimuth, azimuth_tangent, radial, Frame},
+    Hit, HitInfo, Interval, SampleSurface, SurfaceSample,
+};
+use crate::{
+    math::{solve_quadratic, solve_quartic},
+    sampler::Footprint,
+    world::{
+        aabb::{Aabb, Bounded},
+        material::Material,
+        ray::Ray,
+    },
+};
+
+/// Ring around `axis` through `center`: the points at `minor_radius` from
+/// the circle of `major_radius`.
+///
+/// `u` runs around the axis and `v` around the tube. Tori aren't sampled as
+/// lights.
+#[derive(Debug)]
+pub struct Torus {
+    frame: Frame,
+    major_radius: f32,
+    minor_radius: f32,
+    material: Material,
+}
+
+impl Torus {
+    pub fn new(
+        center: Vector3<f32>,
+        axis: Vector3<f32>,
+        major_radius: f32,
+        minor_radius: f32,
+        material: Material,
+    ) -> Self {
+        Self {
+            frame: Frame::new(center, axis.normalize()),
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+impl Torus {
+    /// Parametric distances at which the local `ray` crosses the surface,
+    /// in ascending order, skipping those before `t_min` and after `t_max`
+    /// when they are outside the bounding sphere.
+    fn roots(&self, local: &Ray, t_min: f32, t_max: f32) -> ([f32; 4], usize) {
+        let direction = local.direction();
+        let mut roots = [0.0; 4];
+
+        // Solving from where the ray enters the bounding sphere keeps the
+        // quartic well conditioned for distant origins.
+        let bound = self.major_radius + self.minor_radius;
+        let Some((enter, exit)) = solve_quadratic(
+            direction.magnitude_squared(),
+            2.0 * local.origin().dot(&direction),
+            local.origin().magnitude_squared() - bound * bound,
+        ) else {
+            return (roots, 0);
+        };
+        if exit < t_min || t_max < enter {
+            return (roots, 0);
+        }
+        let start = enter.max(t_min);
+
+        let o = local.at(start).cast::<f64>();
+        let d = direction.cast::<f64>();
+        let (major, minor) =
+            (self.major_radius as f64, self.minor_radius as f64);
+        // (|p|² + R² - r²)² = 4 R² (x² + y²) along p = o + t d.
+        let dd = d.magnitude_squared();
+        let od = o.dot(&d);
+        let k = o.magnitude_squared() + major * major - minor * minor;
+        let four_r2 = 4.0 * major * major;
+        let (offsets, count) = solve_quartic(
+            dd * dd,
+            4.0 * dd * od,
+            2.0 * dd * k + 4.0 * od * od - four_r2 * (d.x * d.x + d.y * d.y),
+            4.0 * k * od - 2.0 * four_r2 * (o.x * d.x + o.y * d.y),
+            k * k - four_r2 * (o.x * o.x + o.y * o.y),
+        );
+        for (root, offset) in roots.iter_mut().zip(&offsets[..count]) {
+            *root = start + *offset as f32;
+        }
+        (roots, count)
+    }
+
+    fn hit_info(&self, ray: &Ray, local: &Ray, t: f32) -> HitInfo<'_> {
+        let point = local.at(t);
+        let ring = self.major_radius * point.xy().normalize();
+        let normal = point - Vector3::new(ring.x, ring.y, 0.0);
+        let tube = fast_math::atan2(
+            normal.z,
+            point.xy().magnitude() - self.major_radius,
+        );
+
+        // Around the tube, the normal turned a quarter towards the top.
+        let outwards = radial(&point);
+        let around_tube =
+            normal.dot(&outwards) * Vector3::z() - normal.z * outwards;
+
+        let position = ray.at(t);
+        HitInfo {
+            t,
+            position,
+            local_position: position,
+            normal: self.frame.vector_to_world(&normal).normalize(),
+            tangent: self.frame.vector_to_world(&azimuth_tangent(&point)),
+            bitangent: self.frame.vector_to_world(&(TAU * around_tube)),
+            u: azimuth(&point),
+            v: (tube / TAU).rem_euclid(1.0),
+            footprint: Footprint::default(),
+            material: &self.material,
+        }
+    }
+}
+
+impl Hit for Torus {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        let local = self.frame.ray_to_local(ray);
+        let (roots, count) = self.roots(&local, t_min, t_max);
+        let t = roots[..count]
+            .iter()
+            .copied()
+            .find(|t| (t_min..=t_max).contains(t))?;
+        Some(self.hit_info(ray, &local, t))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        let local = self.frame.ray_to_local(ray);
+        let (roots, count) =
+            self.roots(&local, f32::NEG_INFINITY, f32::INFINITY);
+        // The line alternately enters and leaves, a lone root left over is
+        // a grazing touch.
+        roots[..count]
+            .chunks_exact(2)
+            .map(|pair| {
+                Interval {
+                    enter: self.hit_info(ray, &local, pair[0]),
+                    exit: self.hit_info(ray, &local, pair[1]),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Bounded for Torus {
+    fn bounding_box(&self) -> Option<Aabb> {
+        let ring = self.frame.disk_bounds(0.0, self.major_radius);
+        let reach = Vector3::repeat(self.minor_radius);
+        Some(Aabb::new(ring.min - reach, ring.max + reach))
+    }
+}
+
+/// Tori aren't sampled, see [`Torus`].
+impl SampleSurface for Torus {
+    fn area(&self) -> f32 {
+        0.0
+    }
+
+    fn sample_area(&self, _u: Vector2<f32>) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn sample_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        None
+    }
+
+    fn pdf_from(
+        &self,
+        _origin: &Vector3<f32>,
+        _direction: &Vector3<f32>,
+    ) -> f32 {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    use super::*;
+    use crate::world::object::test_util::{
+        assert_surface, diffuse, hit_towards,
+    };
+
+    fn torus() -> Torus {
+        Torus::new(Vector3::zeros(), Vector3::z(), 2.0, 0.5, diffuse())
+    }
+
+    #[test]
+    fn surface_is_parametrized_by_its_tangents() {
+        let torus = torus();
+        // An eighth of the way around the tube, above the outer equator.
+        let outwards = Vector3::new(0.6, 0.8, 0.0);
+        let normal = (outwards + Vector3::z()) * FRAC_1_SQRT_2;
+        let target = outwards * 2.0 + normal * 0.5;
+        assert_surface(&torus, target + normal * 3.0, target, normal);
+        let hit_info = hit_towards(&torus, target + normal, target);
+        assert!((hit_info.v - 0.125).abs() < 1e-4);
+    }
+
+    #[test]
+    fn inner_side_faces_the_axis() {
+        let torus = torus();
+        let target = Vector3::new(1.5, 0.0, 0.0);
+        let hit_info = hit_towards(&torus, Vector3::zeros(), target);
+        assert!((hit_info.normal + Vector3::x()).magnitude() < 1e-4);
+        assert!((hit_info.v - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn hole_is_missed() {
+        let torus = torus();
+        let ray = Ray::new(Vector3::z() * 5.0, -Vector3::z());
+        assert!(torus.hit(&ray, 0.0, f32::INFINITY).is_none());
+    }
+}
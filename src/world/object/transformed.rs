@@ -0,0 +1,258 @@
+use std::sync::Arc;
+
+use na::{Matrix3, Matrix4, Point3, Vector2, Vector3};
+
+use super::{Hit, HitInfo, Interval, Object, SampleSurface, SurfaceSample};
+use crate::world::{
+    aabb::{Aabb, Bounded},
+    material::Material,
+    ray::Ray,
+};
+
+/// Relative tolerance within which a transform counts as a similarity.
+const SIMILARITY_EPSILON: f32 = 1e-4;
+
+/// Shared object placed in the world by an affine transform, so one piece of
+/// geometry can be instanced many times and spheres can become ellipsoids.
+///
+/// Emitters can only be sampled as lights when the transform is a similarity
+/// (rotation, reflection, uniform scale and translation), which keeps uniform
+/// area sampling uniform and preserves solid angles. Other emitters are only
+/// found by rays hitting them.
+#[derive(Debug)]
+pub struct Transformed {
+    object: Arc<Object>,
+    matrix: Matrix4<f32>,
+    inverse: Matrix4<f32>,
+    /// Scale factor of the transform if it is a similarity.
+    uniform_scale: Option<f32>,
+}
+
+impl Transformed {
+    /// Panics if `matrix` isn't invertible.
+    pub fn new(object: Arc<Object>, matrix: Matrix4<f32>) -> Self {
+        let inverse = matrix
+            .try_inverse()
+            .expect("object transform needs to be invertible");
+        Self {
+            object,
+            uniform_scale: uniform_scale(&linear_part(&matrix)),
+            matrix,
+            inverse,
+        }
+    }
+
+    #[inline(always)]
+    pub fn object(&self) -> &Arc<Object> {
+        &self.object
+    }
+
+    #[inline(always)]
+    pub fn matrix(&self) -> &Matrix4<f32> {
+        &self.matrix
+    }
+
+    /// Places the object anew, e.g. for the next frame of an animation.
+    /// Panics if `matrix` isn't invertible.
+    pub fn set_matrix(&mut self, matrix: Matrix4<f32>) {
+        *self = Self::new(self.object.clone(), matrix);
+    }
+
+    #[inline(always)]
+    pub fn material(&self) -> &Material {
+        self.object.material()
+    }
+
+    #[inline(always)]
+    pub fn is_medium(&self) -> bool {
+        self.object.is_medium()
+    }
+
+    #[inline(always)]
+    pub fn is_closed(&self) -> bool {
+        self.object.is_closed()
+    }
+
+    #[inline(always)]
+    pub fn is_sampleable(&self) -> bool {
+        self.uniform_scale.is_some() && self.object.is_sampleable()
+    }
+
+    pub fn transmittance(&self, ray: &Ray, t_min: f32, t_max: f32) -> f32 {
+        self.object
+            .transmittance(&to_object(ray, &self.inverse), t_min, t_max)
+    }
+
+    #[inline(always)]
+    fn to_world(&self, sample: SurfaceSample) -> SurfaceSample {
+        SurfaceSample {
+            position: transform_point(&self.matrix, &sample.position),
+            normal: transform_normal(&self.inverse, &sample.normal),
+            ..sample
+        }
+    }
+}
+
+/// Upper left 3x3 block of an affine matrix.
+#[inline(always)]
+fn linear_part(matrix: &Matrix4<f32>) -> Matrix3<f32> {
+    matrix.fixed_view::<3, 3>(0, 0).into()
+}
+
+/// Scale factor of `linear` if it is a uniformly scaled orthogonal matrix.
+fn uniform_scale(linear: &Matrix3<f32>) -> Option<f32> {
+    let gram = linear.transpose() * linear;
+    let scale_squared = gram.trace() / 3.0;
+    let deviation = (gram - Matrix3::from_diagonal_element(scale_squared))
+        .abs()
+        .max();
+    (scale_squared > 0.0 && deviation <= SIMILARITY_EPSILON * scale_squared)
+        .then(|| scale_squared.sqrt())
+}
+
+#[inline(always)]
+fn transform_point(
+    matrix: &Matrix4<f32>,
+    point: &Vector3<f32>,
+) -> Vector3<f32> {
+    matrix.transform_point(&Point3::from(*point)).coords
+}
+
+/// Transforms a normal by the inverse transpose, given the inverse.
+#[inline(always)]
+fn transform_normal(
+    inverse: &Matrix4<f32>,
+    normal: &Vector3<f32>,
+) -> Vector3<f32> {
+    (linear_part(inverse).transpose() * normal).normalize()
+}
+
+/// `ray` in object space, given the object to world inverse.
+#[inline(always)]
+pub(super) fn to_object(ray: &Ray, inverse: &Matrix4<f32>) -> Ray {
+    Ray::with_time(
+        transform_point(inverse, &ray.origin()),
+        inverse.transform_vector(&ray.direction()),
+        ray.time(),
+    )
+}
+
+/// Hits `object` with `ray` taken into its space by `inverse`, and brings
+/// the hit back to world space with `matrix`.
+pub(super) fn hit_transformed<'a>(
+    object: &'a Object,
+    matrix: &Matrix4<f32>,
+    inverse: &Matrix4<f32>,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitInfo<'a>> {
+    object
+        .hit(&to_object(ray, inverse), t_min, t_max)
+        .map(|hit_info| hit_to_world(hit_info, matrix, inverse))
+}
+
+/// Like [`hit_transformed`], for the intervals of a closed `object`.
+pub(super) fn intervals_transformed<'a>(
+    object: &'a Object,
+    matrix: &Matrix4<f32>,
+    inverse: &Matrix4<f32>,
+    ray: &Ray,
+) -> Vec<Interval<'a>> {
+    let mut intervals = object.intervals(&to_object(ray, inverse));
+    for interval in &mut intervals {
+        interval.enter = hit_to_world(interval.enter, matrix, inverse);
+        interval.exit = hit_to_world(interval.exit, matrix, inverse);
+    }
+    intervals
+}
+
+/// Brings a hit found in object space back to world space. Affine maps keep
+/// ray parameters, so `t` carries over unchanged.
+#[inline(always)]
+fn hit_to_world<'a>(
+    hit_info: HitInfo<'a>,
+    matrix: &Matrix4<f32>,
+    inverse: &Matrix4<f32>,
+) -> HitInfo<'a> {
+    HitInfo {
+        position: transform_point(matrix, &hit_info.position),
+        normal: transform_normal(inverse, &hit_info.normal),
+        tangent: linear_part(matrix) * hit_info.tangent,
+        bitangent: linear_part(matrix) * hit_info.bitangent,
+        ..hit_info
+    }
+}
+
+/// Bounds of `bounds` placed by `matrix`.
+pub(super) fn transform_bounds(bounds: &Aabb, matrix: &Matrix4<f32>) -> Aabb {
+    bounds.corners().iter().fold(Aabb::empty(), |aabb, corner| {
+        aabb.include(&transform_point(matrix, corner))
+    })
+}
+
+impl Hit for Transformed {
+    #[inline(always)]
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitInfo<'_>> {
+        hit_transformed(
+            &self.object,
+            &self.matrix,
+            &self.inverse,
+            ray,
+            t_min,
+            t_max,
+        )
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        intervals_transformed(&self.object, &self.matrix, &self.inverse, ray)
+    }
+}
+
+impl Bounded for Transformed {
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(transform_bounds(&self.object.bounding_box()?, &self.matrix))
+    }
+}
+
+impl SampleSurface for Transformed {
+    fn area(&self) -> f32 {
+        match self.uniform_scale {
+            Some(scale) => scale * scale * self.object.area(),
+            None => 0.0,
+        }
+    }
+
+    fn sample_area(&self, u: Vector2<f32>) -> Option<SurfaceSample> {
+        let scale = self.uniform_scale?;
+        let sample = self.object.sample_area(u)?;
+        Some(SurfaceSample {
+            pdf: sample.pdf / (scale * scale),
+            ..self.to_world(sample)
+        })
+    }
+
+    fn sample_from(
+        &self,
+        origin: &Vector3<f32>,
+        u: Vector2<f32>,
+    ) -> Option<SurfaceSample> {
+        self.uniform_scale?;
+        // Similarities preserve angles, so the solid angle density carries
+        // over unchanged.
+        let sample = self
+            .object
+            .sample_from(&transform_point(&self.inverse, origin), u)?;
+        Some(self.to_world(sample))
+    }
+
+    fn pdf_from(&self, origin: &Vector3<f32>, direction: &Vector3<f32>) -> f32 {
+        if self.uniform_scale.is_none() {
+            return 0.0;
+        }
+        self.object.pdf_from(
+            &transform_point(&self.inverse, origin),
+            &self.inverse.transform_vector(direction).normalize(),
+        )
+    }
+}
@@ -1,17 +1,55 @@
 use na::Vector3;
 
-use super::object::Hit;
-use crate::{color::Color, world::material::Scatter};
+/// Origins and directions of the rays through the neighbouring camera
+/// samples along each axis of the image, which tell how large the area seen
+/// through a sample is where a ray hits.
+#[derive(Debug, Clone, Copy)]
+pub struct RayDifferentials {
+    pub x_origin: Vector3<f32>,
+    pub x_direction: Vector3<f32>,
+    pub y_origin: Vector3<f32>,
+    pub y_direction: Vector3<f32>,
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct Ray {
     origin: Vector3<f32>,
     direction: Vector3<f32>,
+    /// Instant within the shutter interval the ray travels at, which
+    /// positions moving objects.
+    time: f32,
+    /// Only camera rays carry differentials, see
+    /// [`Ray::with_differentials`].
+    differentials: Option<RayDifferentials>,
 }
 
 impl Ray {
     pub fn new(origin: Vector3<f32>, direction: Vector3<f32>) -> Self {
-        Self { origin, direction }
+        Self::with_time(origin, direction, 0.0)
+    }
+
+    pub fn with_time(
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+        time: f32,
+    ) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+            differentials: None,
+        }
+    }
+
+    /// Attaches the rays through the neighbouring samples, which lets hits
+    /// estimate a [`Footprint`](crate::sampler::Footprint) for texture
+    /// filtering.
+    pub fn with_differentials(
+        mut self,
+        differentials: RayDifferentials,
+    ) -> Self {
+        self.differentials = Some(differentials);
+        self
     }
 
     #[inline(always)]
@@ -25,31 +63,17 @@ impl Ray {
     }
 
     #[inline(always)]
-    pub fn at(&self, t: f32) -> Vector3<f32> {
-        self.origin + self.direction * t
+    pub fn time(&self) -> f32 {
+        self.time
     }
 
-    pub fn trace(&self, obj: &impl Hit, depth: u32, sky_color: Color) -> Color {
-        if depth == 0 {
-            return Color::black();
-        }
+    #[inline(always)]
+    pub fn differentials(&self) -> Option<RayDifferentials> {
+        self.differentials
+    }
 
-        if let Some(hit_info) = obj.hit(self, 0.001, f32::INFINITY) {
-            if let Some(scatter_info) =
-                hit_info.material.scatter(&self, &hit_info)
-            {
-                return scatter_info.attenuation *
-                    scatter_info.scattered_ray.trace(
-                        obj,
-                        depth - 1,
-                        sky_color,
-                    );
-            } else {
-                return Color::black();
-            }
-        } else {
-            let a = 0.5 * (self.direction.y + 1.0);
-            (1.0 - a) * Color::gray(1.0) + a * sky_color
-        }
+    #[inline(always)]
+    pub fn at(&self, t: f32) -> Vector3<f32> {
+        self.origin + self.direction * t
     }
 }
@@ -0,0 +1,334 @@
+use std::{fs, io, ops::ControlFlow, path::Path};
+
+use na::Vector3;
+
+use super::{aabb::Aabb, ray::Ray};
+
+/// Scalar values on a regular grid of voxels spanning `bounds`, such as the
+/// density or temperature of a volume.
+#[derive(Debug)]
+pub struct VoxelGrid {
+    size: Vector3<usize>,
+    /// Values with x varying fastest, then y, then z.
+    values: Vec<f32>,
+    bounds: Aabb,
+}
+
+impl VoxelGrid {
+    /// Panics if the grid is empty or `values` doesn't hold one value per
+    /// voxel.
+    pub fn new(size: Vector3<usize>, values: Vec<f32>, bounds: Aabb) -> Self {
+        assert!(size.min() > 0, "voxel grid of size {size:?} is empty");
+        assert_eq!(
+            values.len(),
+            size.x * size.y * size.z,
+            "voxel grid of size {size:?} needs one value per voxel"
+        );
+        Self {
+            size,
+            values,
+            bounds,
+        }
+    }
+
+    /// Loads a raw voxel file and places it in `bounds`.
+    ///
+    /// The file holds the grid size as three little-endian `u32`s (x, y, z),
+    /// followed by one little-endian `f32` per voxel with x varying fastest.
+    pub fn load(path: impl AsRef<Path>, bounds: Aabb) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let invalid =
+            |message| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let (header, data) = bytes
+            .split_at_checked(12)
+            .ok_or_else(|| invalid("voxel file is missing its header"))?;
+        let mut size = [0; 3];
+        for (axis, chunk) in size.iter_mut().zip(header.chunks_exact(4)) {
+            *axis = u32::from_le_bytes(chunk.try_into().unwrap()) as usize;
+        }
+        if size.contains(&0) {
+            return Err(invalid("voxel grid is empty"));
+        }
+        let count = size[0]
+            .checked_mul(size[1])
+            .and_then(|count| count.checked_mul(size[2]))
+            .ok_or_else(|| invalid("voxel grid is too large"))?;
+        if data.len() != count * 4 {
+            return Err(invalid("voxel file size doesn't match its header"));
+        }
+
+        let values = data
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+
+        Ok(Self::new(Vector3::from(size), values, bounds))
+    }
+
+    #[inline(always)]
+    pub fn bounds(&self) -> &Aabb {
+        &self.bounds
+    }
+
+    #[inline(always)]
+    pub fn size(&self) -> Vector3<usize> {
+        self.size
+    }
+
+    /// Value of the voxel at `(x, y, z)`.
+    #[inline(always)]
+    pub fn voxel(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.values[(z * self.size.y + y) * self.size.x + x]
+    }
+
+    /// Trilinear interpolation between voxel centers at `position`, zero
+    /// outside the bounds.
+    pub fn sample(&self, position: &Vector3<f32>) -> f32 {
+        let local = (position - self.bounds.min)
+            .component_div(&self.bounds.extent())
+            .component_mul(&self.size.cast::<f32>());
+        if (0..3)
+            .any(|axis| !(0.0..=self.size[axis] as f32).contains(&local[axis]))
+        {
+            return 0.0;
+        }
+
+        let local = local.add_scalar(-0.5);
+        let base = local.map(f32::floor);
+        let frac = local - base;
+        let index = |axis: usize, offset: f32| {
+            (base[axis] + offset).clamp(0.0, (self.size[axis] - 1) as f32)
+                as usize
+        };
+
+        let mut value = 0.0;
+        for corner in 0..8 {
+            let offset =
+                Vector3::new(corner & 1, (corner >> 1) & 1, corner >> 2)
+                    .cast::<f32>();
+            let weight = (0..3)
+                .map(|axis| {
+                    if offset[axis] == 1.0 {
+                        frac[axis]
+                    } else {
+                        1.0 - frac[axis]
+                    }
+                })
+                .product::<f32>();
+            value += weight *
+                self.voxel(
+                    index(0, offset.x),
+                    index(1, offset.y),
+                    index(2, offset.z),
+                );
+        }
+        value
+    }
+}
+
+/// Coarse grid over a [`VoxelGrid`] storing, per macro cell, an upper bound
+/// of the interpolated values inside it. Tracking through a volume can then
+/// take long steps through thin regions and skip empty ones entirely.
+#[derive(Debug)]
+pub struct MajorantGrid {
+    size: Vector3<usize>,
+    majorants: Vec<f32>,
+    bounds: Aabb,
+    /// Size of a macro cell in world units. The last cell along an axis
+    /// sticks out of the bounds when the voxels don't divide evenly.
+    cell_extent: Vector3<f32>,
+}
+
+impl MajorantGrid {
+    /// Builds macro cells of `cell_size` voxels along each axis.
+    pub fn new(grid: &VoxelGrid, cell_size: usize) -> Self {
+        let cell_size = cell_size.max(1);
+        let voxels = grid.size();
+        let size = voxels.map(|n| n.div_ceil(cell_size).max(1));
+        let mut majorants = vec![0.0f32; size.x * size.y * size.z];
+
+        for z in 0..voxels.z {
+            for y in 0..voxels.y {
+                for x in 0..voxels.x {
+                    let value = grid.voxel(x, y, z);
+                    // Interpolation spreads a voxel up to the centers of its
+                    // neighbours, so it bounds the cells those touch too.
+                    let range = |v: usize, axis: usize| {
+                        let low = v.saturating_sub(1) / cell_size;
+                        let high = ((v + 1) / cell_size).min(size[axis] - 1);
+                        low..=high
+                    };
+                    for cz in range(z, 2) {
+                        for cy in range(y, 1) {
+                            for cx in range(x, 0) {
+                                let cell = &mut majorants
+                                    [(cz * size.y + cy) * size.x + cx];
+                                *cell = cell.max(value);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Self {
+            size,
+            majorants,
+            bounds: *grid.bounds(),
+            cell_extent: grid
+                .bounds()
+                .extent()
+                .component_div(&voxels.cast::<f32>()) *
+                cell_size as f32,
+        }
+    }
+
+    /// Walks the macro cells the ray crosses within `[t_min, t_max]` in
+    /// order, calling `f` with the parametric range inside each cell and its
+    /// majorant until it breaks.
+    pub fn traverse<T>(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        mut f: impl FnMut(f32, f32, f32) -> ControlFlow<T>,
+    ) -> Option<T> {
+        let (t_enter, t_exit) = self.bounds.overlap(ray, t_min, t_max)?;
+        // Degenerate rays with NaN components pass the slab test unclipped.
+        if !t_exit.is_finite() {
+            return None;
+        }
+
+        let to_grid = self.cell_extent.map(f32::recip);
+        let origin =
+            (ray.at(t_enter) - self.bounds.min).component_mul(&to_grid);
+        let direction = ray.direction().component_mul(&to_grid);
+
+        let mut cell = Vector3::zeros();
+        let mut step = Vector3::zeros();
+        let mut next_t = Vector3::repeat(f32::INFINITY);
+        let mut delta_t = Vector3::repeat(f32::INFINITY);
+        for axis in 0..3 {
+            let last = self.size[axis] - 1;
+            cell[axis] = (origin[axis].floor().max(0.0) as usize).min(last);
+            let boundary = if direction[axis] > 0.0 {
+                step[axis] = 1;
+                cell[axis] as f32 + 1.0
+            } else if direction[axis] < 0.0 {
+                step[axis] = -1;
+                cell[axis] as f32
+            } else {
+                continue;
+            };
+            next_t[axis] =
+                t_enter + (boundary - origin[axis]) / direction[axis];
+            delta_t[axis] = direction[axis].abs().recip();
+        }
+
+        let mut t = t_enter;
+        loop {
+            let axis = next_t.imin();
+            let t_next = next_t[axis].min(t_exit);
+            let majorant = self.majorants
+                [(cell.z * self.size.y + cell.y) * self.size.x + cell.x];
+            if let ControlFlow::Break(result) = f(t, t_next, majorant) {
+                return Some(result);
+            }
+            if t_next >= t_exit {
+                return None;
+            }
+
+            t = t_next;
+            let moved = cell[axis] as isize + step[axis];
+            if moved < 0 || moved as usize >= self.size[axis] {
+                return None;
+            }
+            cell[axis] = moved as usize;
+            next_t[axis] += delta_t[axis];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_bounds() -> Aabb {
+        Aabb::new(Vector3::zeros(), Vector3::repeat(1.0))
+    }
+
+    /// Writes `bytes` to a file of its own and loads it.
+    fn load_bytes(name: &str, bytes: &[u8]) -> io::Result<VoxelGrid> {
+        let path = std::env::temp_dir()
+            .join(format!("voxel_{name}_{}.raw", std::process::id()));
+        fs::write(&path, bytes).unwrap();
+        let grid = VoxelGrid::load(&path, unit_bounds());
+        fs::remove_file(&path).unwrap();
+        grid
+    }
+
+    fn header(size: [u32; 3]) -> Vec<u8> {
+        size.iter().flat_map(|n| n.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn loads_voxels_in_x_y_z_order() {
+        let mut bytes = header([2, 3, 1]);
+        bytes.extend((0..6).flat_map(|i| (i as f32).to_le_bytes()));
+        let grid = load_bytes("good", &bytes).unwrap();
+        assert_eq!(grid.size(), Vector3::new(2, 3, 1));
+        assert_eq!(grid.voxel(1, 0, 0), 1.0);
+        assert_eq!(grid.voxel(0, 2, 0), 4.0);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let error = |name: &str, bytes: &[u8]| {
+            load_bytes(name, bytes).unwrap_err().kind()
+        };
+        assert_eq!(error("short", &[1, 0, 0, 0]), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error("empty", &header([4, 0, 4])),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            error("huge", &header([u32::MAX; 3])),
+            io::ErrorKind::InvalidData
+        );
+        let mut truncated = header([2, 2, 2]);
+        truncated.extend([0; 28]);
+        assert_eq!(error("truncated", &truncated), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn majorants_bound_the_interpolated_values() {
+        let mut rng = fastrand::Rng::with_seed(7);
+        let size = Vector3::new(9, 5, 7);
+        // Sparse spikes, so that the bound has to account for neighbours.
+        let values = (0..size.product())
+            .map(|_| {
+                if rng.f32() < 0.1 {
+                    rng.f32() * 10.0
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let grid = VoxelGrid::new(size, values, unit_bounds());
+        let majorants = MajorantGrid::new(&grid, 4);
+
+        for _ in 0..100_000 {
+            let position = Vector3::new(rng.f32(), rng.f32(), rng.f32());
+            let cell = position
+                .component_div(&majorants.cell_extent)
+                .map(|x| x as usize)
+                .zip_map(&majorants.size, |x, n| x.min(n - 1));
+            let majorant = majorants.majorants[(cell.z * majorants.size.y +
+                cell.y) *
+                majorants.size.x +
+                cell.x];
+            assert!(grid.sample(&position) <= majorant, "{position:?}");
+        }
+    }
+}